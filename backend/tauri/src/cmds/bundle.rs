@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+
+use crate::config::{IVerge, Profiles, profile::bundle::ProfilesBundle};
+
+#[derive(Debug, Args)]
+pub struct ExportOpts {
+    /// the path of the bundle to create
+    path: PathBuf,
+    /// do not include the app settings
+    #[arg(long, default_value = "false")]
+    no_verge: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportOpts {
+    /// the path of the bundle to import
+    path: PathBuf,
+    /// do not apply the app settings in the bundle
+    #[arg(long, default_value = "false")]
+    no_verge: bool,
+}
+
+pub fn export(args: &ExportOpts) {
+    let profiles = Profiles::new();
+    let verge = IVerge::new();
+    let result = ProfilesBundle::new(&profiles, (!args.no_verge).then_some(&verge))
+        .and_then(|bundle| bundle.write_to(&args.path).map(|_| bundle.manifest));
+    match result {
+        Ok(manifest) => {
+            println!(
+                "{} {} profiles into {}",
+                "Exported".green(),
+                manifest.profiles,
+                args.path.display()
+            );
+        }
+        Err(err) => {
+            eprintln!("{} {err:?}", "Failed to export the profiles:".red());
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn import(args: &ImportOpts) {
+    let result = ProfilesBundle::read_from(&args.path).and_then(|bundle| {
+        let verge = bundle.verge.clone().filter(|_| !args.no_verge);
+        let mut profiles = Profiles::new();
+        let report = profiles.import_bundle(bundle)?;
+        if let Some(patch) = verge {
+            let mut verge = IVerge::new();
+            verge.patch_config(patch);
+            verge.save_file()?;
+        }
+        Ok(report)
+    });
+    match result {
+        Ok(report) => {
+            println!("{} {} profiles", "Imported".green(), report.imported.len());
+            for (old, new) in report.remapped.iter() {
+                println!("  {} {old} -> {new}", "remapped".yellow());
+            }
+        }
+        Err(err) => {
+            eprintln!("{} {err:?}", "Failed to import the bundle:".red());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use clap::Args;
+use colored::Colorize;
+
+use crate::core::clash::rule_explain::{self, RuleMatchRequest, RuleNetwork};
+
+#[derive(Debug, Args)]
+pub struct ExplainOpts {
+    /// the domain or the ip of the destination
+    host: String,
+    /// the port of the destination
+    #[arg(long)]
+    port: Option<u16>,
+    /// the name or the path of the process
+    #[arg(long)]
+    process: Option<String>,
+    /// `tcp` or `udp`
+    #[arg(long)]
+    network: Option<RuleNetwork>,
+    /// print the explanation as json
+    #[arg(long, default_value = "false")]
+    json: bool,
+}
+
+pub fn explain(args: &ExplainOpts) {
+    let request = RuleMatchRequest {
+        host: args.host.clone(),
+        port: args.port,
+        process: args.process.clone(),
+        network: args.network,
+    };
+    let explanation = match tauri::async_runtime::block_on(rule_explain::explain(request)) {
+        Ok(explanation) => explanation,
+        Err(err) => {
+            eprintln!("{} {err:?}", "Failed to explain the rules:".red());
+            std::process::exit(1);
+        }
+    };
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&explanation).unwrap());
+        return;
+    }
+
+    println!("{} {}", "Mode:".bold(), explanation.mode);
+    if let Some(ip) = &explanation.ip {
+        println!("{} {ip}", "Resolved:".bold());
+    }
+    for skipped in explanation.skipped.iter() {
+        println!(
+            "  {} {}[{}] {} ({})",
+            "skipped".yellow(),
+            skipped.step.rules,
+            skipped.step.index,
+            skipped.step.rule,
+            skipped.reason
+        );
+    }
+    if explanation.matched.is_empty() {
+        println!("{} none", "Matched:".bold());
+    }
+    for step in explanation.matched.iter() {
+        println!(
+            "{} {}[{}] {}",
+            "Matched:".bold(),
+            step.rules,
+            step.index,
+            step.rule.green()
+        );
+    }
+    println!(
+        "{} {}",
+        "Chain:".bold(),
+        explanation.chain.join(" -> ").cyan()
+    );
+}
@@ -7,6 +7,8 @@ use migrate::MigrateOpts;
 use nyanpasu_egui::widget::StatisticWidgetVariant;
 use tauri::utils::platform::current_exe;
 
+mod bundle;
+mod explain;
 mod migrate;
 
 #[derive(Parser, Debug)]
@@ -40,6 +42,12 @@ enum Commands {
     PanicDialog { message: String },
     /// Launch the Widget with the specified name.
     StatisticWidget { variant: StatisticWidgetVariant },
+    /// Export the profiles and the portable settings into a bundle.
+    ExportProfiles(bundle::ExportOpts),
+    /// Import a profiles bundle, the app should be closed while importing.
+    ImportProfiles(bundle::ImportOpts),
+    /// Explain which rule a destination matches, and the proxies it goes through.
+    ExplainRule(explain::ExplainOpts),
 }
 
 struct DelayedExitGuard;
@@ -98,6 +106,15 @@ pub fn parse() -> anyhow::Result<()> {
                 nyanpasu_egui::widget::start_statistic_widget(*variant)
                     .expect("Failed to start statistic widget");
             }
+            Commands::ExportProfiles(opts) => {
+                bundle::export(opts);
+            }
+            Commands::ImportProfiles(opts) => {
+                bundle::import(opts);
+            }
+            Commands::ExplainRule(opts) => {
+                explain::explain(opts);
+            }
         }
         drop(guard);
         std::process::exit(0);
@@ -8,7 +8,7 @@ pub use self::{
     clash::*,
     core::*,
     draft::*,
-    profile::{item::*, profiles::*},
+    profile::{item::*, profiles::*, schedule::*},
     runtime::*,
 };
 
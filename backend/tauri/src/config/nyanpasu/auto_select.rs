@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Drive the `Selector` groups by the latency tests of the app,
+/// instead of the `url-test` and `fallback` groups of the core
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct AutoSelectConfig {
+    pub enable: bool,
+    /// the policies of the groups, a group should have one policy at most
+    pub policies: Vec<AutoSelectPolicy>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct AutoSelectPolicy {
+    /// the name of the `Selector` group
+    pub group: String,
+    pub strategy: AutoSelectStrategy,
+    /// how often the group is tested, in seconds
+    pub interval: u64,
+    /// how many latest tests of each proxy are counted
+    pub window: usize,
+    /// the url to test, the default one of the latency tests if empty
+    pub url: Option<String>,
+}
+
+impl Default for AutoSelectPolicy {
+    fn default() -> Self {
+        Self {
+            group: String::new(),
+            strategy: AutoSelectStrategy::default(),
+            interval: 5 * 60,
+            window: 5,
+            url: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutoSelectStrategy {
+    /// the lowest median latency over the window
+    #[default]
+    LowestLatency,
+    /// the lowest loss over the window, the lower latency wins a tie
+    LowestLoss,
+    /// keep the current proxy until its loss exceeds `max_loss`,
+    /// or another one is faster by more than `hysteresis` milliseconds
+    Sticky { hysteresis: u64, max_loss: f64 },
+    /// the lowest latency of the first region which has an available proxy,
+    /// the regions are the ISO codes, e.g. `US`, matched against the regions detected in the names
+    PreferredRegion { regions: Vec<String> },
+}
+
+impl super::IVerge {
+    pub fn get_auto_select(&self) -> AutoSelectConfig {
+        self.auto_select.clone().unwrap_or_default()
+    }
+}
@@ -15,12 +15,22 @@ pub fn is_hex_color(color: &str) -> bool {
     color[1..].chars().all(|c| c.is_ascii_hexdigit())
 }
 
+mod auto_select;
 mod clash_strategy;
 pub mod logging;
+mod network_rules;
+mod provider_mirror;
+mod subscription_alert;
+mod traffic_history;
 mod widget;
 
 pub use self::clash_strategy::{ClashStrategy, ExternalControllerPortStrategy};
+pub use auto_select::{AutoSelectConfig, AutoSelectPolicy, AutoSelectStrategy};
 pub use logging::LoggingLevel;
+pub use network_rules::{ClashMode, NetworkCondition, NetworkRule, NetworkRulesConfig};
+pub use provider_mirror::{ProviderMirrorConfig, ProviderMirrorMode};
+pub use subscription_alert::SubscriptionAlertConfig;
+pub use traffic_history::TrafficHistoryConfig;
 pub use widget::NetworkStatisticWidgetConfig;
 
 // TODO: when support sing-box, remove this struct
@@ -281,6 +291,32 @@ pub struct IVerge {
     /// When enabled, shows proxy and TUN mode status as text next to the tray icon
     /// When disabled, only shows status via icon changes (prevents text display issues on Wayland)
     pub enable_tray_text: Option<bool>,
+
+    /// subscription quota and expiry notifications
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_alert: Option<SubscriptionAlertConfig>,
+
+    /// per-machine overrides of the profile variables, they take precedence over
+    /// the ones in the profiles and are never exported with the profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_variables: Option<indexmap::IndexMap<String, serde_yaml::Value>>,
+
+    /// download the http providers through the subscription client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_mirror: Option<ProviderMirrorConfig>,
+
+    /// switch the profiles and the clash mode by the network,
+    /// they are bound to this machine and never exported with the profiles
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_rules: Option<NetworkRulesConfig>,
+
+    /// the retention of the traffic history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traffic_history: Option<TrafficHistoryConfig>,
+
+    /// the app-side auto selection of the `Selector` groups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_select: Option<AutoSelectConfig>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize, Type)]
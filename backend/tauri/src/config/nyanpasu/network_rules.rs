@@ -0,0 +1,82 @@
+use crate::config::profile::item_type::ProfileUid;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::net::IpAddr;
+
+/// Choose the profiles and the clash mode by the network the machine is connected to
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct NetworkRulesConfig {
+    pub enable: bool,
+    /// how often the network is checked, in seconds,
+    /// the NetworkManager events on Linux trigger a check immediately
+    pub interval: u64,
+    /// the first rule whose conditions all match is applied
+    pub rules: Vec<NetworkRule>,
+}
+
+impl Default for NetworkRulesConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            interval: 15,
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+pub struct NetworkRule {
+    pub name: String,
+    /// all of them should match, a rule without conditions is a catch-all
+    #[serde(default)]
+    pub conditions: Vec<NetworkCondition>,
+    /// the profiles to activate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profiles: Option<Vec<ProfileUid>>,
+    /// the clash mode to switch to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<ClashMode>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NetworkCondition {
+    /// the SSID of the connected Wi-Fi
+    Ssid { ssid: String },
+    /// the MAC address of the default gateway, e.g. `aa:bb:cc:dd:ee:ff`
+    GatewayMac { mac: String },
+    /// the host name resolves, to `address` if it is given,
+    /// the fake-ip answers of the core are not counted
+    Resolves {
+        host: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        address: Option<IpAddr>,
+    },
+    /// the condition does not match
+    Not { condition: Box<NetworkCondition> },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Type)]
+#[serde(rename_all = "lowercase")]
+pub enum ClashMode {
+    Rule,
+    Global,
+    Direct,
+}
+
+impl ClashMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClashMode::Rule => "rule",
+            ClashMode::Global => "global",
+            ClashMode::Direct => "direct",
+        }
+    }
+}
+
+impl super::IVerge {
+    pub fn get_network_rules(&self) -> NetworkRulesConfig {
+        self.network_rules.clone().unwrap_or_default()
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// How the mirrored providers are handed to the core
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderMirrorMode {
+    /// keep `type: http`, and point the url to the internal http server,
+    /// so that the core keeps its own cache and refresh of the provider
+    #[default]
+    Server,
+    /// rewrite the provider to `type: file` with the path of the cached payload,
+    /// the core should be allowed to read the profiles dir (e.g. `SAFE_PATHS` of mihomo)
+    File,
+}
+
+/// Local mirrors of the http `proxy-providers` and `rule-providers`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct ProviderMirrorConfig {
+    /// whether to download the providers through the subscription client
+    pub enable: bool,
+    pub mode: ProviderMirrorMode,
+    /// the refresh interval in minutes of the providers without an `interval`
+    pub default_interval: u64,
+}
+
+impl Default for ProviderMirrorConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            mode: ProviderMirrorMode::default(),
+            default_interval: 12 * 60,
+        }
+    }
+}
+
+impl super::IVerge {
+    pub fn get_provider_mirror(&self) -> ProviderMirrorConfig {
+        self.provider_mirror.clone().unwrap_or_default()
+    }
+}
@@ -0,0 +1,37 @@
+use crate::config::profile::item_type::ProfileUid;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Thresholds for the subscription userinfo watcher
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct SubscriptionAlertConfig {
+    /// whether to watch the `subscription-userinfo` of the current profiles
+    pub enable: bool,
+    /// notify when the remaining traffic is lower than this percentage of the total
+    pub quota_threshold_percent: f64,
+    /// notify when the subscription expires within these days
+    pub expire_threshold_days: u64,
+    /// the profile to activate instead of an expired one
+    pub fallback_profile: Option<ProfileUid>,
+    /// switch to the fallback profile without asking
+    pub auto_switch_fallback: bool,
+}
+
+impl Default for SubscriptionAlertConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            quota_threshold_percent: 10.0,
+            expire_threshold_days: 3,
+            fallback_profile: None,
+            auto_switch_fallback: false,
+        }
+    }
+}
+
+impl super::IVerge {
+    pub fn get_subscription_alert(&self) -> SubscriptionAlertConfig {
+        self.subscription_alert.clone().unwrap_or_default()
+    }
+}
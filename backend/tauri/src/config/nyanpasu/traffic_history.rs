@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// Retention of the traffic history, counted in buckets per granularity
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Type)]
+#[serde(default)]
+pub struct TrafficHistoryConfig {
+    /// whether to record the traffic of the connections
+    pub enable: bool,
+    /// how many minute buckets are kept, only the total and the proxies are recorded by minute
+    pub minute_buckets: u64,
+    /// how many hour buckets are kept
+    pub hour_buckets: u64,
+    /// how many day buckets are kept
+    pub day_buckets: u64,
+}
+
+impl Default for TrafficHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            minute_buckets: 24 * 60,
+            hour_buckets: 30 * 24,
+            day_buckets: 365,
+        }
+    }
+}
+
+impl super::IVerge {
+    pub fn get_traffic_history(&self) -> TrafficHistoryConfig {
+        self.traffic_history.clone().unwrap_or_default()
+    }
+}
@@ -0,0 +1,470 @@
+//! A portable archive of the profiles, their files and the related app settings,
+//! used to move a complete setup between machines.
+//!
+//! ```text
+//! bundle.zip
+//! ├── manifest.json
+//! ├── profiles.yaml
+//! ├── verge.yaml      (optional)
+//! └── files/
+//!     └── <profile file>
+//! ```
+use super::{
+    item::{Profile, prelude::*},
+    item_type::ProfileUid,
+    profiles::Profiles,
+};
+use crate::{config::IVerge, consts::BUILD_INFO, utils::dirs};
+use anyhow::{Context, Result, anyhow, bail};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// bump it when the layout of the bundle changes
+pub const BUNDLE_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PROFILES_ENTRY: &str = "profiles.yaml";
+const VERGE_ENTRY: &str = "verge.yaml";
+const FILES_DIR: &str = "files";
+
+#[derive(Debug, Clone, Deserialize, Serialize, specta::Type)]
+pub struct BundleManifest {
+    /// the bundle format version
+    pub version: u32,
+    /// the app version which created the bundle
+    pub app_version: String,
+    pub created_at: i64,
+    /// the count of the profile items
+    pub profiles: usize,
+    /// whether the app settings are included
+    pub with_verge: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, specta::Type)]
+pub struct BundleImportReport {
+    /// the uids of the imported items, after remapping
+    pub imported: Vec<ProfileUid>,
+    /// the items renamed due to conflicts, old uid -> new uid
+    pub remapped: IndexMap<ProfileUid, ProfileUid>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProfilesBundle {
+    pub manifest: BundleManifest,
+    pub profiles: Profiles,
+    pub verge: Option<IVerge>,
+    /// file name -> content
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+/// the settings which make sense on another machine,
+/// window states, ports and system integrations are left out
+fn portable_verge(verge: &IVerge) -> IVerge {
+    IVerge {
+        clash_core: verge.clash_core,
+        enable_clash_fields: verge.enable_clash_fields,
+        enable_builtin_enhanced: verge.enable_builtin_enhanced,
+        default_latency_test: verge.default_latency_test.clone(),
+        break_when_proxy_change: verge.break_when_proxy_change,
+        break_when_profile_change: verge.break_when_profile_change,
+        break_when_mode_change: verge.break_when_mode_change,
+        subscription_alert: verge.subscription_alert.clone(),
+        provider_mirror: verge.provider_mirror.clone(),
+        ..IVerge::default()
+    }
+}
+
+/// the file name should not escape the profiles dir
+fn is_plain_file_name(file: &str) -> bool {
+    Path::new(file).file_name().is_some_and(|name| name == file)
+}
+
+/// write all the files or none of them, the written ones are removed if one fails
+fn write_files(files: &[(PathBuf, &[u8])]) -> Result<()> {
+    for (index, (path, content)) in files.iter().enumerate() {
+        if let Err(err) = fs_err::write(path, content) {
+            for (path, _) in files[..=index].iter() {
+                let _ = fs_err::remove_file(path);
+            }
+            return Err(err.into());
+        }
+    }
+    Ok(())
+}
+
+impl ProfilesBundle {
+    /// collect the profiles and their files
+    pub fn new(profiles: &Profiles, verge: Option<&IVerge>) -> Result<Self> {
+        let profiles_dir = dirs::app_profiles_dir()?;
+        let files = profiles
+            .items
+            .iter()
+            .map(|item| {
+                let content = fs_err::read(profiles_dir.join(item.file()))?;
+                Ok((item.file().to_string(), content))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        Ok(Self {
+            manifest: BundleManifest {
+                version: BUNDLE_VERSION,
+                app_version: BUILD_INFO.pkg_version.to_string(),
+                created_at: chrono::Local::now().timestamp(),
+                profiles: profiles.items.len(),
+                with_verge: verge.is_some(),
+            },
+            profiles: profiles.clone(),
+            verge: verge.map(portable_verge),
+            files,
+        })
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        let file = fs_err::File::create(path)?;
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+
+        zip.start_file(MANIFEST_ENTRY, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&self.manifest)?)?;
+        zip.start_file(PROFILES_ENTRY, options)?;
+        zip.write_all(serde_yaml::to_string(&self.profiles)?.as_bytes())?;
+        if let Some(verge) = &self.verge {
+            zip.start_file(VERGE_ENTRY, options)?;
+            zip.write_all(serde_yaml::to_string(verge)?.as_bytes())?;
+        }
+        for (name, content) in self.files.iter() {
+            zip.start_file(format!("{FILES_DIR}/{name}"), options)?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let file = fs_err::File::open(path)?;
+        let mut zip = zip::ZipArchive::new(file).context("not a valid bundle")?;
+        let mut read_entry = |name: &str| -> Result<Option<Vec<u8>>> {
+            let mut entry = match zip.by_name(name) {
+                Ok(entry) => entry,
+                Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            let mut buf = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut buf)?;
+            Ok(Some(buf))
+        };
+
+        let manifest: BundleManifest = serde_json::from_slice(
+            &read_entry(MANIFEST_ENTRY)?.ok_or(anyhow!("the bundle has no manifest"))?,
+        )?;
+        if manifest.version > BUNDLE_VERSION {
+            bail!(
+                "the bundle version {} is not supported, please upgrade the app",
+                manifest.version
+            );
+        }
+        let profiles: Profiles = serde_yaml::from_slice(
+            &read_entry(PROFILES_ENTRY)?.ok_or(anyhow!("the bundle has no profiles"))?,
+        )?;
+        let verge = read_entry(VERGE_ENTRY)?
+            .map(|content| serde_yaml::from_slice::<IVerge>(&content))
+            .transpose()?;
+        let mut files = HashMap::with_capacity(profiles.items.len());
+        for item in profiles.items.iter() {
+            let content =
+                read_entry(&format!("{FILES_DIR}/{}", item.file()))?.ok_or_else(|| {
+                    anyhow!("the file of the profile \"uid:{}\" is missing", item.uid())
+                })?;
+            files.insert(item.file().to_string(), content);
+        }
+        Ok(Self {
+            manifest,
+            profiles,
+            verge,
+            files,
+        })
+    }
+
+    /// give the taken items new uids, and point the chains to the new uids
+    fn remap_uids(
+        &mut self,
+        is_taken: impl Fn(&Profile) -> bool,
+    ) -> IndexMap<ProfileUid, ProfileUid> {
+        let mut remapped = IndexMap::new();
+        for item in self.profiles.items.iter_mut() {
+            if !is_taken(item) {
+                continue;
+            }
+            let old_uid = item.uid().to_string();
+            let old_file = item.file().to_string();
+            let new_uid = item.renew_uid();
+            if let Some(content) = self.files.remove(&old_file) {
+                self.files.insert(item.file().to_string(), content);
+            }
+            remapped.insert(old_uid, new_uid);
+        }
+
+        let remap = |uids: &mut Vec<ProfileUid>| {
+            for uid in uids.iter_mut() {
+                if let Some(new_uid) = remapped.get(uid) {
+                    *uid = new_uid.clone();
+                }
+            }
+        };
+        for item in self.profiles.items.iter_mut() {
+            match item {
+                Profile::Remote(item) => remap(&mut item.chain),
+                Profile::Local(item) => remap(&mut item.chain),
+                _ => {}
+            }
+        }
+        remap(&mut self.profiles.chain);
+        remap(&mut self.profiles.current);
+        for schedule in self.profiles.schedules.iter_mut() {
+            remap(&mut schedule.current);
+        }
+        remapped
+    }
+}
+
+impl Profiles {
+    /// write the files of the bundle into the profiles dir and append the items,
+    /// the items conflicting with the existing ones are imported with new uids
+    pub fn import_bundle(&mut self, mut bundle: ProfilesBundle) -> Result<BundleImportReport> {
+        let profiles_dir = dirs::app_profiles_dir()?;
+        let remapped = bundle.remap_uids(|item| {
+            self.get_item(item.uid()).is_ok()
+                || !is_plain_file_name(item.file())
+                || profiles_dir.join(item.file()).exists()
+        });
+        let files = bundle
+            .profiles
+            .items
+            .iter()
+            .map(|item| {
+                let content = bundle.files.get(item.file()).ok_or_else(|| {
+                    anyhow!("the file of the profile \"uid:{}\" is missing", item.uid())
+                })?;
+                Ok((profiles_dir.join(item.file()), content.as_slice()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        write_files(&files)?;
+
+        let imported = bundle
+            .profiles
+            .items
+            .iter()
+            .map(|item| item.uid().to_string())
+            .collect::<Vec<_>>();
+        self.items.extend(bundle.profiles.items);
+        for uid in bundle.profiles.chain {
+            if !self.chain.contains(&uid) {
+                self.chain.push(uid);
+            }
+        }
+        for field in bundle.profiles.valid {
+            if !self.valid.contains(&field) {
+                self.valid.push(field);
+            }
+        }
+        for group in bundle.profiles.groups {
+            if !self.groups.contains(&group) {
+                self.groups.push(group);
+            }
+        }
+        // the local values are kept, they may be tuned for this machine
+        for (name, value) in bundle.profiles.variables {
+            self.variables.entry(name).or_insert(value);
+        }
+        for schedule in bundle.profiles.schedules {
+            if !self.schedules.contains(&schedule) {
+                self.schedules.push(schedule);
+            }
+        }
+        self.sync_groups();
+        if self.current.is_empty() {
+            self.current = bundle.profiles.current;
+        }
+        self.save_file()?;
+
+        Ok(BundleImportReport { imported, remapped })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROFILES_SAMPLE: &str = r#"current:
+- rExisting
+chain:
+- mExisting
+valid:
+- dns
+items:
+- uid: rExisting
+  type: remote
+  name: Remote
+  file: rExisting.yaml
+  desc: null
+  updated: 1758110672
+  url: https://example.com
+  chain:
+  - mExisting
+  - sFresh
+- uid: mExisting
+  type: merge
+  name: Merge
+  file: mExisting.yaml
+  desc: null
+  updated: 1720954186
+- uid: sFresh
+  type: script
+  script_type: javascript
+  name: Script
+  file: sFresh.js
+  desc: null
+  updated: 1720954186
+"#;
+
+    fn sample_bundle() -> ProfilesBundle {
+        let profiles: Profiles = serde_yaml::from_str(PROFILES_SAMPLE).unwrap();
+        let files = profiles
+            .items
+            .iter()
+            .map(|item| (item.file().to_string(), item.uid().as_bytes().to_vec()))
+            .collect();
+        ProfilesBundle {
+            manifest: BundleManifest {
+                version: BUNDLE_VERSION,
+                app_version: "0.0.0".to_string(),
+                created_at: 0,
+                profiles: profiles.items.len(),
+                with_verge: false,
+            },
+            profiles,
+            verge: None,
+            files,
+        }
+    }
+
+    #[test]
+    fn test_remap_uids() {
+        let mut bundle = sample_bundle();
+        let remapped = bundle.remap_uids(|item| item.uid().ends_with("Existing"));
+        assert_eq!(remapped.len(), 2);
+        let new_remote = &remapped["rExisting"];
+        let new_merge = &remapped["mExisting"];
+        assert!(new_remote.starts_with('r') && new_merge.starts_with('m'));
+
+        let remote = bundle.profiles.get_item(new_remote).unwrap();
+        assert_eq!(remote.file(), format!("{new_remote}.yaml"));
+        assert_eq!(
+            remote.as_remote().unwrap().chain,
+            vec![new_merge.clone(), "sFresh".to_string()]
+        );
+        assert_eq!(bundle.profiles.chain, vec![new_merge.clone()]);
+        assert_eq!(bundle.profiles.current, vec![new_remote.clone()]);
+        // the contents follow the renamed files
+        assert_eq!(bundle.files[&format!("{new_merge}.yaml")], b"mExisting");
+        assert_eq!(bundle.files["sFresh.js"], b"sFresh");
+        assert!(!bundle.files.contains_key("rExisting.yaml"));
+    }
+
+    #[test]
+    fn test_group_subset() {
+        let yaml = PROFILES_SAMPLE.replace("  name: Remote\n", "  name: Remote\n  group: Work\n")
+            + r#"schedules:
+- name: work
+  cron: 0 9 * * 1-5
+  current:
+  - rExisting
+- name: other
+  cron: 0 18 * * 1-5
+  current:
+  - rOther
+"#;
+        let profiles: Profiles = serde_yaml::from_str(&yaml).unwrap();
+        let subset = profiles.group_subset("Work").unwrap();
+        // the items in the chain are exported with the group
+        assert_eq!(
+            subset
+                .items
+                .iter()
+                .map(|item| item.uid())
+                .collect::<Vec<_>>(),
+            vec!["rExisting", "mExisting", "sFresh"]
+        );
+        assert_eq!(subset.groups, vec!["Work".to_string()]);
+        assert!(subset.current.is_empty());
+        assert_eq!(
+            subset
+                .schedules
+                .iter()
+                .map(|schedule| schedule.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["work"]
+        );
+        assert!(profiles.group_subset("Home").is_err());
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.zip");
+        let mut bundle = sample_bundle();
+        bundle.verge = Some(IVerge {
+            default_latency_test: Some("https://example.com".to_string()),
+            ..IVerge::default()
+        });
+        bundle.write_to(&path).unwrap();
+
+        let read = ProfilesBundle::read_from(&path).unwrap();
+        assert_eq!(read.manifest.version, BUNDLE_VERSION);
+        assert_eq!(read.profiles.items.len(), 3);
+        assert_eq!(read.files, bundle.files);
+        assert_eq!(
+            read.verge.unwrap().default_latency_test.as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn test_portable_verge_excludes_machine_variables() {
+        let verge = IVerge {
+            default_latency_test: Some("https://example.com".to_string()),
+            profile_variables: Some(serde_yaml::from_str("port: 7890").unwrap()),
+            ..IVerge::default()
+        };
+        let portable = portable_verge(&verge);
+        assert!(portable.profile_variables.is_none());
+        assert_eq!(portable.default_latency_test, verge.default_latency_test);
+    }
+
+    #[test]
+    fn test_write_files_rollback() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = dir.path().join("rFresh.yaml");
+        let files = [
+            (written.clone(), b"rFresh".as_slice()),
+            (dir.path().join("missing/sFresh.js"), b"sFresh".as_slice()),
+        ];
+        assert!(write_files(&files).is_err());
+        assert!(!written.exists());
+
+        write_files(&files[..1]).unwrap();
+        assert_eq!(fs_err::read(&written).unwrap(), b"rFresh");
+    }
+
+    #[test]
+    fn test_plain_file_name() {
+        assert!(is_plain_file_name("rExisting.yaml"));
+        assert!(!is_plain_file_name("../rExisting.yaml"));
+        assert!(!is_plain_file_name("/etc/passwd"));
+    }
+}
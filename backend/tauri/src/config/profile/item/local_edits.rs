@@ -0,0 +1,425 @@
+//! The edits of a remote profile file made by the user, relative to the upstream content it was fetched as.
+//! They are expressed in the merge profile syntax, so that they could be replayed on a newer upstream
+//! content (a three-way merge), or be moved to a merge profile in the chain of the remote profile.
+use crate::enhance::use_merge;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::collections::HashSet;
+
+/// What to do with the local edits of a remote profile when the subscription is updated
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalEditsPolicy {
+    /// drop the local edits
+    Overwrite,
+    /// replay the local edits on the new upstream content
+    #[default]
+    Merge,
+    /// move the local edits to a generated merge profile in the chain
+    MergeItem,
+}
+
+/// A place where the local content replaces the upstream one as a whole,
+/// the upstream changes there are overridden
+#[derive(Debug, Clone, PartialEq)]
+enum Pin {
+    /// the path of the keys
+    Field(Vec<Value>),
+    /// an item of a list, identified by its name
+    Item { field: Value, name: Value },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LocalEdits {
+    /// a merge profile which turns the upstream content into the local one
+    pub edits: Mapping,
+    /// the paths of the keys removed locally, the merge syntax could not express the removals
+    pub removed: Vec<Vec<Value>>,
+    pins: Vec<Pin>,
+}
+
+/// the items of the lists are identified by their names, e.g. proxies and proxy groups,
+/// or by themselves, e.g. rules
+fn item_id(item: &Value) -> &Value {
+    item.get("name")
+        .filter(|name| name.is_string())
+        .unwrap_or(item)
+}
+
+/// a lua literal of a scalar, the string is written as a long bracket string to skip the escaping
+fn lua_literal(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => {
+            let level = (0..)
+                .map(|level| "=".repeat(level))
+                .find(|level| !s.contains(&format!("]{level}]")))?;
+            Some(format!("[{level}[{s}]{level}]"))
+        }
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// the lua condition which matches the item
+fn item_condition(item: &Value) -> Option<String> {
+    match item.get("name").filter(|name| name.is_string()) {
+        Some(name) => Some(format!("item.name == {}", lua_literal(name)?)),
+        None => Some(format!("item == {}", lua_literal(item)?)),
+    }
+}
+
+fn lookup<'a>(mapping: &'a Mapping, path: &[Value]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    rest.iter()
+        .try_fold(mapping.get(first)?, |value, key| value.get(key))
+}
+
+/// remove the key at the path, the missing parents are ignored
+fn remove_path(mapping: &mut Mapping, path: &[Value]) {
+    let Some((key, parents)) = path.split_last() else {
+        return;
+    };
+    let parent = parents.iter().try_fold(mapping, |mapping, key| {
+        mapping.get_mut(key).and_then(Value::as_mapping_mut)
+    });
+    if let Some(parent) = parent {
+        parent.remove(key);
+    }
+}
+
+/// e.g. `dns.nameserver`
+fn display_path(path: &[Value]) -> String {
+    path.iter()
+        .map(|key| key.as_str().unwrap_or("?"))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn find_item<'a>(mapping: &'a Mapping, field: &Value, name: &Value) -> Option<&'a Value> {
+    mapping
+        .get(field)?
+        .as_sequence()?
+        .iter()
+        .find(|item| item_id(item) == name)
+}
+
+impl LocalEdits {
+    /// the edits which turn `base` into `local`
+    pub fn diff(base: &Mapping, local: &Mapping) -> Self {
+        let mut edits = Self::default();
+        let mut path = Vec::new();
+        edits.edits = edits.diff_mapping(&mut path, base, local, true);
+        edits
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty() && self.removed.is_empty()
+    }
+
+    fn diff_mapping(
+        &mut self,
+        path: &mut Vec<Value>,
+        old: &Mapping,
+        new: &Mapping,
+        top_level: bool,
+    ) -> Mapping {
+        let mut edits = Mapping::new();
+        for (key, value) in new {
+            match (old.get(key), value) {
+                (Some(old), new) if old == new => {}
+                (Some(Value::Sequence(old)), Value::Sequence(new)) if top_level => {
+                    self.diff_sequence(&mut edits, key, old, new);
+                }
+                (Some(Value::Mapping(old)), Value::Mapping(new)) => {
+                    path.push(key.clone());
+                    let nested = self.diff_mapping(path, old, new, false);
+                    path.pop();
+                    if !nested.is_empty() {
+                        edits.insert(key.clone(), Value::Mapping(nested));
+                    }
+                }
+                _ => {
+                    self.pin_field(path, key);
+                    edits.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        for key in old.keys() {
+            if !new.contains_key(key) {
+                self.pin_field(path, key);
+                let mut removed = path.clone();
+                removed.push(key.clone());
+                self.removed.push(removed);
+            }
+        }
+        edits
+    }
+
+    fn pin_field(&mut self, path: &[Value], key: &Value) {
+        let mut path = path.to_vec();
+        path.push(key.clone());
+        self.pins.push(Pin::Field(path));
+    }
+
+    /// the added items at the both ends are prepended or appended, the removed ones are filtered out,
+    /// and the modified ones are overridden. The list is pinned as a whole if it is reordered,
+    /// or some items are inserted in the middle.
+    fn diff_sequence(&mut self, edits: &mut Mapping, key: &Value, old: &[Value], new: &[Value]) {
+        let old_ids = old.iter().map(item_id).collect::<HashSet<_>>();
+        let new_ids = new.iter().map(item_id).collect::<HashSet<_>>();
+        let kept = new
+            .iter()
+            .map(item_id)
+            .filter(|id| old_ids.contains(id))
+            .collect::<Vec<_>>();
+        let old_kept = old
+            .iter()
+            .map(item_id)
+            .filter(|id| new_ids.contains(id))
+            .collect::<Vec<_>>();
+        let first = new.iter().position(|item| old_ids.contains(item_id(item)));
+        let last = new.iter().rposition(|item| old_ids.contains(item_id(item)));
+        let (prepend, append) = match (first, last) {
+            (Some(first), Some(last)) => (&new[..first], &new[last + 1..]),
+            _ => (new, &new[..0]),
+        };
+        let inserted = match (first, last) {
+            (Some(first), Some(last)) => new[first..=last]
+                .iter()
+                .any(|item| !old_ids.contains(item_id(item))),
+            _ => false,
+        };
+
+        let mut filters = Vec::new();
+        let removed = old
+            .iter()
+            .filter(|item| !new_ids.contains(item_id(item)))
+            .map(item_condition)
+            .collect::<Option<Vec<_>>>();
+        let modified = new
+            .iter()
+            .filter(|item| item_id(item) != *item)
+            .filter_map(|item| {
+                let old = old.iter().find(|old| item_id(old) == item_id(item))?;
+                (old != item).then_some(item)
+            })
+            .collect::<Vec<_>>();
+        let (Some(field), Some(removed), false) =
+            (key.as_str(), removed, kept != old_kept || inserted)
+        else {
+            // the order could not be expressed by the merge syntax, so the list is replaced as a whole
+            self.pin_field(&[], key);
+            edits.insert(key.clone(), Value::Sequence(new.to_vec()));
+            return;
+        };
+        if !removed.is_empty() {
+            filters.push(Value::from(format!("not ({})", removed.join(" or "))));
+        }
+        for item in modified {
+            let Some(condition) = item_condition(item) else {
+                continue;
+            };
+            let mut filter = Mapping::new();
+            filter.insert("when".into(), condition.into());
+            filter.insert("override".into(), item.clone());
+            filters.push(Value::Mapping(filter));
+            self.pins.push(Pin::Item {
+                field: key.clone(),
+                name: item_id(item).clone(),
+            });
+        }
+        if !filters.is_empty() {
+            edits.insert(format!("filter__{field}").into(), Value::Sequence(filters));
+        }
+        if !prepend.is_empty() {
+            edits.insert(
+                format!("prepend-{field}").into(),
+                Value::Sequence(prepend.to_vec()),
+            );
+        }
+        if !append.is_empty() {
+            edits.insert(
+                format!("append-{field}").into(),
+                Value::Sequence(append.to_vec()),
+            );
+        }
+    }
+
+    /// the places where both the local edits and the upstream changes are, the local edits win there
+    pub fn conflicts(&self, base: &Mapping, upstream: &Mapping) -> Vec<String> {
+        self.pins
+            .iter()
+            .filter_map(|pin| match pin {
+                Pin::Field(path) => {
+                    (lookup(base, path) != lookup(upstream, path)).then(|| display_path(path))
+                }
+                Pin::Item { field, name } => {
+                    (find_item(base, field, name) != find_item(upstream, field, name)).then(|| {
+                        format!(
+                            "{}.{}",
+                            field.as_str().unwrap_or("?"),
+                            name.as_str().unwrap_or("?")
+                        )
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// the removed keys, e.g. `dns.nameserver`
+    pub fn removed_fields(&self) -> Vec<String> {
+        self.removed.iter().map(|path| display_path(path)).collect()
+    }
+
+    /// replay the edits on the upstream content
+    pub fn apply(&self, upstream: Mapping) -> Result<Mapping> {
+        let (result, logs) = use_merge(&self.edits, upstream);
+        for (span, log) in logs {
+            tracing::warn!("replaying the local edits: {span:?} {log}");
+        }
+        let mut result = result?;
+        for path in self.removed.iter() {
+            remove_path(&mut result, path);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn yaml(s: &str) -> Mapping {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    const BASE: &str = r#"
+mixed-port: 7890
+dns:
+  enable: true
+  nameserver: [223.5.5.5]
+proxies:
+  - { name: HK, type: ss, server: hk.example, port: 443 }
+  - { name: JP, type: ss, server: jp.example, port: 443 }
+  - { name: US, type: ss, server: us.example, port: 443 }
+rules:
+  - DOMAIN-SUFFIX,ads.example,REJECT
+  - MATCH,DIRECT
+"#;
+
+    #[test]
+    fn test_replay_local_edits() {
+        let base = yaml(BASE);
+        let local = yaml(
+            r#"
+mixed-port: 7890
+dns:
+  enable: true
+  nameserver: [1.1.1.1]
+proxies:
+  - { name: HK, type: ss, server: hk.example, port: 8443 }
+  - { name: JP, type: ss, server: jp.example, port: 443 }
+  - { name: Home, type: socks5, server: 10.0.0.2, port: 1080 }
+rules:
+  - DOMAIN,intranet.example,DIRECT
+  - MATCH,DIRECT
+"#,
+        );
+        let upstream = yaml(
+            r#"
+mixed-port: 7890
+dns:
+  enable: true
+  ipv6: true
+  nameserver: [223.5.5.5]
+proxies:
+  - { name: HK, type: ss, server: hk2.example, port: 443 }
+  - { name: JP, type: ss, server: jp.example, port: 443 }
+  - { name: US, type: ss, server: us.example, port: 443 }
+  - { name: SG, type: ss, server: sg.example, port: 443 }
+rules:
+  - DOMAIN-SUFFIX,ads.example,REJECT
+  - DOMAIN-SUFFIX,tracker.example,REJECT
+  - MATCH,DIRECT
+"#,
+        );
+        let edits = LocalEdits::diff(&base, &local);
+        assert!(edits.edits.contains_key("prepend-rules"));
+        assert!(edits.edits.contains_key("filter__proxies"));
+        assert!(edits.edits.contains_key("append-proxies"));
+        assert_eq!(edits.conflicts(&base, &upstream), vec!["proxies.HK"]);
+
+        let merged = edits.apply(upstream).unwrap();
+        let names = merged["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|proxy| proxy["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["HK", "JP", "SG", "Home"]);
+        assert_eq!(merged["proxies"][0]["port"], Value::from(8443));
+        assert_eq!(merged["dns"]["ipv6"], Value::from(true));
+        assert_eq!(merged["dns"]["nameserver"], yaml("v: [1.1.1.1]")["v"]);
+        assert_eq!(
+            merged["rules"],
+            yaml(
+                "v: [DOMAIN,intranet.example,DIRECT, DOMAIN-SUFFIX,tracker.example,REJECT, MATCH,DIRECT]"
+            )["v"]
+        );
+    }
+
+    #[test]
+    fn test_pin_reordered_list() {
+        let base = yaml(BASE);
+        let mut local = base.clone();
+        local["proxies"].as_sequence_mut().unwrap().reverse();
+        let mut upstream = base.clone();
+        upstream["proxies"]
+            .as_sequence_mut()
+            .unwrap()
+            .push(yaml("{ name: SG, type: ss, server: sg.example, port: 443 }").into());
+        let edits = LocalEdits::diff(&base, &local);
+        assert_eq!(edits.edits["proxies"], local["proxies"]);
+        assert_eq!(edits.conflicts(&base, &upstream), vec!["proxies"]);
+        assert_eq!(edits.apply(upstream).unwrap()["proxies"], local["proxies"]);
+    }
+
+    #[test]
+    fn test_remove_fields() {
+        let base = yaml(BASE);
+        let mut local = base.clone();
+        local.remove("mixed-port");
+        local["dns"].as_mapping_mut().unwrap().remove("nameserver");
+        let edits = LocalEdits::diff(&base, &local);
+        assert!(edits.edits.is_empty());
+        assert!(!edits.is_empty());
+
+        let mut upstream = base.clone();
+        upstream.insert("mixed-port".into(), 7891.into());
+        let merged = edits.apply(upstream.clone()).unwrap();
+        assert!(!merged.contains_key("mixed-port"));
+        assert_eq!(merged["dns"], Value::Mapping(yaml("enable: true")));
+        assert_eq!(edits.conflicts(&base, &upstream), vec!["mixed-port"]);
+    }
+
+    #[test]
+    fn test_prepend_rules() {
+        let base = yaml(BASE);
+        let mut local = base.clone();
+        local["rules"]
+            .as_sequence_mut()
+            .unwrap()
+            .insert(0, "DOMAIN,intranet.example,DIRECT".into());
+        let edits = LocalEdits::diff(&base, &local);
+        assert_eq!(
+            edits.edits,
+            yaml("prepend-rules: [DOMAIN,intranet.example,DIRECT]")
+        );
+        assert!(edits.conflicts(&base, &base).is_empty());
+        assert!(LocalEdits::diff(&base, &base).is_empty());
+    }
+}
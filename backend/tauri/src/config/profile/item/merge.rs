@@ -2,8 +2,9 @@ use crate::config::{ProfileKindGetter, profile::item_type::ProfileItemType};
 
 use super::{
     ProfileCleanup, ProfileFileIo, ProfileHelper, ProfileMetaGetter, ProfileMetaSetter,
-    ProfileShared, ProfileSharedBuilder, ambassador_impl_ProfileFileIo,
-    ambassador_impl_ProfileMetaGetter, ambassador_impl_ProfileMetaSetter,
+    ProfileShared, ProfileSharedBuilder, ProfileSource, ProfileSourceSync,
+    ambassador_impl_ProfileFileIo, ambassador_impl_ProfileMetaGetter,
+    ambassador_impl_ProfileMetaSetter,
 };
 use ambassador::Delegate;
 use derive_builder::Builder;
@@ -29,6 +30,10 @@ pub struct MergeProfile {
     #[builder_field_attr(serde(flatten))]
     #[builder_update(nested)]
     pub shared: ProfileShared,
+    /// the url which the content is fetched from, `None` for a local-only profile
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ProfileSource>,
 }
 
 impl MergeProfile {
@@ -48,3 +53,13 @@ impl ProfileKindGetter for MergeProfile {
 
 impl ProfileCleanup for MergeProfile {}
 impl ProfileHelper for MergeProfile {}
+
+impl ProfileSourceSync for MergeProfile {
+    fn source(&self) -> Option<&ProfileSource> {
+        self.source.as_ref()
+    }
+
+    fn source_mut(&mut self) -> Option<&mut ProfileSource> {
+        self.source.as_mut()
+    }
+}
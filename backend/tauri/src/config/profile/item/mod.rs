@@ -7,18 +7,26 @@ use nyanpasu_macro::EnumWrapperCombined;
 use std::{borrow::Borrow, fmt::Debug, fs, io::Write};
 
 mod local;
+mod local_edits;
 mod merge;
+mod post_subscribe;
 pub mod prelude;
+mod region;
 mod remote;
 mod script;
 mod shared;
+mod source;
 mod utils; // private use utils
 
 pub use local::*;
+pub use local_edits::*;
 pub use merge::*;
+pub use post_subscribe::*;
+pub use region::*;
 pub use remote::*;
 pub use script::*;
 pub use shared::*;
+pub use source::*;
 
 /// Profile Setter Helper
 /// It is intended to be used in the default trait implementation, so it is PRIVATE.
@@ -42,6 +50,9 @@ pub trait ProfileMetaGetter {
     fn uid(&self) -> &str;
     fn updated(&self) -> usize;
     fn file(&self) -> &str;
+    fn group(&self) -> Option<&str>;
+    fn tags(&self) -> &[String];
+    fn variables(&self) -> &indexmap::IndexMap<String, serde_yaml::Value>;
 }
 
 #[delegatable_trait]
@@ -72,6 +83,15 @@ pub trait ProfileHelper:
         duplicate_profile.set_updated(chrono::Local::now().timestamp() as usize);
         Ok(duplicate_profile)
     }
+
+    /// assign a new uid and the matching file name, the file itself is not touched
+    fn renew_uid(&mut self) -> String {
+        let kind = self.kind();
+        let uid = utils::generate_uid(&kind);
+        self.set_file(ProfileSharedBuilder::default_file_name(&kind, &uid));
+        self.set_uid(uid.clone());
+        uid
+    }
 }
 
 pub trait ProfileCleanup: ProfileHelper {
@@ -119,6 +139,24 @@ impl Profile {
         }
     }
 
+    /// the url source of a merge or script profile
+    pub fn source(&self) -> Option<&ProfileSource> {
+        match self {
+            Profile::Merge(profile) => profile.source.as_ref(),
+            Profile::Script(profile) => profile.source.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// sync a merge or script profile with its url source, see [`ProfileSourceSync::sync_source`]
+    pub async fn sync_source(&mut self, pin: bool) -> Result<SourceSyncOutcome> {
+        match self {
+            Profile::Merge(profile) => profile.sync_source(pin).await,
+            Profile::Script(profile) => profile.sync_source(pin).await,
+            _ => bail!("profile `{}` could not be backed by a url", self.uid()),
+        }
+    }
+
     /// get the file data
     pub fn read_file(&self) -> Result<String> {
         let file = self.file();
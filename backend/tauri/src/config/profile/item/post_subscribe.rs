@@ -0,0 +1,874 @@
+//! The processing of the nodes after a subscription:
+//! deduplication, reachability pruning, name normalization and region groups
+use super::{RegionGroups, RegionOptions, detect_region};
+use futures::StreamExt;
+use indexmap::{IndexMap, IndexSet};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::{collections::HashMap, time::Duration};
+
+/// the fields which identify the account of a node, besides the endpoint
+const CREDENTIAL_FIELDS: [&str; 9] = [
+    "uuid",
+    "password",
+    "username",
+    "auth",
+    "auth-str",
+    "psk",
+    "private-key",
+    "token",
+    "obfs-password",
+];
+
+/// the protocols over udp, which could not be probed by tcp
+const UDP_TYPES: [&str; 4] = ["hysteria", "hysteria2", "tuic", "wireguard"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(default)]
+pub struct PostSubscribeOptions {
+    /// drop the nodes with the same type, server, port and credentials, the first one is kept
+    pub dedupe: bool,
+    /// the find and replace rules of the node names, applied in order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rename: Vec<NodeRename>,
+    /// rename the nodes by their regions, after the rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<RegionOptions>,
+    /// drop the nodes which could not be reached by tcp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe: Option<NodeProbe>,
+}
+
+/// all the matches of `pattern` in the node name are replaced by `template`,
+/// which could refer to the captures as `$1` or `${name}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub struct NodeRename {
+    pub pattern: String,
+    pub template: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(default)]
+pub struct NodeProbe {
+    /// connect timeout in milliseconds
+    pub timeout: u64,
+    /// the number of the concurrent probes
+    pub concurrency: usize,
+}
+
+impl Default for NodeProbe {
+    fn default() -> Self {
+        Self {
+            timeout: 3000,
+            concurrency: 16,
+        }
+    }
+}
+
+/// A change made by the post-subscribe processing
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SubscriptionLog {
+    /// the node has the same endpoint as `kept`
+    Duplicate { name: String, kept: String },
+    /// the node failed the tcp probe
+    Unreachable { name: String, reason: String },
+    /// the rename pattern is not a valid regex
+    InvalidPattern { pattern: String, reason: String },
+    /// all the nodes of the group are removed, `DIRECT` is put in it
+    EmptyGroup { group: String },
+    /// the region group is not generated, because the name is taken
+    GroupExists { group: String },
+    /// the group to inject the region groups into is not found
+    UnknownGroup { group: String },
+    /// both the local edits and the upstream changed the field, the local edits are kept
+    LocalEditConflict { field: String },
+    /// the target of the rule is removed, the rule is dropped
+    DanglingRule { rule: String },
+    /// the `dialer-proxy` of the node is removed, the node is dropped as well
+    DanglingDialer { name: String, dialer: String },
+}
+
+type EndpointKey = (String, String, u16, Vec<Option<Value>>);
+
+fn node_name(node: &Mapping) -> Option<&str> {
+    node.get("name").and_then(Value::as_str)
+}
+
+fn node_endpoint(node: &Mapping) -> Option<(&str, u16)> {
+    let server = node.get("server").and_then(Value::as_str)?;
+    let port = match node.get("port")? {
+        Value::Number(port) => port.as_u64().and_then(|port| u16::try_from(port).ok()),
+        Value::String(port) => port.parse().ok(),
+        _ => None,
+    }?;
+    Some((server, port))
+}
+
+fn endpoint_key(node: &Mapping) -> Option<EndpointKey> {
+    let kind = node.get("type").and_then(Value::as_str)?;
+    let (server, port) = node_endpoint(node)?;
+    let credentials = CREDENTIAL_FIELDS
+        .iter()
+        .map(|field| node.get(*field).cloned())
+        .collect();
+    Some((
+        kind.to_string(),
+        server.to_ascii_lowercase(),
+        port,
+        credentials,
+    ))
+}
+
+async fn probe_endpoint(server: String, port: u16, timeout: Duration) -> Result<(), String> {
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect((server, port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(err)) => Err(err.to_string()),
+        Err(_) => Err(format!("connect timed out after {}ms", timeout.as_millis())),
+    }
+}
+
+/// a name which is not taken yet, by appending a counter
+fn unique_name(name: &str, taken: &IndexSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|i| format!("{name} {i}"))
+        .find(|name| !taken.contains(name))
+        .unwrap()
+}
+
+/// process the `proxies` of the subscription, the references in `proxy-groups`, `rules`,
+/// `sub-rules` and `dialer-proxy` are updated accordingly. Returns the removals and the problems.
+pub async fn post_subscribe(
+    data: &mut Mapping,
+    options: &PostSubscribeOptions,
+) -> Vec<SubscriptionLog> {
+    let mut logs = Vec::new();
+    let Some(proxies) = data.get_mut("proxies").and_then(Value::as_sequence_mut) else {
+        return logs;
+    };
+    // the later stages refer to the nodes by name, so the repeated names are told apart first,
+    // the references to them are left to the first node
+    let mut names = IndexSet::new();
+    for node in proxies.iter_mut().filter_map(Value::as_mapping_mut) {
+        let Some(name) = node_name(node) else {
+            continue;
+        };
+        let unique = unique_name(name, &names);
+        if unique != name {
+            node.insert("name".into(), unique.clone().into());
+        }
+        names.insert(unique);
+    }
+    // duplicated name -> the kept one
+    let mut duplicates: IndexMap<String, String> = IndexMap::new();
+    if options.dedupe {
+        let mut seen: HashMap<EndpointKey, String> = HashMap::new();
+        proxies.retain(|node| {
+            let Some(node) = node.as_mapping() else {
+                return true;
+            };
+            let (Some(name), Some(key)) = (node_name(node), endpoint_key(node)) else {
+                return true;
+            };
+            match seen.get(&key) {
+                Some(kept) => {
+                    logs.push(SubscriptionLog::Duplicate {
+                        name: name.to_string(),
+                        kept: kept.clone(),
+                    });
+                    duplicates.insert(name.to_string(), kept.clone());
+                    false
+                }
+                None => {
+                    seen.insert(key, name.to_string());
+                    true
+                }
+            }
+        });
+    }
+
+    // probe before renaming, so that the sequence numbers have no gaps
+    let mut pruned = IndexSet::new();
+    if let Some(probe) = &options.probe {
+        let timeout = Duration::from_millis(probe.timeout);
+        let targets = proxies
+            .iter()
+            .filter_map(Value::as_mapping)
+            .filter(|node| {
+                node.get("type")
+                    .and_then(Value::as_str)
+                    .is_some_and(|kind| !UDP_TYPES.contains(&kind))
+            })
+            .filter_map(|node| {
+                let name = node_name(node)?;
+                let (server, port) = node_endpoint(node)?;
+                Some((name.to_string(), server.to_string(), port))
+            })
+            .collect::<Vec<_>>();
+        let unreachable = futures::stream::iter(targets)
+            .map(|(name, server, port)| async move {
+                probe_endpoint(server, port, timeout)
+                    .await
+                    .err()
+                    .map(|reason| (name, reason))
+            })
+            .buffered(probe.concurrency.max(1))
+            .filter_map(|result| async move { result })
+            .collect::<IndexMap<_, _>>()
+            .await;
+        proxies.retain(|node| {
+            node.as_mapping()
+                .and_then(node_name)
+                .is_none_or(|name| !unreachable.contains_key(name))
+        });
+        for (name, reason) in unreachable {
+            pruned.insert(name.clone());
+            logs.push(SubscriptionLog::Unreachable { name, reason });
+        }
+    }
+
+    let rules = options
+        .rename
+        .iter()
+        .filter_map(|rule| match Regex::new(&rule.pattern) {
+            Ok(regex) => Some((regex, rule.template.as_str())),
+            Err(err) => {
+                logs.push(SubscriptionLog::InvalidPattern {
+                    pattern: rule.pattern.clone(),
+                    reason: err.to_string(),
+                });
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    // the groups keep their names, so the nodes could not be renamed to them
+    let mut taken: IndexSet<String> = data
+        .get("proxy-groups")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(|group| group.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect();
+    // original name -> the name to refer to, `None` if it is removed
+    let mut resolved: HashMap<String, Option<String>> = HashMap::new();
+    // region code -> the renamed nodes
+    let mut regions: IndexMap<&str, Vec<String>> = IndexMap::new();
+    let proxies = data
+        .get_mut("proxies")
+        .and_then(Value::as_sequence_mut)
+        .unwrap();
+    for node in proxies.iter_mut().filter_map(Value::as_mapping_mut) {
+        let Some(name) = node_name(node).map(str::to_string) else {
+            continue;
+        };
+        let mut renamed = name.clone();
+        for (regex, template) in rules.iter() {
+            let replaced = regex.replace_all(&renamed, *template).trim().to_string();
+            renamed = replaced;
+        }
+        if renamed.is_empty() {
+            renamed = name.clone();
+        }
+        let region = options
+            .region
+            .as_ref()
+            .and_then(|region| Some((region, detect_region(&renamed)?)));
+        if let Some((region, code)) = region {
+            let index = regions.get(code).map_or(0, Vec::len) + 1;
+            renamed = region.render(code, index, &renamed);
+        }
+        let renamed = unique_name(&renamed, &taken);
+        taken.insert(renamed.clone());
+        if let Some((_, code)) = region {
+            regions.entry(code).or_default().push(renamed.clone());
+        }
+        if renamed != name {
+            node.insert("name".into(), renamed.clone().into());
+        }
+        resolved.insert(name, Some(renamed));
+    }
+
+    for name in pruned {
+        resolved.insert(name, None);
+    }
+    for (name, kept) in duplicates {
+        let target = resolved.get(&kept).cloned().flatten();
+        resolved.insert(name, target);
+    }
+    update_dialers(data, &mut resolved, &mut logs);
+    update_groups(data, &resolved, &mut logs);
+    update_rules(data, &resolved, &mut logs);
+    if let Some(groups) = options
+        .region
+        .as_ref()
+        .and_then(|region| region.groups.as_ref())
+    {
+        inject_region_groups(data, groups, &regions, &taken, &mut logs);
+    }
+    logs
+}
+
+/// append the region groups to `proxy-groups`, and to the `inject_into` groups
+fn inject_region_groups(
+    data: &mut Mapping,
+    options: &RegionGroups,
+    regions: &IndexMap<&str, Vec<String>>,
+    taken: &IndexSet<String>,
+    logs: &mut Vec<SubscriptionLog>,
+) {
+    let region_groups = options
+        .build(regions)
+        .into_iter()
+        .filter(|group| {
+            let name = node_name(group).unwrap_or_default();
+            if taken.contains(name) {
+                logs.push(SubscriptionLog::GroupExists {
+                    group: name.to_string(),
+                });
+                return false;
+            }
+            true
+        })
+        .collect::<Vec<_>>();
+    let names = region_groups
+        .iter()
+        .filter_map(node_name)
+        .map(Value::from)
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        return;
+    }
+    let groups = data
+        .entry("proxy-groups".into())
+        .or_insert_with(|| Value::Sequence(Vec::new()));
+    let Some(groups) = groups.as_sequence_mut() else {
+        return;
+    };
+    for target in options.inject_into.iter() {
+        let group = groups
+            .iter_mut()
+            .filter_map(Value::as_mapping_mut)
+            .find(|group| node_name(group) == Some(target.as_str()));
+        let Some(group) = group else {
+            logs.push(SubscriptionLog::UnknownGroup {
+                group: target.clone(),
+            });
+            continue;
+        };
+        let members = group
+            .entry("proxies".into())
+            .or_insert_with(|| Value::Sequence(Vec::new()));
+        if let Some(members) = members.as_sequence_mut() {
+            let missing = names
+                .iter()
+                .filter(|name| !members.contains(name))
+                .cloned()
+                .collect::<Vec<_>>();
+            members.extend(missing);
+        }
+    }
+    groups.extend(region_groups.into_iter().map(Value::Mapping));
+}
+
+/// apply the renames to the `dialer-proxy` of the nodes, the nodes dialing through
+/// a removed node are removed as well, rather than connecting without it
+fn update_dialers(
+    data: &mut Mapping,
+    resolved: &mut HashMap<String, Option<String>>,
+    logs: &mut Vec<SubscriptionLog>,
+) {
+    let Some(proxies) = data.get_mut("proxies").and_then(Value::as_sequence_mut) else {
+        return;
+    };
+    // the removals may chain through the dialers, which refer to the original names
+    loop {
+        let mut removed = Vec::new();
+        proxies.retain(|node| {
+            let Some(node) = node.as_mapping() else {
+                return true;
+            };
+            let (Some(name), Some(dialer)) = (
+                node_name(node),
+                node.get("dialer-proxy").and_then(Value::as_str),
+            ) else {
+                return true;
+            };
+            if !matches!(resolved.get(dialer), Some(None)) {
+                return true;
+            }
+            logs.push(SubscriptionLog::DanglingDialer {
+                name: name.to_string(),
+                dialer: dialer.to_string(),
+            });
+            removed.push(name.to_string());
+            false
+        });
+        if removed.is_empty() {
+            break;
+        }
+        // the original name of the node and its duplicates
+        for target in resolved.values_mut() {
+            if target
+                .as_ref()
+                .is_some_and(|target| removed.contains(target))
+            {
+                *target = None;
+            }
+        }
+    }
+    for node in proxies.iter_mut().filter_map(Value::as_mapping_mut) {
+        let renamed = node
+            .get("dialer-proxy")
+            .and_then(Value::as_str)
+            .and_then(|dialer| resolved.get(dialer).cloned().flatten());
+        if let Some(renamed) = renamed {
+            node.insert("dialer-proxy".into(), renamed.into());
+        }
+    }
+}
+
+/// split the rule by the commas out of the parentheses of the logic rules
+fn split_rule(rule: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in rule.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&rule[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&rule[start..]);
+    parts
+}
+
+/// apply the renames to the targets of `rules` and `sub-rules`,
+/// the rules targeting the removed nodes are dropped
+fn update_rules(
+    data: &mut Mapping,
+    resolved: &HashMap<String, Option<String>>,
+    logs: &mut Vec<SubscriptionLog>,
+) {
+    let mut update = |rules: &mut Vec<Value>| {
+        rules.retain_mut(|rule| {
+            let Some(line) = rule.as_str() else {
+                return true;
+            };
+            let mut parts = split_rule(line);
+            let index = match parts[0].trim().to_ascii_uppercase().as_str() {
+                "MATCH" | "FINAL" => 1,
+                // the target is a set of `sub-rules`
+                "SUB-RULE" => return true,
+                _ => 2,
+            };
+            let Some(target) = parts.get(index).map(|target| target.trim()) else {
+                return true;
+            };
+            match resolved.get(target) {
+                Some(Some(renamed)) if renamed != target => {
+                    parts[index] = renamed.as_str();
+                    *rule = Value::from(parts.join(","));
+                    true
+                }
+                Some(None) => {
+                    logs.push(SubscriptionLog::DanglingRule {
+                        rule: line.to_string(),
+                    });
+                    false
+                }
+                _ => true,
+            }
+        });
+    };
+    if let Some(rules) = data.get_mut("rules").and_then(Value::as_sequence_mut) {
+        update(rules);
+    }
+    if let Some(sub_rules) = data.get_mut("sub-rules").and_then(Value::as_mapping_mut) {
+        for rules in sub_rules.values_mut().filter_map(Value::as_sequence_mut) {
+            update(rules);
+        }
+    }
+}
+
+/// apply the renames and removals to the `proxies` of the groups
+fn update_groups(
+    data: &mut Mapping,
+    resolved: &HashMap<String, Option<String>>,
+    logs: &mut Vec<SubscriptionLog>,
+) {
+    let Some(groups) = data
+        .get_mut("proxy-groups")
+        .and_then(Value::as_sequence_mut)
+    else {
+        return;
+    };
+    for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+        let Some(members) = group.get("proxies").and_then(Value::as_sequence) else {
+            continue;
+        };
+        let mut updated = IndexSet::new();
+        for member in members {
+            let Some(member) = member.as_str() else {
+                continue;
+            };
+            match resolved.get(member) {
+                Some(Some(name)) => updated.insert(name.clone()),
+                Some(None) => continue,
+                None => updated.insert(member.to_string()),
+            };
+        }
+        // the groups which pull the nodes from elsewhere could be empty
+        let pulls_nodes = ["use", "include-all", "include-all-proxies", "filter"]
+            .iter()
+            .any(|key| group.contains_key(*key));
+        if updated.is_empty() && !members.is_empty() && !pulls_nodes {
+            let name = group
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            logs.push(SubscriptionLog::EmptyGroup {
+                group: name.to_string(),
+            });
+            updated.insert("DIRECT".to_string());
+        }
+        let updated = updated.into_iter().map(Value::from).collect::<Vec<_>>();
+        group.insert("proxies".into(), Value::Sequence(updated));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_post_subscribe() {
+        let reachable = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = reachable.local_addr().unwrap().port();
+        // a port which is closed right after it is allocated
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let mut data: Mapping = serde_yaml::from_str(&format!(
+            r#"
+proxies:
+  - {{ name: "🇭🇰 HK 01 | x1.0", type: ss, server: 127.0.0.1, port: {port}, cipher: aes-128-gcm, password: a }}
+  - {{ name: "🇭🇰 HK 01 copy", type: ss, server: 127.0.0.1, port: "{port}", cipher: aes-128-gcm, password: a }}
+  - {{ name: "🇭🇰 HK 02 | x1.0", type: ss, server: 127.0.0.1, port: {port}, cipher: aes-128-gcm, password: b }}
+  - {{ name: "🇯🇵 JP 01 | x2.0", type: trojan, server: 127.0.0.1, port: {closed}, password: a }}
+  - {{ name: "TUIC", type: tuic, server: 127.0.0.1, port: {closed}, uuid: a }}
+proxy-groups:
+  - {{ name: Proxy, type: select, proxies: ["🇭🇰 HK 01 copy", "🇭🇰 HK 02 | x1.0", "🇯🇵 JP 01 | x2.0", DIRECT] }}
+  - {{ name: JP, type: select, proxies: ["🇯🇵 JP 01 | x2.0"] }}
+  - {{ name: Auto, type: url-test, use: [provider], proxies: ["🇯🇵 JP 01 | x2.0"] }}
+"#
+        ))
+        .unwrap();
+        let options = PostSubscribeOptions {
+            dedupe: true,
+            rename: vec![NodeRename {
+                pattern: r"^\S+ (?<region>[A-Z]{2}) (?<index>\d+).*$".to_string(),
+                template: "${region}-${index}".to_string(),
+            }],
+            region: None,
+            probe: Some(NodeProbe {
+                timeout: 1000,
+                concurrency: 2,
+            }),
+        };
+        let logs = post_subscribe(&mut data, &options).await;
+
+        let names = data["proxies"]
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|node| node["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["HK-01", "HK-02", "TUIC"]);
+        let members = |index: usize| {
+            data["proxy-groups"][index]["proxies"]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|name| name.as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(members(0), vec!["HK-01", "HK-02", "DIRECT"]);
+        assert_eq!(members(1), vec!["DIRECT"]);
+        assert!(members(2).is_empty());
+
+        assert_eq!(logs.len(), 3);
+        assert_eq!(
+            logs[0],
+            SubscriptionLog::Duplicate {
+                name: "🇭🇰 HK 01 copy".to_string(),
+                kept: "🇭🇰 HK 01 | x1.0".to_string(),
+            }
+        );
+        assert!(
+            matches!(&logs[1], SubscriptionLog::Unreachable { name, .. } if name == "🇯🇵 JP 01 | x2.0")
+        );
+        assert_eq!(
+            logs[2],
+            SubscriptionLog::EmptyGroup {
+                group: "JP".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_subscribe_references() {
+        let reachable = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = reachable.local_addr().unwrap().port();
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let mut data: Mapping = serde_yaml::from_str(&format!(
+            r#"
+proxies:
+  - {{ name: Relay, type: ss, server: 127.0.0.1, port: {port}, password: a }}
+  - {{ name: Relay copy, type: ss, server: 127.0.0.1, port: {port}, password: a }}
+  - {{ name: Exit, type: ss, server: 127.0.0.1, port: {port}, password: b, dialer-proxy: Relay copy }}
+  - {{ name: Down, type: trojan, server: 127.0.0.1, port: {closed}, password: a }}
+  - {{ name: Chained, type: ss, server: 127.0.0.1, port: {port}, password: c, dialer-proxy: Down }}
+  - {{ name: Chained twice, type: ss, server: 127.0.0.1, port: {port}, password: d, dialer-proxy: Chained }}
+proxy-groups:
+  - {{ name: Proxy, type: select, proxies: [Relay copy, Chained, Exit] }}
+rules:
+  - DOMAIN,a.com,Relay copy,no-resolve
+  - AND,((DOMAIN,b.com),(NETWORK,tcp)),Chained twice
+  - DOMAIN,c.com,Proxy
+  - SUB-RULE,(NETWORK,udp),udp
+  - MATCH,Down
+sub-rules:
+  udp:
+    - DOMAIN,d.com,Relay copy
+"#
+        ))
+        .unwrap();
+        let options = PostSubscribeOptions {
+            dedupe: true,
+            rename: vec![NodeRename {
+                pattern: "^".to_string(),
+                template: "[A] ".to_string(),
+            }],
+            probe: Some(NodeProbe {
+                timeout: 1000,
+                concurrency: 2,
+            }),
+            ..Default::default()
+        };
+        let logs = post_subscribe(&mut data, &options).await;
+
+        let strings = |value: &Value| {
+            value
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_str().unwrap_or_else(|| v["name"].as_str().unwrap()))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        // the nodes dialing through the unreachable one are removed as well
+        assert_eq!(strings(&data["proxies"]), vec!["[A] Relay", "[A] Exit"]);
+        assert_eq!(data["proxies"][1]["dialer-proxy"], Value::from("[A] Relay"));
+        assert_eq!(
+            strings(&data["proxy-groups"][0]["proxies"]),
+            vec!["[A] Relay", "[A] Exit"]
+        );
+        assert_eq!(
+            strings(&data["rules"]),
+            vec![
+                "DOMAIN,a.com,[A] Relay,no-resolve",
+                "DOMAIN,c.com,Proxy",
+                "SUB-RULE,(NETWORK,udp),udp",
+            ]
+        );
+        assert_eq!(
+            strings(&data["sub-rules"]["udp"]),
+            vec!["DOMAIN,d.com,[A] Relay"]
+        );
+        assert!(logs.contains(&SubscriptionLog::DanglingDialer {
+            name: "[A] Chained twice".to_string(),
+            dialer: "Chained".to_string(),
+        }));
+        assert!(logs.contains(&SubscriptionLog::DanglingRule {
+            rule: "MATCH,Down".to_string(),
+        }));
+        assert!(logs.contains(&SubscriptionLog::DanglingRule {
+            rule: "AND,((DOMAIN,b.com),(NETWORK,tcp)),Chained twice".to_string(),
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_post_subscribe_repeated_names() {
+        let reachable = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = reachable.local_addr().unwrap().port();
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let mut data: Mapping = serde_yaml::from_str(&format!(
+            r#"
+proxies:
+  - {{ name: "香港 01", type: ss, server: 127.0.0.1, port: {port}, password: a }}
+  - {{ name: "香港 01", type: ss, server: 127.0.0.1, port: {closed}, password: a }}
+  - {{ name: "香港 02", type: ss, server: 127.0.0.1, port: {port}, password: b }}
+proxy-groups:
+  - {{ name: Proxy, type: select, proxies: ["香港 01", "香港 02"] }}
+rules:
+  - DOMAIN,a.com,香港 01
+"#
+        ))
+        .unwrap();
+        let options = PostSubscribeOptions {
+            region: Some(RegionOptions::default()),
+            probe: Some(NodeProbe {
+                timeout: 1000,
+                concurrency: 2,
+            }),
+            ..Default::default()
+        };
+        let logs = post_subscribe(&mut data, &options).await;
+
+        // only the unreachable one of the repeated names is removed
+        assert!(matches!(
+            &logs[..],
+            [SubscriptionLog::Unreachable { name, .. }] if name == "香港 01 2"
+        ));
+        let names = |value: &Value| {
+            value
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_str().unwrap_or_else(|| v["name"].as_str().unwrap()))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&data["proxies"]), vec!["🇭🇰 HK-01", "🇭🇰 HK-02"]);
+        assert_eq!(
+            names(&data["proxy-groups"][0]["proxies"]),
+            vec!["🇭🇰 HK-01", "🇭🇰 HK-02"]
+        );
+        assert_eq!(names(&data["rules"]), vec!["DOMAIN,a.com,🇭🇰 HK-01"]);
+    }
+
+    #[tokio::test]
+    async fn test_post_subscribe_invalid_pattern() {
+        let mut data: Mapping = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: A, type: ss, server: example.com, port: 443, password: a }
+  - { name: A, type: ss, server: example.org, port: 443, password: a }
+"#,
+        )
+        .unwrap();
+        let options = PostSubscribeOptions {
+            rename: vec![NodeRename {
+                pattern: "(".to_string(),
+                template: String::new(),
+            }],
+            ..Default::default()
+        };
+        let logs = post_subscribe(&mut data, &options).await;
+        assert!(matches!(
+            &logs[..],
+            [SubscriptionLog::InvalidPattern { .. }]
+        ));
+        // the names are still made unique
+        assert_eq!(data["proxies"][1]["name"], Value::from("A 2"));
+    }
+
+    #[tokio::test]
+    async fn test_post_subscribe_regions() {
+        let mut data: Mapping = serde_yaml::from_str(
+            r#"
+proxies:
+  - { name: "香港 IPLC 01", type: ss, server: a.example.com, port: 443, password: a }
+  - { name: "HK 高速 02 [x1.5]", type: ss, server: b.example.com, port: 443, password: a }
+  - { name: "日本 东京 01", type: ss, server: c.example.com, port: 443, password: a }
+  - { name: "Traffic: 10GB", type: ss, server: d.example.com, port: 443, password: a }
+  - { name: "USA Seattle", type: ss, server: e.example.com, port: 443, password: a }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: ["香港 IPLC 01", "HK 高速 02 [x1.5]", "日本 东京 01", "USA Seattle"] }
+  - { name: "🇺🇸 US", type: select, proxies: [DIRECT] }
+"#,
+        )
+        .unwrap();
+        let options = PostSubscribeOptions {
+            rename: vec![NodeRename {
+                pattern: r"\s*\[x[\d.]+\]".to_string(),
+                template: String::new(),
+            }],
+            region: Some(RegionOptions {
+                groups: Some(RegionGroups {
+                    inject_into: vec!["Proxy".to_string(), "Missing".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let logs = post_subscribe(&mut data, &options).await;
+
+        let names = |value: &Value| {
+            value
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_str().unwrap_or_else(|| v["name"].as_str().unwrap()))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&data["proxies"]),
+            vec![
+                "🇭🇰 HK-01",
+                "🇭🇰 HK-02",
+                "🇯🇵 JP-01",
+                "Traffic: 10GB",
+                "🇺🇸 US-01"
+            ]
+        );
+        assert_eq!(
+            names(&data["proxy-groups"][0]["proxies"]),
+            vec![
+                "🇭🇰 HK-01",
+                "🇭🇰 HK-02",
+                "🇯🇵 JP-01",
+                "🇺🇸 US-01",
+                "🇭🇰 HK",
+                "🇯🇵 JP"
+            ]
+        );
+        assert_eq!(
+            names(&data["proxy-groups"]),
+            vec!["Proxy", "🇺🇸 US", "🇭🇰 HK", "🇯🇵 JP"]
+        );
+        let hk = &data["proxy-groups"][2];
+        assert_eq!(hk["type"], Value::from("url-test"));
+        assert_eq!(names(&hk["proxies"]), vec!["🇭🇰 HK-01", "🇭🇰 HK-02"]);
+        assert_eq!(
+            logs,
+            vec![
+                SubscriptionLog::GroupExists {
+                    group: "🇺🇸 US".to_string()
+                },
+                SubscriptionLog::UnknownGroup {
+                    group: "Missing".to_string()
+                },
+            ]
+        );
+    }
+}
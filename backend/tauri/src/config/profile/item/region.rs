@@ -0,0 +1,359 @@
+//! Region detection of the node names, and the region `url-test` groups
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+
+/// The region keywords, the uppercase codes are matched case-sensitively as whole words,
+/// the other latin keywords are matched case-insensitively as whole words.
+/// A keyword could be followed by digits (e.g. `HK01`), but not preceded by them (e.g. `10GB`).
+const REGIONS: &[(&str, &[&str])] = &[
+    ("HK", &["香港", "Hong Kong", "HongKong", "HKG", "HK"]),
+    (
+        "TW",
+        &[
+            "台湾", "台灣", "臺灣", "台北", "Taiwan", "Taipei", "TWN", "TW",
+        ],
+    ),
+    ("MO", &["澳门", "澳門", "Macau", "Macao", "MAC", "MO"]),
+    (
+        "JP",
+        &[
+            "日本", "东京", "東京", "大阪", "Japan", "Tokyo", "Osaka", "JPN", "JP",
+        ],
+    ),
+    (
+        "KR",
+        &[
+            "韩国", "韓國", "首尔", "首爾", "Korea", "Seoul", "KOR", "KR",
+        ],
+    ),
+    ("SG", &["新加坡", "狮城", "獅城", "Singapore", "SGP", "SG"]),
+    (
+        "US",
+        &[
+            "美国",
+            "美國",
+            "洛杉矶",
+            "硅谷",
+            "纽约",
+            "United States",
+            "America",
+            "Los Angeles",
+            "San Jose",
+            "Silicon Valley",
+            "Seattle",
+            "New York",
+            "Chicago",
+            "Dallas",
+            "USA",
+            "US",
+        ],
+    ),
+    (
+        "GB",
+        &[
+            "英国",
+            "英國",
+            "伦敦",
+            "United Kingdom",
+            "Britain",
+            "London",
+            "GBR",
+            "UK",
+            "GB",
+        ],
+    ),
+    (
+        "DE",
+        &[
+            "德国",
+            "德國",
+            "法兰克福",
+            "Germany",
+            "Frankfurt",
+            "DEU",
+            "DE",
+        ],
+    ),
+    (
+        "FR",
+        &["法国", "法國", "巴黎", "France", "Paris", "FRA", "FR"],
+    ),
+    (
+        "NL",
+        &[
+            "荷兰",
+            "荷蘭",
+            "阿姆斯特丹",
+            "Netherlands",
+            "Amsterdam",
+            "NLD",
+            "NL",
+        ],
+    ),
+    (
+        "RU",
+        &[
+            "俄罗斯",
+            "俄羅斯",
+            "莫斯科",
+            "Russia",
+            "Moscow",
+            "RUS",
+            "RU",
+        ],
+    ),
+    (
+        "CA",
+        &["加拿大", "多伦多", "Canada", "Toronto", "CAN", "CA"],
+    ),
+    (
+        "AU",
+        &[
+            "澳大利亚",
+            "澳洲",
+            "悉尼",
+            "Australia",
+            "Sydney",
+            "AUS",
+            "AU",
+        ],
+    ),
+    ("IN", &["印度", "孟买", "India", "Mumbai", "IND", "IN"]),
+    (
+        "ID",
+        &["印尼", "印度尼西亚", "Indonesia", "Jakarta", "IDN", "ID"],
+    ),
+    (
+        "TH",
+        &["泰国", "泰國", "曼谷", "Thailand", "Bangkok", "THA", "TH"],
+    ),
+    ("VN", &["越南", "Vietnam", "VNM", "VN"]),
+    (
+        "PH",
+        &["菲律宾", "菲律賓", "Philippines", "Manila", "PHL", "PH"],
+    ),
+    (
+        "MY",
+        &[
+            "马来西亚",
+            "馬來西亞",
+            "Malaysia",
+            "Kuala Lumpur",
+            "MYS",
+            "MY",
+        ],
+    ),
+    (
+        "TR",
+        &["土耳其", "Turkey", "Türkiye", "Istanbul", "TUR", "TR"],
+    ),
+    (
+        "AE",
+        &[
+            "阿联酋",
+            "迪拜",
+            "United Arab Emirates",
+            "Dubai",
+            "UAE",
+            "ARE",
+            "AE",
+        ],
+    ),
+    ("IT", &["意大利", "Italy", "Milan", "ITA", "IT"]),
+    ("ES", &["西班牙", "Spain", "Madrid", "ESP", "ES"]),
+    ("CH", &["瑞士", "Switzerland", "Zurich", "CHE", "CH"]),
+    ("SE", &["瑞典", "Sweden", "Stockholm", "SWE", "SE"]),
+    (
+        "IE",
+        &["爱尔兰", "愛爾蘭", "Ireland", "Dublin", "IRL", "IE"],
+    ),
+    ("UA", &["乌克兰", "烏克蘭", "Ukraine", "Kyiv", "UKR", "UA"]),
+    ("BR", &["巴西", "Brazil", "Sao Paulo", "BRA", "BR"]),
+    ("AR", &["阿根廷", "Argentina", "Buenos Aires", "ARG", "AR"]),
+    ("ZA", &["南非", "South Africa", "Johannesburg", "ZAF", "ZA"]),
+];
+
+/// the flag emoji of an ISO 3166-1 alpha-2 code
+pub fn region_flag(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_uppercase)
+        .filter_map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+static MATCHERS: Lazy<Vec<(&'static str, Regex)>> = Lazy::new(|| {
+    REGIONS
+        .iter()
+        .map(|(code, keywords)| {
+            let is_code = |k: &&&str| k.len() <= 3 && k.chars().all(|c| c.is_ascii_uppercase());
+            let join = |keywords: Vec<&&str>| {
+                keywords
+                    .into_iter()
+                    .map(|k| regex::escape(k))
+                    .collect::<Vec<_>>()
+                    .join("|")
+            };
+            let codes = join(keywords.iter().filter(is_code).collect());
+            let words = join(
+                keywords
+                    .iter()
+                    .filter(|k| !is_code(k) && k.is_ascii())
+                    .collect(),
+            );
+            let mut others = join(keywords.iter().filter(|k| !k.is_ascii()).collect());
+            others.push('|');
+            others.push_str(&regex::escape(&region_flag(code)));
+            let pattern =
+                format!(r"(?:^|[^A-Za-z0-9])((?i:{words})|{codes})(?:[^A-Za-z]|$)|({others})");
+            (*code, Regex::new(&pattern).expect("invalid region pattern"))
+        })
+        .collect()
+});
+
+/// the ISO code of the region mentioned in the name, the earliest and longest mention wins
+pub fn detect_region(name: &str) -> Option<&'static str> {
+    MATCHERS
+        .iter()
+        .filter_map(|(code, regex)| {
+            let captures = regex.captures(name)?;
+            let mention = captures.get(1).or_else(|| captures.get(2))?;
+            Some((mention.start(), std::cmp::Reverse(mention.len()), *code))
+        })
+        .min()
+        .map(|(_, _, code)| code)
+}
+
+/// Rename the nodes by their regions, with a sequence number per region
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(default)]
+pub struct RegionOptions {
+    /// the placeholders are `{flag}`, `{code}`, `{index}` and `{name}`,
+    /// the nodes without a known region keep their names
+    pub template: String,
+    /// the zero padded width of `{index}`
+    pub digits: usize,
+    /// generate a `url-test` group per region
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub groups: Option<RegionGroups>,
+}
+
+impl Default for RegionOptions {
+    fn default() -> Self {
+        Self {
+            template: "{flag} {code}-{index}".to_string(),
+            digits: 2,
+            groups: None,
+        }
+    }
+}
+
+impl RegionOptions {
+    pub fn render(&self, code: &str, index: usize, name: &str) -> String {
+        self.template
+            .replace("{flag}", &region_flag(code))
+            .replace("{code}", code)
+            .replace("{index}", &format!("{index:0width$}", width = self.digits))
+            .replace("{name}", name)
+            .trim()
+            .to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(default)]
+pub struct RegionGroups {
+    /// the placeholders are `{flag}` and `{code}`
+    pub name: String,
+    pub url: String,
+    /// in seconds
+    pub interval: u64,
+    /// in milliseconds
+    pub tolerance: u64,
+    /// the existing groups which the region groups are appended to, e.g. the main `select` group
+    pub inject_into: Vec<String>,
+}
+
+impl Default for RegionGroups {
+    fn default() -> Self {
+        Self {
+            name: "{flag} {code}".to_string(),
+            url: "https://www.gstatic.com/generate_204".to_string(),
+            interval: 300,
+            tolerance: 50,
+            inject_into: Vec::new(),
+        }
+    }
+}
+
+impl RegionGroups {
+    pub fn group_name(&self, code: &str) -> String {
+        self.name
+            .replace("{flag}", &region_flag(code))
+            .replace("{code}", code)
+            .trim()
+            .to_string()
+    }
+
+    /// the `url-test` groups of the regions, region code -> names of the nodes
+    pub fn build(&self, regions: &IndexMap<&str, Vec<String>>) -> Vec<Mapping> {
+        regions
+            .iter()
+            .filter(|(_, nodes)| !nodes.is_empty())
+            .map(|(code, nodes)| {
+                let mut group = Mapping::new();
+                group.insert("name".into(), self.group_name(code).into());
+                group.insert("type".into(), "url-test".into());
+                group.insert(
+                    "proxies".into(),
+                    Value::Sequence(nodes.iter().cloned().map(Value::from).collect()),
+                );
+                group.insert("url".into(), self.url.clone().into());
+                group.insert("interval".into(), self.interval.into());
+                group.insert("tolerance".into(), self.tolerance.into());
+                group
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_region() {
+        let cases = [
+            ("🇯🇵 Tokyo 01", Some("JP")),
+            ("香港 IPLC 02", Some("HK")),
+            ("HK01 | 1x", Some("HK")),
+            ("hong kong premium", Some("HK")),
+            ("印度尼西亚 01", Some("ID")),
+            ("USA Seattle", Some("US")),
+            ("Russia 03", Some("RU")),
+            ("Relay in AU", Some("AU")),
+            ("US → HK", Some("US")),
+            ("Traffic left: 10GB", None),
+            ("Expire 2025-01-01", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(detect_region(name), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let options = RegionOptions::default();
+        assert_eq!(options.render("JP", 1, "Tokyo"), "🇯🇵 JP-01");
+        let options = RegionOptions {
+            template: "{code} {index} {name}".to_string(),
+            digits: 0,
+            groups: None,
+        };
+        assert_eq!(options.render("SG", 12, "Singapore"), "SG 12 Singapore");
+        assert_eq!(RegionGroups::default().group_name("US"), "🇺🇸 US");
+    }
+}
@@ -1,7 +1,8 @@
 use super::{
     ProfileCleanup, ProfileFileIo, ProfileHelper, ProfileMetaGetter, ProfileMetaSetter,
-    ProfileShared, ProfileSharedBuilder, ambassador_impl_ProfileFileIo,
-    ambassador_impl_ProfileMetaGetter, ambassador_impl_ProfileMetaSetter,
+    ProfileShared, ProfileSharedBuilder, ProfileSource, ProfileSourceSync,
+    ambassador_impl_ProfileFileIo, ambassador_impl_ProfileMetaGetter,
+    ambassador_impl_ProfileMetaSetter,
 };
 use crate::{
     config::{ProfileKindGetter, profile::item_type::ProfileItemType},
@@ -27,6 +28,10 @@ pub struct ScriptProfile {
     #[builder_update(nested)]
     pub shared: ProfileShared,
     pub script_type: ScriptType,
+    /// the url which the content is fetched from, `None` for a local-only profile
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ProfileSource>,
 }
 
 impl ScriptProfileBuilder {
@@ -61,3 +66,13 @@ impl ScriptProfile {
 
 impl ProfileHelper for ScriptProfile {}
 impl ProfileCleanup for ScriptProfile {}
+
+impl ProfileSourceSync for ScriptProfile {
+    fn source(&self) -> Option<&ProfileSource> {
+        self.source.as_ref()
+    }
+
+    fn source_mut(&mut self) -> Option<&mut ProfileSource> {
+        self.source.as_mut()
+    }
+}
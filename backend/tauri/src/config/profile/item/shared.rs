@@ -2,6 +2,7 @@ use std::{fmt, str::FromStr};
 
 use ambassador::delegatable_trait;
 use derive_builder::Builder;
+use indexmap::IndexMap;
 use nyanpasu_macro::BuilderUpdate;
 use serde::{Deserialize, Serialize, de::Visitor};
 
@@ -42,6 +43,26 @@ pub struct ProfileShared {
     #[builder(default = "chrono::Local::now().timestamp() as usize")]
     /// update time
     pub updated: usize,
+
+    /// the group (folder) which the profile belongs to
+    #[builder(default, setter(strip_option))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+
+    /// free-form labels of the profile
+    #[builder(default)]
+    #[serde(
+        default,
+        deserialize_with = "deserialize_single_or_vec",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub tags: Vec<String>,
+
+    /// the variables which are substituted into the merge profiles as `${name}`,
+    /// and exposed to the scripts as the `variables` global
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub variables: IndexMap<String, serde_yaml::Value>,
 }
 
 impl ProfileShared {
@@ -138,6 +159,9 @@ impl ProfileSharedBuilder {
             updated: builder
                 .updated
                 .unwrap_or_else(|| chrono::Local::now().timestamp() as usize),
+            group: builder.group.clone().unwrap_or_default(),
+            tags: builder.tags.clone().unwrap_or_default(),
+            variables: builder.variables.clone().unwrap_or_default(),
         })
     }
 }
@@ -168,6 +192,18 @@ impl ProfileMetaGetter for ProfileShared {
     fn file(&self) -> &str {
         &self.file
     }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    fn variables(&self) -> &IndexMap<String, serde_yaml::Value> {
+        &self.variables
+    }
 }
 
 impl ProfileMetaSetter for ProfileShared {
@@ -0,0 +1,179 @@
+use super::{
+    ProfileFileIo, ProfileKindGetter, ProfileMetaGetter, ProfileMetaSetter, RemoteProfileOptions,
+    SubscribeError,
+    remote::{redact_url, request_url},
+};
+use crate::config::profile::item_type::ProfileItemType;
+use anyhow::{Context, Result};
+use reqwest::{
+    StatusCode,
+    header::{self, HeaderMap, HeaderValue},
+};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use sha2::{Digest, Sha256};
+use specta::Type;
+use url::Url;
+
+/// The upstream of a merge or script profile, so that it could be shared by url.
+/// The content in use is pinned to `hash`, the scheduled checks only record
+/// the newer upstream content, and it is applied by an explicit update.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub struct ProfileSource {
+    pub url: Url,
+    /// the proxy, headers, user agent and update interval of the requests
+    #[serde(default)]
+    pub option: RemoteProfileOptions,
+    /// sha256 of the content in use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// sha256 of the newer upstream content, found by the scheduled check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available: Option<String>,
+    /// the validators of the content in use, for the conditional requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// The result of syncing a profile with its source
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+#[serde(tag = "status", content = "hash", rename_all = "snake_case")]
+pub enum SourceSyncOutcome {
+    /// the upstream content is the pinned one
+    Unchanged,
+    /// a newer content is found, but not applied because it is not an explicit update
+    Available(String),
+    /// the newer content is written and pinned
+    Updated(String),
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+impl ProfileSource {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            option: RemoteProfileOptions::default(),
+            hash: None,
+            available: None,
+            etag: None,
+            last_modified: None,
+        }
+    }
+
+    /// the headers to revalidate the pinned content
+    fn conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if self.hash.is_none() {
+            return headers;
+        }
+        if let Some(value) = self
+            .etag
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_NONE_MATCH, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_MODIFIED_SINCE, value);
+        }
+        headers
+    }
+
+    /// fetch the upstream content, `None` if it is not modified since the pinned one
+    async fn fetch(&self) -> Result<Option<(String, HeaderMap)>, SubscribeError> {
+        let resp = request_url(&self.url, &self.option, self.conditional_headers()).await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let headers = resp.headers().clone();
+        let content =
+            resp.text_with_charset("utf-8")
+                .await
+                .map_err(|e| SubscribeError::Network {
+                    url: redact_url(&self.url),
+                    source: e.without_url(),
+                })?;
+        Ok(Some((
+            content.trim_start_matches('\u{feff}').to_string(),
+            headers,
+        )))
+    }
+}
+
+fn validate_content(kind: &ProfileItemType, content: &str) -> Result<()> {
+    if matches!(kind, ProfileItemType::Merge) {
+        serde_yaml::from_str::<Mapping>(content)
+            .context("the upstream content is not a valid merge profile")?;
+    }
+    Ok(())
+}
+
+/// A profile which could be backed by a [`ProfileSource`]
+#[allow(private_bounds)]
+pub trait ProfileSourceSync:
+    ProfileMetaSetter + ProfileMetaGetter + ProfileKindGetter + ProfileFileIo
+{
+    fn source(&self) -> Option<&ProfileSource>;
+
+    fn source_mut(&mut self) -> Option<&mut ProfileSource>;
+
+    /// check the source for a newer content, it is only written if `pin` is set,
+    /// or there is no pinned content yet. The updated time is refreshed on every successful check
+    async fn sync_source(&mut self, pin: bool) -> Result<SourceSyncOutcome> {
+        let now = chrono::Local::now().timestamp() as usize;
+        let kind = self.kind();
+        let Some(source) = self.source() else {
+            anyhow::bail!("profile `{}` is not backed by a url", self.uid());
+        };
+        let Some((content, headers)) = source.fetch().await? else {
+            tracing::debug!("source of `{}` is not modified", self.uid());
+            self.source_mut().unwrap().available = None;
+            self.set_updated(now);
+            return Ok(SourceSyncOutcome::Unchanged);
+        };
+        let hash = content_hash(content.as_bytes());
+        let pinned = source.hash.is_some();
+        let header_value = |name| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let etag = header_value(header::ETAG);
+        let last_modified = header_value(header::LAST_MODIFIED);
+
+        if source.hash.as_ref() == Some(&hash) {
+            let source = self.source_mut().unwrap();
+            source.available = None;
+            source.etag = etag;
+            source.last_modified = last_modified;
+            self.set_updated(now);
+            return Ok(SourceSyncOutcome::Unchanged);
+        }
+        validate_content(&kind, &content)?;
+        if pinned && !pin {
+            tracing::info!("source of `{}` has a newer content: {hash}", self.uid());
+            self.source_mut().unwrap().available = Some(hash.clone());
+            self.set_updated(now);
+            return Ok(SourceSyncOutcome::Available(hash));
+        }
+
+        self.write_file(content).await?;
+        let source = self.source_mut().unwrap();
+        source.hash = Some(hash.clone());
+        source.available = None;
+        source.etag = etag;
+        source.last_modified = last_modified;
+        self.set_updated(now);
+        Ok(SourceSyncOutcome::Updated(hash))
+    }
+}
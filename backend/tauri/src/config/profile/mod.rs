@@ -1,7 +1,9 @@
 pub mod builder;
+pub mod bundle;
 pub mod item;
 pub mod item_type;
 pub mod profiles;
+pub mod schedule;
 
 pub use builder::ProfileBuilder;
 use item::deserialize_single_or_vec;
@@ -2,11 +2,12 @@ use super::{
     builder::ProfileBuilder,
     item::{Profile, prelude::*},
     item_type::ProfileUid,
+    schedule::ProfileSchedule,
 };
 use crate::utils::{dirs, help};
 use anyhow::{Result, bail};
 use derive_builder::Builder;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use nyanpasu_macro::BuilderUpdate;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -32,6 +33,15 @@ pub struct Profiles {
     #[serde(default)]
     /// profile list
     pub items: Vec<Profile>,
+    #[serde(default)]
+    /// the display order of the profile groups
+    pub groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    /// the variables shared by all the profiles, the ones of a profile take precedence
+    pub variables: IndexMap<String, serde_yaml::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// the rules to activate the profiles on schedule
+    pub schedules: Vec<ProfileSchedule>,
 }
 
 impl Default for Profiles {
@@ -45,6 +55,9 @@ impl Default for Profiles {
                 "tcp-concurrent".into(),
             ],
             items: vec![],
+            groups: vec![],
+            variables: IndexMap::new(),
+            schedules: vec![],
         }
     }
 }
@@ -85,9 +98,69 @@ impl Profiles {
             .ok_or_else(|| anyhow::anyhow!("failed to get the profile item \"uid:{uid}\""))
     }
 
+    /// whether the item is activated, or in the chains of the activated items or the global chain
+    pub fn is_in_use(&self, uid: &str) -> bool {
+        self.current.iter().any(|c| c == uid)
+            || self.chain.iter().any(|c| c == uid)
+            || self.current.iter().any(|c| {
+                self.get_item(c).is_ok_and(|item| match item {
+                    Profile::Remote(item) => item.chain.iter().any(|c| c == uid),
+                    Profile::Local(item) => item.chain.iter().any(|c| c == uid),
+                    _ => false,
+                })
+            })
+    }
+
+    /// get the items of the group
+    pub fn get_group_items(&self, group: &str) -> Vec<&Profile> {
+        self.items
+            .iter()
+            .filter(|item| item.group() == Some(group))
+            .collect()
+    }
+
+    /// get the items with the tag
+    pub fn get_tagged_items(&self, tag: &str) -> Vec<&Profile> {
+        self.items
+            .iter()
+            .filter(|item| item.tags().iter().any(|t| t == tag))
+            .collect()
+    }
+
+    /// register the groups used by items but missing in the group list
+    pub(super) fn sync_groups(&mut self) {
+        for item in self.items.iter() {
+            if let Some(group) = item.group()
+                && !self.groups.iter().any(|g| g == group)
+            {
+                self.groups.push(group.to_string());
+            }
+        }
+    }
+
     /// append new item
     pub fn append_item(&mut self, item: Profile) -> Result<()> {
         self.items.push(item);
+        self.sync_groups();
+        self.save_file()
+    }
+
+    /// replace the schedule rules, the invalid ones are rejected
+    pub fn set_schedules(&mut self, schedules: Vec<ProfileSchedule>) -> Result<()> {
+        for schedule in schedules.iter() {
+            schedule.validate()?;
+            if let Some(uid) = schedule
+                .current
+                .iter()
+                .find(|uid| self.get_item(uid).is_err())
+            {
+                bail!(
+                    "the profile \"uid:{uid}\" of the schedule `{}` is not found",
+                    schedule.name
+                );
+            }
+        }
+        self.schedules = schedules;
         self.save_file()
     }
 
@@ -150,10 +223,81 @@ impl Profiles {
             (Profile::Script(item), ProfileBuilder::Script(builder)) => item.apply(builder),
             _ => bail!("profile type mismatch when patching"),
         };
+        self.sync_groups();
 
         self.save_file()
     }
 
+    /// set the chain of all the remote and local items in the group
+    /// return the uids of the updated items
+    pub fn set_group_chain(
+        &mut self,
+        group: &str,
+        chain: Vec<ProfileUid>,
+    ) -> Result<Vec<ProfileUid>> {
+        if let Some(uid) = chain.iter().find(|uid| {
+            !self
+                .get_item(uid)
+                .is_ok_and(|item| item.is_merge() || item.is_script())
+        }) {
+            bail!("\"uid:{uid}\" is not a merge or script profile");
+        }
+        let mut updated = Vec::new();
+        for item in self.items.iter_mut() {
+            if item.group() != Some(group) {
+                continue;
+            }
+            let uid = item.uid().to_string();
+            match item {
+                Profile::Remote(item) => item.chain = chain.clone(),
+                Profile::Local(item) => item.chain = chain.clone(),
+                _ => continue,
+            }
+            updated.push(uid);
+        }
+        self.save_file()?;
+        Ok(updated)
+    }
+
+    /// the items of the group and their chains, with the schedules only activating them,
+    /// used to export the group as a bundle
+    pub fn group_subset(&self, group: &str) -> Result<Self> {
+        let items = self.get_group_items(group);
+        if items.is_empty() {
+            bail!("group \"{group}\" is empty");
+        }
+        let mut uids = items
+            .iter()
+            .map(|item| item.uid().to_string())
+            .collect::<IndexSet<_>>();
+        for item in items.iter() {
+            let chain = match item {
+                Profile::Remote(item) => &item.chain,
+                Profile::Local(item) => &item.chain,
+                _ => continue,
+            };
+            uids.extend(chain.iter().cloned());
+        }
+
+        Ok(Self {
+            current: vec![],
+            chain: vec![],
+            valid: self.valid.clone(),
+            items: uids
+                .iter()
+                .map(|uid| self.get_item(uid).cloned())
+                .collect::<Result<_>>()?,
+            groups: vec![group.to_string()],
+            variables: self.variables.clone(),
+            schedules: self
+                .schedules
+                .iter()
+                .filter(|schedule| schedule.current.iter().all(|uid| uids.contains(uid)))
+                .cloned()
+                .collect(),
+        })
+    }
+
     /// replace item
     pub fn replace_item<T: Borrow<String>>(&mut self, uid: T, item: Profile) -> Result<()> {
         let uid = uid.borrow();
@@ -179,6 +323,9 @@ impl Profiles {
         if let Some(index) = index {
             let mut profile = items.remove(index);
             profile.remove_file().await?;
+            if let Profile::Remote(profile) = &profile {
+                profile.remove_upstream().await?;
+            }
         }
 
         // delete the original uid
@@ -202,6 +349,21 @@ impl Profiles {
         Ok(is_current)
     }
 
+    /// delete the items with the tag
+    /// if any of them is current then return true
+    pub async fn delete_tagged_items(&mut self, tag: &str) -> Result<(Vec<ProfileUid>, bool)> {
+        let uids = self
+            .get_tagged_items(tag)
+            .iter()
+            .map(|item| item.uid().to_string())
+            .collect::<Vec<_>>();
+        let mut is_current = false;
+        for uid in uids.iter() {
+            is_current |= self.delete_item(uid).await?;
+        }
+        Ok((uids, is_current))
+    }
+
     /// 获取current指向的配置内容
     pub fn current_mappings(&self) -> Result<IndexMap<&str, Mapping>> {
         let current = self
@@ -0,0 +1,94 @@
+//! Activate a set of profiles on a cron schedule, e.g. a work profile during office hours
+use super::item_type::ProfileUid;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize, specta::Type)]
+pub struct ProfileSchedule {
+    pub name: String,
+    /// the standard crontab syntax `minute hour day month weekday` in local time, e.g. `0 9 * * 1-5`,
+    /// the syntax of the timer with the leading seconds and the trailing years is accepted as well
+    pub cron: String,
+    /// the profiles to activate, it replaces `current` when the schedule fires
+    pub current: Vec<ProfileUid>,
+    #[serde(default = "default_enable")]
+    pub enable: bool,
+}
+
+fn default_enable() -> bool {
+    true
+}
+
+impl ProfileSchedule {
+    /// the cron expression understood by the timer
+    pub fn timer_cron(&self) -> Result<String> {
+        normalize_cron(&self.cron)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.current.is_empty() {
+            bail!("the schedule `{}` activates no profile", self.name);
+        }
+        self.timer_cron()?;
+        Ok(())
+    }
+}
+
+/// The timer expects the seconds field, and counts the weekdays from Sunday as 1,
+/// so the standard 5-field expressions are given a zero second and the weekday names.
+pub fn normalize_cron(cron: &str) -> Result<String> {
+    let cron = cron.trim();
+    if cron.starts_with('@') {
+        return Ok(cron.to_string());
+    }
+    let fields = cron.split_whitespace().collect::<Vec<_>>();
+    match fields.len() {
+        5 => {
+            let weekday = fields[4]
+                .split(',')
+                .map(|part| match part.split_once('/') {
+                    Some((range, step)) => format!("{}/{step}", weekday_names(range)),
+                    None => weekday_names(part),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            Ok(format!("0 {} {weekday}", fields[..4].join(" ")))
+        }
+        6 | 7 => Ok(fields.join(" ")),
+        _ => bail!("invalid cron expression `{cron}`, expected 5 fields"),
+    }
+}
+
+/// replace the crontab weekday numbers, where both 0 and 7 are Sunday, with their names
+fn weekday_names(range: &str) -> String {
+    range
+        .split('-')
+        .map(|day| match day.parse::<usize>() {
+            Ok(day) if day <= 7 => WEEKDAYS[day % 7].to_string(),
+            _ => day.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_cron() {
+        let cases = [
+            ("0 9 * * 1-5", "0 0 9 * * MON-FRI"),
+            ("30 18 * * 0,6", "0 30 18 * * SUN,SAT"),
+            ("0 */2 * * */2", "0 0 */2 * * */2"),
+            ("0 0 9 * * Mon-Fri", "0 0 9 * * Mon-Fri"),
+            (" @daily ", "@daily"),
+        ];
+        for (cron, expected) in cases {
+            assert_eq!(normalize_cron(cron).unwrap(), expected, "{cron}");
+        }
+        assert!(normalize_cron("0 9 * *").is_err());
+    }
+}
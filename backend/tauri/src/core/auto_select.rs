@@ -0,0 +1,429 @@
+//! Drive the `Selector` groups by the latency history of the app.
+//! Each policy tests its group on its own interval, scores the proxies over the latest tests,
+//! and selects the chosen proxy by [`feat::select_proxy`], every switch is logged with the reason.
+use crate::{
+    config::{
+        Config,
+        nyanpasu::{AutoSelectPolicy, AutoSelectStrategy},
+        profile::item::detect_region,
+    },
+    core::clash::{
+        api::{ClashApi, ClashController},
+        latency::{self, LatencyHistory, LatencyRecord, LatencyStats, LatencyTestOptions},
+    },
+    feat,
+};
+use anyhow::{Result, bail};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use specta::Type;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+use tauri::Manager;
+
+const SWITCHES_LEN: usize = 50;
+
+/// The score of a proxy over the window
+#[derive(Debug, Clone, PartialEq, Serialize, Type)]
+pub struct CandidateScore {
+    pub name: String,
+    /// the median of the medians of the tests, `None` if all of them failed
+    pub median: Option<u64>,
+    /// the mean loss of the tests, 1 if the proxy is never tested
+    pub loss: f64,
+}
+
+impl CandidateScore {
+    /// score the latest `window` records, the records are the oldest first
+    pub fn from_records(name: String, records: &[LatencyRecord], window: usize) -> Self {
+        let records = &records[records.len().saturating_sub(window.max(1))..];
+        let medians = records
+            .iter()
+            .map(|record| record.stats.median)
+            .collect::<Vec<_>>();
+        let loss = if records.is_empty() {
+            1.0
+        } else {
+            records.iter().map(|record| record.stats.loss).sum::<f64>() / records.len() as f64
+        };
+        Self {
+            name,
+            median: LatencyStats::from_samples(&medians).median,
+            loss,
+        }
+    }
+
+    fn available(&self) -> bool {
+        self.median.is_some() && self.loss < 1.0
+    }
+}
+
+fn lowest_latency<'a>(
+    candidates: impl IntoIterator<Item = &'a CandidateScore>,
+) -> Option<&'a CandidateScore> {
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.available())
+        .min_by_key(|candidate| candidate.median)
+}
+
+fn describe(candidate: Option<&CandidateScore>) -> String {
+    match candidate {
+        Some(CandidateScore {
+            name,
+            median: Some(median),
+            loss,
+        }) => format!("`{name}` ({median}ms, {:.0}% loss)", loss * 100.0),
+        Some(CandidateScore { name, .. }) => format!("`{name}` (unavailable)"),
+        None => "none".to_string(),
+    }
+}
+
+/// Choose the proxy by the strategy, return the proxy and the reason if it is not the current one
+pub fn choose(
+    strategy: &AutoSelectStrategy,
+    current: Option<&str>,
+    candidates: &[CandidateScore],
+) -> Option<(String, String)> {
+    let current = current.and_then(|current| candidates.iter().find(|c| c.name == current));
+    let (chosen, reason) = match strategy {
+        AutoSelectStrategy::LowestLatency => {
+            let chosen = lowest_latency(candidates)?;
+            (chosen, "lowest median latency".to_string())
+        }
+        AutoSelectStrategy::LowestLoss => {
+            let chosen = candidates
+                .iter()
+                .filter(|candidate| candidate.available())
+                .min_by(|a, b| a.loss.total_cmp(&b.loss).then(a.median.cmp(&b.median)))?;
+            (chosen, "lowest loss".to_string())
+        }
+        AutoSelectStrategy::Sticky {
+            hysteresis,
+            max_loss,
+        } => {
+            let chosen = lowest_latency(candidates)?;
+            match current {
+                Some(current) if current.available() && current.loss <= *max_loss => {
+                    // both are available, so that the medians are some
+                    let (Some(current_median), Some(chosen_median)) =
+                        (current.median, chosen.median)
+                    else {
+                        return None;
+                    };
+                    if chosen_median.saturating_add(*hysteresis) >= current_median {
+                        return None;
+                    }
+                    (chosen, format!("faster by more than {hysteresis}ms"))
+                }
+                Some(current) if current.available() => (
+                    chosen,
+                    format!(
+                        "the loss of the current proxy exceeds {:.0}%",
+                        max_loss * 100.0
+                    ),
+                ),
+                _ => (chosen, "the current proxy is unavailable".to_string()),
+            }
+        }
+        AutoSelectStrategy::PreferredRegion { regions } => regions
+            .iter()
+            .find_map(|region| {
+                lowest_latency(candidates.iter().filter(|candidate| {
+                    detect_region(&candidate.name)
+                        .is_some_and(|code| code.eq_ignore_ascii_case(region.trim()))
+                }))
+                .map(|chosen| {
+                    (
+                        chosen,
+                        format!("lowest latency in the preferred region `{region}`"),
+                    )
+                })
+            })
+            .or_else(|| {
+                lowest_latency(candidates).map(|chosen| {
+                    (
+                        chosen,
+                        "no preferred region is available, lowest latency".to_string(),
+                    )
+                })
+            })?,
+    };
+    if current.is_some_and(|current| current.name == chosen.name) {
+        return None;
+    }
+    let reason = format!(
+        "{reason}: {} over {}",
+        describe(Some(chosen)),
+        describe(current)
+    );
+    Some((chosen.name.clone(), reason))
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct AutoSelectSwitch {
+    pub timestamp: i64,
+    pub group: String,
+    pub from: Option<String>,
+    pub to: String,
+    pub reason: String,
+    pub error: Option<String>,
+}
+
+pub struct AutoSelect {
+    /// the group -> when its policy ran last time
+    last_run: Mutex<HashMap<String, Instant>>,
+    switches: Mutex<VecDeque<AutoSelectSwitch>>,
+}
+
+impl AutoSelect {
+    pub fn global() -> &'static AutoSelect {
+        static AUTO_SELECT: OnceCell<AutoSelect> = OnceCell::new();
+
+        AUTO_SELECT.get_or_init(|| AutoSelect {
+            last_run: Mutex::new(HashMap::new()),
+            switches: Mutex::new(VecDeque::with_capacity(SWITCHES_LEN)),
+        })
+    }
+
+    /// the latest switches, the newest first
+    pub fn switches(&self) -> Vec<AutoSelectSwitch> {
+        self.switches.lock().iter().rev().cloned().collect()
+    }
+
+    /// run the policies whose interval has elapsed
+    pub async fn run_due(&self) {
+        let config = Config::verge().latest().get_auto_select();
+        if !config.enable {
+            return;
+        }
+        let mut due = Vec::new();
+        {
+            let mut last_run = self.last_run.lock();
+            last_run.retain(|group, _| config.policies.iter().any(|p| &p.group == group));
+            for policy in config.policies {
+                let interval = Duration::from_secs(policy.interval);
+                if last_run
+                    .get(&policy.group)
+                    .is_some_and(|last| last.elapsed() < interval)
+                {
+                    continue;
+                }
+                last_run.insert(policy.group.clone(), Instant::now());
+                due.push(policy);
+            }
+        }
+        for policy in due {
+            if let Err(err) = self.run_policy(&policy).await {
+                log::warn!(target: "app", "auto select of `{}` failed: {err:?}", policy.group);
+            }
+        }
+    }
+
+    /// test the group, and switch to the chosen proxy
+    pub async fn run_policy(&self, policy: &AutoSelectPolicy) -> Result<Option<AutoSelectSwitch>> {
+        let controller = ClashController::current()?;
+        let proxies = controller.get_proxies().await?.proxies;
+        let Some(group) = proxies.get(&policy.group) else {
+            bail!("proxy group `{}` not found", policy.group);
+        };
+        if group.r#type != "Selector" {
+            bail!(
+                "`{}` is a {} group, not a Selector",
+                policy.group,
+                group.r#type
+            );
+        }
+
+        let options = LatencyTestOptions {
+            url: policy.url.clone(),
+            ..Default::default()
+        };
+        let results =
+            latency::test_latency(&controller, Some(&policy.group), &options, |_| {}).await?;
+        let Some(history) = crate::consts::app_handle().try_state::<LatencyHistory>() else {
+            bail!("the latency history is unavailable");
+        };
+        history.record(chrono::Utc::now().timestamp(), &results)?;
+        let candidates = results
+            .into_iter()
+            .map(|result| {
+                let records = history.get(&result.name)?;
+                Ok(CandidateScore::from_records(
+                    result.name,
+                    &records,
+                    policy.window,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let Some((to, reason)) = choose(&policy.strategy, group.now.as_deref(), &candidates) else {
+            return Ok(None);
+        };
+        let mut switch = AutoSelectSwitch {
+            timestamp: chrono::Local::now().timestamp(),
+            group: policy.group.clone(),
+            from: group.now.clone(),
+            to,
+            reason,
+            error: None,
+        };
+        if let Err(err) = feat::select_proxy(&switch.group, &switch.to).await {
+            switch.error = Some(format!("{err:#}"));
+        }
+        match &switch.error {
+            Some(error) => {
+                log::error!(target: "app", "auto select: {switch:?}, failed: {error}")
+            }
+            None => log::info!(
+                target: "app",
+                "auto select: switch `{}` from {:?} to `{}`, {}",
+                switch.group,
+                switch.from,
+                switch.to,
+                switch.reason
+            ),
+        }
+        let mut switches = self.switches.lock();
+        if switches.len() >= SWITCHES_LEN {
+            switches.pop_front();
+        }
+        switches.push_back(switch.clone());
+        Ok(Some(switch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn candidate(name: &str, median: Option<u64>, loss: f64) -> CandidateScore {
+        CandidateScore {
+            name: name.to_string(),
+            median,
+            loss,
+        }
+    }
+
+    fn candidates() -> Vec<CandidateScore> {
+        vec![
+            candidate("HK 01", Some(120), 0.0),
+            candidate("HK 02", Some(90), 0.4),
+            candidate("JP 01", Some(60), 0.2),
+            candidate("US 01", None, 1.0),
+        ]
+    }
+
+    fn chosen(strategy: &AutoSelectStrategy, current: Option<&str>) -> Option<String> {
+        choose(strategy, current, &candidates()).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn test_candidate_score() {
+        let record = |median, loss| LatencyRecord {
+            time: 0,
+            stats: LatencyStats {
+                median,
+                jitter: None,
+                loss,
+            },
+        };
+        let records = vec![
+            record(Some(500), 0.0),
+            record(Some(100), 0.0),
+            record(None, 1.0),
+            record(Some(120), 0.0),
+        ];
+        // the oldest record is out of the window
+        let score = CandidateScore::from_records("HK".to_string(), &records, 3);
+        assert_eq!(score.median, Some(110));
+        assert!((score.loss - 1.0 / 3.0).abs() < f64::EPSILON);
+
+        let score = CandidateScore::from_records("JP".to_string(), &[], 3);
+        assert_eq!(score.median, None);
+        assert!(!score.available());
+    }
+
+    #[test]
+    fn test_lowest_latency_and_loss() {
+        assert_eq!(
+            chosen(&AutoSelectStrategy::LowestLatency, Some("HK 01")),
+            Some("JP 01".to_string())
+        );
+        assert_eq!(
+            chosen(&AutoSelectStrategy::LowestLatency, Some("JP 01")),
+            None
+        );
+        assert_eq!(
+            chosen(&AutoSelectStrategy::LowestLoss, Some("JP 01")),
+            Some("HK 01".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sticky() {
+        let sticky = |hysteresis| AutoSelectStrategy::Sticky {
+            hysteresis,
+            max_loss: 0.3,
+        };
+        // 60ms is not faster than 120ms by more than 100ms
+        assert_eq!(chosen(&sticky(100), Some("HK 01")), None);
+        assert_eq!(
+            chosen(&sticky(50), Some("HK 01")),
+            Some("JP 01".to_string())
+        );
+        // the loss of HK 02 exceeds the limit
+        assert_eq!(
+            chosen(&sticky(100), Some("HK 02")),
+            Some("JP 01".to_string())
+        );
+        let (name, reason) = choose(&sticky(100), Some("US 01"), &candidates()).unwrap();
+        assert_eq!(name, "JP 01");
+        assert_eq!(
+            reason,
+            "the current proxy is unavailable: `JP 01` (60ms, 20% loss) over `US 01` (unavailable)"
+        );
+    }
+
+    #[test]
+    fn test_preferred_region() {
+        let strategy = AutoSelectStrategy::PreferredRegion {
+            regions: vec!["us".to_string(), "hk".to_string()],
+        };
+        // US 01 is unavailable, the fastest of hk is chosen
+        assert_eq!(chosen(&strategy, Some("JP 01")), Some("HK 02".to_string()));
+        let strategy = AutoSelectStrategy::PreferredRegion {
+            regions: vec!["sg".to_string()],
+        };
+        assert_eq!(chosen(&strategy, None), Some("JP 01".to_string()));
+
+        // the regions are matched by the codes, not by the substrings of the names
+        let candidates = vec![
+            candidate("Russia 01", Some(30), 0.0),
+            candidate("Australia 01", Some(40), 0.0),
+            candidate("United States 01", Some(150), 0.0),
+        ];
+        let strategy = AutoSelectStrategy::PreferredRegion {
+            regions: vec!["us".to_string()],
+        };
+        let (name, _) = choose(&strategy, None, &candidates).unwrap();
+        assert_eq!(name, "United States 01");
+    }
+
+    #[test]
+    fn test_sticky_overflow() {
+        let candidates = vec![
+            candidate("HK 01", Some(u64::MAX), 0.0),
+            candidate("JP 01", Some(u64::MAX - 1), 0.0),
+        ];
+        let strategy = AutoSelectStrategy::Sticky {
+            hysteresis: u64::MAX,
+            max_loss: 0.5,
+        };
+        assert_eq!(choose(&strategy, Some("HK 01"), &candidates), None);
+    }
+}
@@ -1,8 +1,11 @@
+use super::ws::ClashConnection;
 use crate::config::Config;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
 use indexmap::IndexMap;
 use reqwest::{Method, StatusCode, header::HeaderMap};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_yaml::Mapping;
 use specta::Type;
 use std::{
@@ -16,22 +19,15 @@ use url::Url;
 /// path 是绝对路径
 #[instrument]
 pub async fn put_configs(config_path: &str) -> Result<()> {
-    let path = "/configs";
-
-    let mut data = HashMap::new();
-    data.insert("path", config_path);
-
-    let _ = perform_request((Method::PUT, path, Data(data))).await?;
-
-    Ok(())
+    ClashController::current()?
+        .put_configs(config_path, false)
+        .await
 }
 
 /// PATCH /configs
 #[instrument]
 pub async fn patch_configs(config: &Mapping) -> Result<()> {
-    let path = "/configs";
-    let _ = perform_request((Method::PATCH, path, Data(config))).await?;
-    Ok(())
+    ClashController::current()?.patch_configs(config).await
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
@@ -41,14 +37,14 @@ pub struct ProxiesRes {
     pub proxies: IndexMap<String, ProxyItem>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default, Type)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyItemHistory {
     pub time: String,
     pub delay: i64,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Default, Type)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxyItem {
     pub name: String,
@@ -79,6 +75,7 @@ impl From<ProxyProviderItem> for ProxyItem {
             vehicle_type: _,
             test_url: _,
             expected_status: _,
+            updated_at: _,
         } = item;
 
         let now = proxies
@@ -110,9 +107,7 @@ impl From<ProxyProviderItem> for ProxyItem {
 /// 获取代理列表
 #[instrument]
 pub async fn get_proxies() -> Result<ProxiesRes> {
-    let path = "/proxies";
-    let resp: ProxiesRes = perform_request((Method::GET, path)).await?.json().await?;
-    Ok(resp)
+    ClashController::current()?.get_proxies().await
 }
 
 /// GET /proxies/{name}
@@ -123,12 +118,7 @@ pub async fn get_proxies() -> Result<ProxiesRes> {
 #[allow(dead_code)]
 #[instrument]
 pub async fn get_proxy(name: String) -> Result<ProxyItem> {
-    let path = format!("/proxies/{name}");
-    let resp: ProxyItem = perform_request((Method::GET, path.as_str()))
-        .await?
-        .json()
-        .await?;
-    Ok(resp)
+    ClashController::current()?.get_proxy(&name).await
 }
 
 /// PUT /proxies/{group}
@@ -137,13 +127,7 @@ pub async fn get_proxy(name: String) -> Result<ProxyItem> {
 /// name: 代理名称
 #[instrument]
 pub async fn update_proxy(group: &str, name: &str) -> Result<()> {
-    let path = format!("/proxies/{group}");
-
-    let mut data = HashMap::new();
-    data.insert("name", name);
-
-    let _ = perform_request((Method::PUT, path.as_str(), Data(data))).await?;
-    Ok(())
+    ClashController::current()?.update_proxy(group, name).await
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
@@ -152,6 +136,7 @@ pub enum VehicleType {
     #[serde(rename = "HTTP")]
     Http,
     Compatible,
+    Inline,
     Unknown,
 }
 
@@ -183,6 +168,8 @@ pub struct ProxyProviderItem {
     pub test_url: Option<String>, // Mihomo Only
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expected_status: Option<String>, // Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>, // Mihomo Only
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Type)]
@@ -196,9 +183,7 @@ pub struct ProvidersProxiesRes {
 /// 获取所有代理集合的所有代理信息
 #[instrument]
 pub async fn get_providers_proxies() -> Result<ProvidersProxiesRes> {
-    let path = "/providers/proxies";
-    let resp: ProvidersProxiesRes = perform_request((Method::GET, path)).await?.json().await?;
-    Ok(resp)
+    ClashController::current()?.get_providers_proxies().await
 }
 
 /// GET /providers/proxies/:name
@@ -207,12 +192,9 @@ pub async fn get_providers_proxies() -> Result<ProvidersProxiesRes> {
 #[allow(dead_code)]
 #[instrument]
 pub async fn get_providers_proxies_group(group: String) -> Result<ProxyProviderItem> {
-    let path = format!("/providers/proxies/{group}");
-    let resp: ProxyProviderItem = perform_request((Method::GET, path.as_str()))
-        .await?
-        .json()
-        .await?;
-    Ok(resp)
+    ClashController::current()?
+        .get_providers_proxies_group(&group)
+        .await
 }
 
 /// PUT /providers/proxies/:name
@@ -220,9 +202,19 @@ pub async fn get_providers_proxies_group(group: String) -> Result<ProxyProviderI
 /// name: 代理集合名称
 #[instrument]
 pub async fn update_providers_proxies_group(name: &str) -> Result<()> {
-    let path = format!("/providers/proxies/{name}");
-    let _ = perform_request((Method::PUT, path.as_str())).await?;
-    Ok(())
+    ClashController::current()?
+        .update_providers_proxies_group(name)
+        .await
+}
+
+/// PUT /providers/rules/:name
+/// 更新规则集合
+/// name: 规则集合名称
+#[instrument]
+pub async fn update_providers_rules_group(name: &str) -> Result<()> {
+    ClashController::current()?
+        .update_providers_rules_group(name)
+        .await
 }
 
 /// GET /providers/proxies/:name/healthcheck
@@ -231,189 +223,504 @@ pub async fn update_providers_proxies_group(name: &str) -> Result<()> {
 #[allow(dead_code)]
 #[instrument]
 pub async fn get_providers_proxies_healthcheck(name: String) -> Result<Mapping> {
-    let path = format!("/providers/proxies/{name}/healthcheck");
-    let resp: Mapping = perform_request((Method::GET, path.as_str()))
-        .await?
-        .json()
-        .await?;
-    Ok(resp)
+    ClashController::current()?
+        .get_providers_proxies_healthcheck(&name)
+        .await
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize, Type)]
 pub struct DelayRes {
-    delay: u64,
+    pub delay: u64,
 }
 
+pub const DEFAULT_DELAY_TEST_URL: &str = "http://www.gstatic.com/generate_204";
+
 /// GET /proxies/{name}/delay
 /// 获取代理延迟
 #[instrument]
 pub async fn get_proxy_delay(name: String, test_url: Option<String>) -> Result<DelayRes> {
-    let path = format!("/proxies/{name}/delay");
-    let default_url = "http://www.gstatic.com/generate_204";
     let test_url = test_url
-        .map(|s| if s.is_empty() { default_url.into() } else { s })
-        .unwrap_or(default_url.into());
+        .filter(|s| !s.is_empty())
+        .unwrap_or(DEFAULT_DELAY_TEST_URL.into());
+    ClashController::current()?
+        .get_proxy_delay(&name, &test_url, 10000, None)
+        .await
+}
 
-    let query = Query([("timeout", "10000"), ("url", &test_url)]);
-    let resp: DelayRes = perform_request((Method::GET, path.as_str(), query))
-        .await?
-        .json()
-        .await?;
-    Ok(resp)
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionsRes {
+    pub download_total: u64,
+    pub upload_total: u64,
+    /// it is `null` if there are no connections
+    #[serde(default)]
+    pub connections: Option<Vec<ClashConnection>>,
 }
 
-/// 根据clash info获取clash服务地址和请求头
-#[instrument]
-fn clash_client_info() -> Result<(String, HeaderMap)> {
-    let client = { Config::clash().data().get_client_info() };
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+pub struct VersionRes {
+    pub version: String,
+    /// Mihomo Only
+    #[serde(default)]
+    pub meta: bool,
+    /// Premium Only
+    #[serde(default)]
+    pub premium: bool,
+}
 
-    let server = format!("http://{}", client.server);
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleItem {
+    /// the index in the rules, Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+    pub r#type: String,
+    pub payload: String,
+    pub proxy: String,
+    /// the count of the rules of a `RULE-SET`, -1 for the others, Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<i64>,
+}
 
-    let mut headers = HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse()?);
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RulesRes {
+    #[serde(default)]
+    pub rules: Vec<RuleItem>,
+}
 
-    if let Some(secret) = client.secret {
-        let secret = format!("Bearer {secret}").parse()?;
-        headers.insert("Authorization", secret);
-    }
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleProviderItem {
+    pub name: String,
+    pub r#type: ProviderType,
+    pub vehicle_type: VehicleType,
+    /// `domain`, `ipcidr` or `classical`
+    pub behavior: String,
+    /// `yaml`, `text` or `mrs`, Mihomo Only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub rule_count: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
 
-    Ok((server, headers))
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvidersRulesRes {
+    #[serde(default)]
+    pub providers: IndexMap<String, RuleProviderItem>,
 }
 
-/// The Request Parameters
-struct PerformRequest<D = (), Q = ()> {
-    method: reqwest::Method,
-    path: String,
-    query: Option<Q>,
-    data: Option<D>,
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "PascalCase")]
+pub struct DnsQuestion {
+    pub name: String,
+    /// the numeric record type, e.g. 1 for `A`
+    #[serde(rename = "Qtype")]
+    pub r#type: u16,
+    #[serde(rename = "Qclass")]
+    pub class: u16,
 }
-/// A newtype wrapper for query parameters
-struct Query<T>(T);
-/// A newtype wrapper for request body
-struct Data<T>(T);
 
-impl From<(reqwest::Method, &str)> for PerformRequest<(), ()> {
-    fn from((method, path): (reqwest::Method, &str)) -> Self {
-        Self {
-            method,
-            path: path.to_string(),
-            data: None,
-            query: None,
-        }
-    }
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct DnsAnswer {
+    pub name: String,
+    pub r#type: u16,
+    #[serde(rename = "TTL")]
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// The DNS message answered by the core, Mihomo Only
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+#[serde(rename_all = "PascalCase")]
+pub struct DnsQueryRes {
+    /// the rcode, 0 for `NOERROR`
+    pub status: u16,
+    #[serde(default)]
+    pub question: Vec<DnsQuestion>,
+    #[serde(default)]
+    pub answer: Vec<DnsAnswer>,
+    #[serde(default)]
+    pub authority: Vec<DnsAnswer>,
+    #[serde(default)]
+    pub additional: Vec<DnsAnswer>,
+}
+
+/// A request to the external controller
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    pub method: Method,
+    pub path: String,
+    pub query: Option<Vec<(String, String)>>,
+    pub data: Option<serde_json::Value>,
 }
 
-impl<T> From<(reqwest::Method, &str, Data<T>)> for PerformRequest<T, ()>
-where
-    T: Serialize,
-{
-    fn from((method, path, Data(data)): (reqwest::Method, &str, Data<T>)) -> Self {
+impl ApiRequest {
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
         Self {
             method,
-            path: path.to_string(),
-            data: Some(data),
+            path: path.into(),
             query: None,
+            data: None,
         }
     }
+
+    pub fn query<K: Into<String>, V: Into<String>>(
+        mut self,
+        query: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        self.query = Some(
+            query
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+
+    pub fn data(mut self, data: impl Serialize) -> Result<Self> {
+        self.data = Some(serde_json::to_value(data)?);
+        Ok(self)
+    }
 }
 
-impl<T> From<(reqwest::Method, &str, Query<T>)> for PerformRequest<(), T>
-where
-    T: Serialize,
-{
-    fn from((method, path, Query(query)): (reqwest::Method, &str, Query<T>)) -> Self {
-        Self {
-            method,
-            path: path.to_string(),
-            data: None,
-            query: Some(query),
+fn parse<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    serde_json::from_slice(bytes).context("failed to parse the response")
+}
+
+fn delay_query(url: &str, timeout: u64, expected: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut query = vec![("timeout", timeout.to_string()), ("url", url.to_string())];
+    if let Some(expected) = expected {
+        query.push(("expected", expected.to_string()));
+    }
+    query
+}
+
+/// The REST api of clash and mihomo.
+/// The operations are built upon [`ClashApi::request`], implement it to mock the api
+#[async_trait]
+pub trait ClashApi: Send + Sync {
+    /// perform the request, return the body of the successful response
+    async fn request(&self, request: ApiRequest) -> Result<Bytes>;
+
+    /// GET /version
+    async fn get_version(&self) -> Result<VersionRes> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, "/version"))
+                .await?,
+        )
+    }
+
+    /// PUT /configs
+    /// force: 强制重载，Mihomo 会重新加载端口等配置
+    async fn put_configs(&self, path: &str, force: bool) -> Result<()> {
+        let mut request =
+            ApiRequest::new(Method::PUT, "/configs").data(HashMap::from([("path", path)]))?;
+        if force {
+            request = request.query([("force", "true")]);
         }
+        self.request(request).await?;
+        Ok(())
     }
-}
 
-impl<D, Q> From<(reqwest::Method, &str, Query<Q>, Data<D>)> for PerformRequest<D, Q>
-where
-    D: Serialize,
-    Q: Serialize,
-{
-    fn from(
-        (method, path, Query(query), Data(data)): (reqwest::Method, &str, Query<Q>, Data<D>),
-    ) -> Self {
-        Self {
-            method,
-            path: path.to_string(),
-            data: Some(data),
-            query: Some(query),
+    /// PATCH /configs
+    async fn patch_configs(&self, config: &Mapping) -> Result<()> {
+        self.request(ApiRequest::new(Method::PATCH, "/configs").data(config)?)
+            .await?;
+        Ok(())
+    }
+
+    /// POST /restart
+    /// 重启内核进程，Mihomo Only
+    async fn restart(&self) -> Result<()> {
+        self.request(ApiRequest::new(Method::POST, "/restart"))
+            .await?;
+        Ok(())
+    }
+
+    /// POST /upgrade
+    /// 升级内核，Mihomo Only
+    async fn upgrade(&self) -> Result<()> {
+        self.request(ApiRequest::new(Method::POST, "/upgrade"))
+            .await?;
+        Ok(())
+    }
+
+    /// GET /proxies
+    /// 获取代理列表
+    async fn get_proxies(&self) -> Result<ProxiesRes> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, "/proxies"))
+                .await?,
+        )
+    }
+
+    /// GET /proxies/{name}
+    async fn get_proxy(&self, name: &str) -> Result<ProxyItem> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, format!("/proxies/{name}")))
+                .await?,
+        )
+    }
+
+    /// PUT /proxies/{group}
+    async fn update_proxy(&self, group: &str, name: &str) -> Result<()> {
+        let request = ApiRequest::new(Method::PUT, format!("/proxies/{group}"))
+            .data(HashMap::from([("name", name)]))?;
+        self.request(request).await?;
+        Ok(())
+    }
+
+    /// GET /proxies/{name}/delay
+    /// timeout: 毫秒
+    /// expected: 期望的状态码，如 `204` 或 `200-299`
+    async fn get_proxy_delay(
+        &self,
+        name: &str,
+        url: &str,
+        timeout: u64,
+        expected: Option<&str>,
+    ) -> Result<DelayRes> {
+        let request = ApiRequest::new(Method::GET, format!("/proxies/{name}/delay"))
+            .query(delay_query(url, timeout, expected));
+        parse(&self.request(request).await?)
+    }
+
+    /// GET /group/{name}/delay, Mihomo Only
+    /// 测试代理组内所有代理的延迟，返回 代理名称 -> 延迟，失败的代理不在其中
+    async fn get_group_delay(
+        &self,
+        name: &str,
+        url: &str,
+        timeout: u64,
+        expected: Option<&str>,
+    ) -> Result<IndexMap<String, u64>> {
+        let request = ApiRequest::new(Method::GET, format!("/group/{name}/delay"))
+            .query(delay_query(url, timeout, expected));
+        parse(&self.request(request).await?)
+    }
+
+    /// GET /providers/proxies
+    async fn get_providers_proxies(&self) -> Result<ProvidersProxiesRes> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, "/providers/proxies"))
+                .await?,
+        )
+    }
+
+    /// GET /providers/proxies/{name}
+    async fn get_providers_proxies_group(&self, name: &str) -> Result<ProxyProviderItem> {
+        parse(
+            &self
+                .request(ApiRequest::new(
+                    Method::GET,
+                    format!("/providers/proxies/{name}"),
+                ))
+                .await?,
+        )
+    }
+
+    /// PUT /providers/proxies/{name}
+    async fn update_providers_proxies_group(&self, name: &str) -> Result<()> {
+        self.request(ApiRequest::new(
+            Method::PUT,
+            format!("/providers/proxies/{name}"),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// GET /providers/proxies/{name}/healthcheck
+    async fn get_providers_proxies_healthcheck(&self, name: &str) -> Result<Mapping> {
+        let bytes = self
+            .request(ApiRequest::new(
+                Method::GET,
+                format!("/providers/proxies/{name}/healthcheck"),
+            ))
+            .await?;
+        // it responds `204 No Content` in the recent cores
+        if bytes.is_empty() {
+            return Ok(Mapping::new());
         }
+        parse(&bytes)
+    }
+
+    /// GET /rules
+    async fn get_rules(&self) -> Result<RulesRes> {
+        parse(&self.request(ApiRequest::new(Method::GET, "/rules")).await?)
+    }
+
+    /// GET /providers/rules
+    async fn get_providers_rules(&self) -> Result<ProvidersRulesRes> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, "/providers/rules"))
+                .await?,
+        )
+    }
+
+    /// PUT /providers/rules/{name}
+    async fn update_providers_rules_group(&self, name: &str) -> Result<()> {
+        self.request(ApiRequest::new(
+            Method::PUT,
+            format!("/providers/rules/{name}"),
+        ))
+        .await?;
+        Ok(())
+    }
+
+    /// GET /dns/query, Mihomo Only
+    /// 通过内核的 DNS 解析域名
+    /// record_type: 记录类型，如 `A`、`AAAA`，默认为 `A`
+    async fn query_dns(&self, name: &str, record_type: Option<&str>) -> Result<DnsQueryRes> {
+        let request = ApiRequest::new(Method::GET, "/dns/query")
+            .query([("name", name), ("type", record_type.unwrap_or("A"))]);
+        parse(&self.request(request).await?)
+    }
+
+    /// POST /cache/fakeip/flush, Mihomo Only
+    /// 清空 fake-ip 缓存
+    async fn flush_fakeip_cache(&self) -> Result<()> {
+        self.request(ApiRequest::new(Method::POST, "/cache/fakeip/flush"))
+            .await?;
+        Ok(())
+    }
+
+    /// PUT /debug/gc, Mihomo Only
+    /// 触发内核 GC，仅在内核日志等级为 debug 时可用
+    async fn debug_gc(&self) -> Result<()> {
+        self.request(ApiRequest::new(Method::PUT, "/debug/gc"))
+            .await?;
+        Ok(())
+    }
+
+    /// GET /connections
+    /// 获取当前连接的快照
+    async fn get_connections(&self) -> Result<ConnectionsRes> {
+        parse(
+            &self
+                .request(ApiRequest::new(Method::GET, "/connections"))
+                .await?,
+        )
+    }
+
+    /// DELETE /connections
+    /// Close all connections or a specific connection by ID
+    async fn delete_connections(&self, id: Option<&str>) -> Result<()> {
+        let path = match id {
+            Some(id) => format!("/connections/{id}"),
+            None => "/connections".to_string(),
+        };
+        self.request(ApiRequest::new(Method::DELETE, path)).await?;
+        Ok(())
     }
 }
 
-#[instrument(skip_all, fields(
-    method = tracing::field::Empty,
-    url = tracing::field::Empty,
-    query = tracing::field::Empty,
-    data = tracing::field::Empty,
-))]
-async fn perform_request<D, Q>(param: impl Into<PerformRequest<D, Q>>) -> Result<reqwest::Response>
-where
-    Q: Serialize + core::fmt::Debug,
-    D: Serialize + core::fmt::Debug,
-{
-    let PerformRequest {
-        method,
-        path,
-        data,
-        query,
-    } = param.into();
-    let (host, headers) = clash_client_info().context("failed to get clash client info")?;
-    let base_url = Url::parse(&host).context("failed to parse host")?;
-    let opts = url::Url::options().base_url(Some(&base_url));
-    let url = opts.parse(&path).context("failed to parse path")?;
+/// The external controller of a core, which the requests are sent to
+#[derive(Debug, Clone)]
+pub struct ClashController {
+    host: String,
+    headers: HeaderMap,
+}
 
-    let span = tracing::Span::current();
-    span.record("method", tracing::field::display(&method));
-    span.record("url", tracing::field::display(&url));
-    span.record("query", tracing::field::debug(&query));
-    span.record("data", tracing::field::debug(&data));
+impl ClashController {
+    /// the controller of the running core
+    pub fn current() -> Result<Self> {
+        let client = { Config::clash().data().get_client_info() };
+        Self::new(
+            format!("http://{}", client.server),
+            client.secret.as_deref(),
+        )
+        .context("failed to get clash client info")
+    }
 
-    async {
-        let client = reqwest::ClientBuilder::new().no_proxy().build()?;
-        let mut builder = client.request(method.clone(), url.clone()).headers(headers);
+    /// `host` is the base url, e.g. `http://127.0.0.1:9090`
+    pub fn new(host: String, secret: Option<&str>) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", "application/json".parse()?);
 
-        if let Some(query) = &query {
-            builder = builder.query(query);
-        }
-        if let Some(data) = &data {
-            builder = builder.json(data);
+        if let Some(secret) = secret {
+            let secret = format!("Bearer {secret}").parse()?;
+            headers.insert("Authorization", secret);
         }
 
-        let resp = builder.send().await?;
-
-        if let Err(err) = resp.error_for_status_ref() {
-            match err.status() {
-                // Try To parse error message
-                Some(StatusCode::BAD_REQUEST) => {
-                    let Ok(bytes) = resp.bytes().await else {
-                        return Err(err.into());
-                    };
-
-                    let message: serde_json::Value = match serde_json::from_slice(&bytes) {
-                        Ok(v) => v,
-                        Err(_) => {
-                            let s = String::from_utf8_lossy(&bytes);
-                            serde_json::Value::String(s.to_string())
-                        }
-                    };
-
-                    return Err(err).context(format!("message: {message}"));
+        Ok(Self { host, headers })
+    }
+}
+
+#[async_trait]
+impl ClashApi for ClashController {
+    #[instrument(skip_all, fields(
+        method = tracing::field::Empty,
+        url = tracing::field::Empty,
+        query = tracing::field::Empty,
+        data = tracing::field::Empty,
+    ))]
+    async fn request(&self, request: ApiRequest) -> Result<Bytes> {
+        let ApiRequest {
+            method,
+            path,
+            query,
+            data,
+        } = request;
+        let base_url = Url::parse(&self.host).context("failed to parse host")?;
+        let opts = url::Url::options().base_url(Some(&base_url));
+        let url = opts.parse(&path).context("failed to parse path")?;
+
+        let span = tracing::Span::current();
+        span.record("method", tracing::field::display(&method));
+        span.record("url", tracing::field::display(&url));
+        span.record("query", tracing::field::debug(&query));
+        span.record("data", tracing::field::debug(&data));
+
+        async {
+            let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+            let mut builder = client
+                .request(method.clone(), url.clone())
+                .headers(self.headers.clone());
+
+            if let Some(query) = &query {
+                builder = builder.query(query);
+            }
+            if let Some(data) = &data {
+                builder = builder.json(data);
+            }
+
+            let resp = builder.send().await?;
+
+            if let Err(err) = resp.error_for_status_ref() {
+                match err.status() {
+                    // Try To parse error message
+                    Some(StatusCode::BAD_REQUEST) => {
+                        let Ok(bytes) = resp.bytes().await else {
+                            return Err(err.into());
+                        };
+
+                        let message: serde_json::Value = match serde_json::from_slice(&bytes) {
+                            Ok(v) => v,
+                            Err(_) => {
+                                let s = String::from_utf8_lossy(&bytes);
+                                serde_json::Value::String(s.to_string())
+                            }
+                        };
+
+                        return Err(err).context(format!("message: {message}"));
+                    }
+                    _ => return Err(err).context("clash api error"),
                 }
-                _ => return Err(err).context("clash api error"),
             }
+            Ok(resp.bytes().await?)
         }
-        Ok(resp)
+        .await
+        .inspect_err(|e| tracing::error!(method = %method, url = %url, query = ?query, data = ?data, "failed to perform request: {:?}", e))
     }
-    .await
-    .inspect_err(|e| tracing::error!(method = %method, url = %url, query = ?query, data = ?data, "failed to perform request: {:?}", e))
 }
 
 /// 缩短clash的日志
@@ -458,17 +765,17 @@ pub fn parse_check_output(log: String) -> String {
     log
 }
 
+/// GET /connections
+#[instrument]
+pub async fn get_connections() -> Result<ConnectionsRes> {
+    ClashController::current()?.get_connections().await
+}
+
 /// DELETE /connections
 /// Close all connections or a specific connection by ID
 #[instrument]
 pub async fn delete_connections(id: Option<&str>) -> Result<()> {
-    let path = match id {
-        Some(id) => format!("/connections/{}", id),
-        None => "/connections".to_string(),
-    };
-
-    let _ = perform_request((Method::DELETE, path.as_str())).await?;
-    Ok(())
+    ClashController::current()?.delete_connections(id).await
 }
 
 #[test]
@@ -512,3 +819,132 @@ fn test_path() {
         .unwrap();
     assert_eq!(url.to_string(), "http://127.0.0.1:9090/configs");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use pretty_assertions::assert_eq;
+
+    /// responds the canned bodies by the paths, and records the requests
+    #[derive(Default)]
+    struct MockApi {
+        responses: HashMap<&'static str, &'static str>,
+        requests: Mutex<Vec<ApiRequest>>,
+    }
+
+    #[async_trait]
+    impl ClashApi for MockApi {
+        async fn request(&self, request: ApiRequest) -> Result<Bytes> {
+            let body = self
+                .responses
+                .get(request.path.as_str())
+                .copied()
+                .unwrap_or_default();
+            self.requests.lock().push(request);
+            Ok(Bytes::from_static(body.as_bytes()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_typed_responses() {
+        let api = MockApi {
+            responses: HashMap::from([
+                ("/version", r#"{"meta":true,"version":"v1.18.5"}"#),
+                (
+                    "/rules",
+                    r#"{"rules":[
+                        {"index":0,"type":"DomainSuffix","payload":"google.com","proxy":"Proxy","size":-1},
+                        {"type":"Match","payload":"","proxy":"DIRECT"}
+                    ]}"#,
+                ),
+                (
+                    "/providers/rules",
+                    r#"{"providers":{"cn":{
+                        "behavior":"Domain","format":"MrsRule","name":"cn","ruleCount":100,
+                        "type":"Rule","updatedAt":"2024-01-01T00:00:00Z","vehicleType":"HTTP"
+                    }}}"#,
+                ),
+                (
+                    "/dns/query",
+                    r#"{"Status":0,"TC":false,"RD":true,"RA":true,"AD":false,"CD":false,
+                        "Question":[{"Name":"example.com.","Qtype":1,"Qclass":1}],
+                        "Answer":[{"name":"example.com.","type":1,"TTL":60,"data":"93.184.215.14"}]}"#,
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        let version = api.get_version().await.unwrap();
+        assert_eq!(version.version, "v1.18.5");
+        assert!(version.meta);
+        assert!(!version.premium);
+
+        let rules = api.get_rules().await.unwrap().rules;
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].r#type, "DomainSuffix");
+        assert_eq!(rules[0].size, Some(-1));
+        assert_eq!(rules[1].proxy, "DIRECT");
+        assert_eq!(rules[1].index, None);
+
+        let providers = api.get_providers_rules().await.unwrap().providers;
+        assert_eq!(providers["cn"].rule_count, 100);
+        assert!(matches!(providers["cn"].vehicle_type, VehicleType::Http));
+
+        let res = api.query_dns("example.com", None).await.unwrap();
+        assert_eq!(res.status, 0);
+        assert_eq!(res.question[0].r#type, 1);
+        assert_eq!(res.answer[0].ttl, 60);
+        assert_eq!(res.answer[0].data, "93.184.215.14");
+        let requests = api.requests.lock();
+        assert_eq!(
+            requests.last().unwrap().query,
+            Some(vec![
+                ("name".to_string(), "example.com".to_string()),
+                ("type".to_string(), "A".to_string())
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_responses() {
+        let api = MockApi::default();
+        api.put_configs("/tmp/config.yaml", true).await.unwrap();
+        api.restart().await.unwrap();
+        api.flush_fakeip_cache().await.unwrap();
+        api.debug_gc().await.unwrap();
+        assert!(
+            api.get_providers_proxies_healthcheck("provider")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let requests = api.requests.lock();
+        let put_configs = &requests[0];
+        assert_eq!(put_configs.method, Method::PUT);
+        assert_eq!(put_configs.path, "/configs");
+        assert_eq!(
+            put_configs.query,
+            Some(vec![("force".to_string(), "true".to_string())])
+        );
+        assert_eq!(
+            put_configs.data,
+            Some(serde_json::json!({ "path": "/tmp/config.yaml" }))
+        );
+        let calls = requests
+            .iter()
+            .skip(1)
+            .map(|request| (request.method.clone(), request.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            vec![
+                (Method::POST, "/restart"),
+                (Method::POST, "/cache/fakeip/flush"),
+                (Method::PUT, "/debug/gc"),
+                (Method::GET, "/providers/proxies/provider/healthcheck"),
+            ]
+        );
+    }
+}
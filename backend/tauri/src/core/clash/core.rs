@@ -501,7 +501,9 @@ impl CoreManager {
             let mut this = self.instance.lock();
             *this = Some(instance.clone());
         }
-        instance.start().await
+        instance.start().await?;
+        super::selections::restore_in_background();
+        Ok(())
     }
 
     /// 重启内核
@@ -648,6 +650,7 @@ impl CoreManager {
             }
             sleep(Duration::from_millis(250)).await;
         }
+        super::selections::restore_in_background();
 
         Ok(())
     }
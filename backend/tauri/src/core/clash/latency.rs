@@ -0,0 +1,454 @@
+//! Batch latency testing of a proxy group or all the proxies.
+//! The median, jitter and loss of each test are kept in the storage as the latency history.
+use super::api::{ClashApi, DEFAULT_DELAY_TEST_URL};
+use crate::core::storage::{Storage, StorageOperationError};
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
+use indexmap::IndexMap;
+use redb::{ReadableDatabase, TableDefinition};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+
+/// proxy name -> json encoded `Vec<LatencyRecord>`, the oldest first
+pub const LATENCY_HISTORY_TABLE: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("latency-history");
+
+const HISTORY_LEN: usize = 100;
+
+/// the outbounds which are not proxies, they are skipped when testing all the proxies
+const BUILTIN_TYPES: [&str; 5] = ["Direct", "Reject", "RejectDrop", "Pass", "Compatible"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyTestOptions {
+    /// the url to test, `http://www.gstatic.com/generate_204` if empty
+    pub url: Option<String>,
+    /// the timeout of a test in milliseconds
+    pub timeout: u64,
+    /// the expected status code, e.g. `204` or `200-299`, any 2xx status is fine if empty
+    pub expected_status: Option<String>,
+    /// how many proxies are tested at the same time, it does not limit the group endpoint
+    pub concurrency: usize,
+    /// how many times each proxy is tested
+    pub repeat: usize,
+}
+
+impl Default for LatencyTestOptions {
+    fn default() -> Self {
+        Self {
+            url: None,
+            timeout: 5000,
+            expected_status: None,
+            concurrency: 16,
+            repeat: 1,
+        }
+    }
+}
+
+impl LatencyTestOptions {
+    fn url(&self) -> &str {
+        self.url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .unwrap_or(DEFAULT_DELAY_TEST_URL)
+    }
+
+    fn expected_status(&self) -> Option<&str> {
+        self.expected_status
+            .as_deref()
+            .filter(|status| !status.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Type, Serialize, Deserialize)]
+pub struct LatencyStats {
+    /// the median delay of the successful tests in milliseconds, `None` if all the tests failed
+    pub median: Option<u64>,
+    /// the mean difference between the consecutive successful delays
+    pub jitter: Option<u64>,
+    /// the ratio of the failed tests, from 0 to 1
+    pub loss: f64,
+}
+
+impl LatencyStats {
+    /// `None` samples are the failed tests
+    pub fn from_samples(samples: &[Option<u64>]) -> Self {
+        let delays = samples.iter().flatten().copied().collect::<Vec<_>>();
+        let loss = if samples.is_empty() {
+            0.0
+        } else {
+            (samples.len() - delays.len()) as f64 / samples.len() as f64
+        };
+        let jitter = (delays.len() > 1).then(|| {
+            let sum: u64 = delays.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+            sum / (delays.len() as u64 - 1)
+        });
+        let mut sorted = delays;
+        sorted.sort_unstable();
+        let median = match sorted.len() {
+            0 => None,
+            len if len % 2 == 0 => Some((sorted[len / 2 - 1] + sorted[len / 2]) / 2),
+            len => Some(sorted[len / 2]),
+        };
+        Self {
+            median,
+            jitter,
+            loss,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Type, Serialize, Deserialize)]
+pub struct ProxyLatency {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: LatencyStats,
+    /// the delays in the order they are tested, `None` for the failed tests
+    pub samples: Vec<Option<u64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Type, Serialize, Deserialize)]
+pub struct LatencyRecord {
+    /// the unix timestamp of the test
+    pub time: i64,
+    #[serde(flatten)]
+    pub stats: LatencyStats,
+}
+
+#[derive(Debug, Clone, Type, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind", content = "data")]
+pub enum LatencyTestEvent {
+    /// `total` is the count of the tests, the proxies times the repeat
+    Started {
+        proxies: Vec<String>,
+        total: usize,
+    },
+    Progress {
+        proxy: String,
+        delay: Option<u64>,
+        done: usize,
+        total: usize,
+    },
+    Finished(Vec<ProxyLatency>),
+}
+
+/// Test the proxies of the group, or all the proxies if `group` is `None`.
+/// The group is tested by `/group/{name}/delay` once per repeat,
+/// and the proxies are tested one by one if the core does not support it.
+pub async fn test_latency(
+    controller: &impl ClashApi,
+    group: Option<&str>,
+    options: &LatencyTestOptions,
+    on_event: impl Fn(LatencyTestEvent),
+) -> Result<Vec<ProxyLatency>> {
+    let records = controller.get_proxies().await?.proxies;
+    let proxies = match group {
+        Some(group) => records
+            .get(group)
+            .and_then(|item| item.all.clone())
+            .ok_or_else(|| anyhow!("proxy group `{group}` not found"))?,
+        None => records
+            .values()
+            .filter(|item| item.all.is_none() && !BUILTIN_TYPES.contains(&item.r#type.as_str()))
+            .map(|item| item.name.clone())
+            .collect(),
+    };
+    let repeat = options.repeat.max(1);
+    let total = proxies.len() * repeat;
+    on_event(LatencyTestEvent::Started {
+        proxies: proxies.clone(),
+        total,
+    });
+
+    let mut samples: IndexMap<String, Vec<Option<u64>>> = proxies
+        .iter()
+        .map(|name| (name.clone(), Vec::with_capacity(repeat)))
+        .collect();
+    let mut done = 0;
+    let mut record = |proxy: &str, delay: Option<u64>| {
+        let delay = delay.filter(|delay| *delay > 0);
+        if let Some(samples) = samples.get_mut(proxy) {
+            samples.push(delay);
+        }
+        done += 1;
+        on_event(LatencyTestEvent::Progress {
+            proxy: proxy.to_string(),
+            delay,
+            done,
+            total,
+        });
+    };
+
+    let mut rounds = 0;
+    if let Some(group) = group {
+        while rounds < repeat {
+            match controller
+                .get_group_delay(
+                    group,
+                    options.url(),
+                    options.timeout,
+                    options.expected_status(),
+                )
+                .await
+            {
+                Ok(delays) => {
+                    for proxy in &proxies {
+                        record(proxy, delays.get(proxy).copied());
+                    }
+                    rounds += 1;
+                }
+                // the group endpoint is unreachable or unsupported, test the rest one by one
+                Err(err) => {
+                    tracing::warn!("failed to test the group `{group}` at once: {err:?}");
+                    break;
+                }
+            }
+        }
+    }
+
+    let concurrency = options.concurrency.max(1);
+    let mut tests = futures::stream::iter((rounds..repeat).flat_map(|_| proxies.iter()))
+        .map(|proxy| async move {
+            let delay = controller
+                .get_proxy_delay(
+                    proxy,
+                    options.url(),
+                    options.timeout,
+                    options.expected_status(),
+                )
+                .await
+                .ok()
+                .map(|res| res.delay);
+            (proxy, delay)
+        })
+        .buffer_unordered(concurrency);
+    while let Some((proxy, delay)) = tests.next().await {
+        record(proxy, delay);
+    }
+
+    let results = samples
+        .into_iter()
+        .map(|(name, samples)| ProxyLatency {
+            name,
+            stats: LatencyStats::from_samples(&samples),
+            samples,
+        })
+        .collect::<Vec<_>>();
+    on_event(LatencyTestEvent::Finished(results.clone()));
+    Ok(results)
+}
+
+/// The latency history of the proxies in the storage
+#[derive(Clone)]
+pub struct LatencyHistory {
+    storage: Storage,
+}
+
+impl LatencyHistory {
+    pub fn try_new(storage: Storage) -> std::result::Result<Self, StorageOperationError> {
+        let write_txn = storage.get_instance().begin_write()?;
+        write_txn.open_table(LATENCY_HISTORY_TABLE)?;
+        write_txn.commit()?;
+        Ok(Self { storage })
+    }
+
+    /// append the results to the history, only the latest records are kept
+    pub fn record(
+        &self,
+        time: i64,
+        results: &[ProxyLatency],
+    ) -> std::result::Result<(), StorageOperationError> {
+        let write_txn = self.storage.get_instance().begin_write()?;
+        {
+            let mut table = write_txn.open_table(LATENCY_HISTORY_TABLE)?;
+            for result in results {
+                let mut records = match table.get(result.name.as_str())? {
+                    Some(value) => serde_json::from_slice::<Vec<LatencyRecord>>(value.value())?,
+                    None => Vec::new(),
+                };
+                records.push(LatencyRecord {
+                    time,
+                    stats: result.stats.clone(),
+                });
+                if records.len() > HISTORY_LEN {
+                    records.drain(..records.len() - HISTORY_LEN);
+                }
+                let value = serde_json::to_vec(&records)?;
+                table.insert(result.name.as_str(), value.as_slice())?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    pub fn get(
+        &self,
+        proxy: &str,
+    ) -> std::result::Result<Vec<LatencyRecord>, StorageOperationError> {
+        let read_txn = self.storage.get_instance().begin_read()?;
+        let table = read_txn.open_table(LATENCY_HISTORY_TABLE)?;
+        match table.get(proxy)? {
+            Some(value) => Ok(serde_json::from_slice(value.value())?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::api::ClashController;
+    use axum::{
+        Json, Router,
+        extract::{Path, Query, State},
+        http::StatusCode,
+        routing::get,
+    };
+    use parking_lot::Mutex;
+    use pretty_assertions::assert_eq;
+    use serde_json::{Value, json};
+    use std::{collections::HashMap, sync::Arc};
+
+    #[test]
+    fn test_latency_stats() {
+        let stats = LatencyStats::from_samples(&[Some(100), None, Some(120), Some(110), None]);
+        assert_eq!(stats.median, Some(110));
+        // |100 - 120| + |120 - 110|
+        assert_eq!(stats.jitter, Some(15));
+        assert_eq!(stats.loss, 0.4);
+
+        let stats = LatencyStats::from_samples(&[Some(100), Some(200)]);
+        assert_eq!(stats.median, Some(150));
+
+        let stats = LatencyStats::from_samples(&[None, None]);
+        assert_eq!(stats.median, None);
+        assert_eq!(stats.jitter, None);
+        assert_eq!(stats.loss, 1.0);
+    }
+
+    #[test]
+    fn test_latency_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::try_new(&dir.path().join("storage.db")).unwrap();
+        let history = LatencyHistory::try_new(storage).unwrap();
+        let result = ProxyLatency {
+            name: "HK".to_string(),
+            stats: LatencyStats::from_samples(&[Some(100)]),
+            samples: vec![Some(100)],
+        };
+        for time in 0..HISTORY_LEN as i64 + 5 {
+            history.record(time, std::slice::from_ref(&result)).unwrap();
+        }
+        let records = history.get("HK").unwrap();
+        assert_eq!(records.len(), HISTORY_LEN);
+        assert_eq!(records[0].time, 5);
+        assert!(history.get("JP").unwrap().is_empty());
+    }
+
+    fn proxies() -> Value {
+        json!({
+            "proxies": {
+                "DIRECT": { "name": "DIRECT", "type": "Direct", "udp": true, "history": [] },
+                "HK": { "name": "HK", "type": "Shadowsocks", "udp": true, "history": [] },
+                "JP": { "name": "JP", "type": "Vmess", "udp": true, "history": [] },
+                "Proxy": {
+                    "name": "Proxy",
+                    "type": "Selector",
+                    "udp": true,
+                    "history": [],
+                    "all": ["HK", "JP"],
+                    "now": "HK",
+                },
+            }
+        })
+    }
+
+    async fn serve(app: Router) -> ClashController {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        ClashController::new(format!("http://{addr}"), None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_group_delay() {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route("/proxies", get(|| async { Json(proxies()) }))
+            .route(
+                "/group/{name}/delay",
+                get(
+                    |State(queries): State<Arc<Mutex<Vec<HashMap<String, String>>>>>,
+                     Path(name): Path<String>,
+                     Query(query): Query<HashMap<String, String>>| async move {
+                        assert_eq!(name, "Proxy");
+                        queries.lock().push(query);
+                        // the failed proxies are omitted
+                        Json(json!({ "HK": 120 }))
+                    },
+                ),
+            )
+            .with_state(queries.clone());
+        let controller = serve(app).await;
+        let options = LatencyTestOptions {
+            expected_status: Some("204".to_string()),
+            repeat: 2,
+            ..Default::default()
+        };
+        let events = Mutex::new(Vec::new());
+        let results = test_latency(&controller, Some("Proxy"), &options, |event| {
+            events.lock().push(event)
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.name.as_str(), result.samples.clone()))
+                .collect::<Vec<_>>(),
+            vec![("HK", vec![Some(120), Some(120)]), ("JP", vec![None, None])]
+        );
+        assert_eq!(results[1].stats.loss, 1.0);
+        let queries = queries.lock();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0]["expected"], "204");
+        assert_eq!(queries[0]["url"], DEFAULT_DELAY_TEST_URL);
+        // started, 4 progresses, finished
+        assert_eq!(events.lock().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_to_proxy_delay() {
+        let app = Router::new()
+            .route("/proxies", get(|| async { Json(proxies()) }))
+            .route(
+                "/proxies/{name}/delay",
+                get(|Path(name): Path<String>| async move {
+                    match name.as_str() {
+                        "HK" => Ok(Json(json!({ "delay": 80 }))),
+                        _ => Err(StatusCode::GATEWAY_TIMEOUT),
+                    }
+                }),
+            );
+        let controller = serve(app).await;
+        // all the proxies, the builtin outbounds and the groups are skipped
+        let results = test_latency(&controller, None, &Default::default(), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| (result.name.as_str(), result.stats.median))
+                .collect::<Vec<_>>(),
+            vec![("HK", Some(80)), ("JP", None)]
+        );
+
+        // the group endpoint is missing
+        let results = test_latency(&controller, Some("Proxy"), &Default::default(), |_| {})
+            .await
+            .unwrap();
+        assert_eq!(results[0].samples, vec![Some(80)]);
+        assert_eq!(results[1].samples, vec![None]);
+    }
+}
@@ -4,7 +4,10 @@ use tauri::Emitter;
 
 pub mod api;
 pub mod core;
+pub mod latency;
 pub mod proxies;
+pub mod rule_explain;
+pub mod selections;
 pub mod ws;
 
 pub static CLASH_API_DEFAULT_BACKOFF_STRATEGY: Lazy<ExponentialBuilder> = Lazy::new(|| {
@@ -15,10 +18,43 @@ pub static CLASH_API_DEFAULT_BACKOFF_STRATEGY: Lazy<ExponentialBuilder> = Lazy::
 });
 
 pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> anyhow::Result<()> {
-    let ws_connector = ws::ClashConnectionsConnector::new();
+    let ws_connector = ws::ClashConnectionsConnector::new(Default::default());
+    let traffic_connector = ws::ClashTrafficConnector::new(Default::default());
+    let memory_connector = ws::ClashMemoryConnector::new(Default::default());
+    let logs_connector = ws::ClashLogsConnector::new(ws::ClashLogsStream::new(log_level()));
     manager.manage(ws_connector.clone());
+    manager.manage(traffic_connector.clone());
+    manager.manage(memory_connector.clone());
+    manager.manage(logs_connector.clone());
+    // the events are still forwarded without the storage
+    match manager.try_state::<crate::core::storage::Storage>() {
+        Some(storage) => {
+            let storage = storage.inner().clone();
+            match latency::LatencyHistory::try_new(storage.clone()) {
+                Ok(history) => {
+                    manager.manage(history);
+                }
+                Err(e) => tracing::error!("failed to init the latency history: {e}"),
+            }
+            match selections::ProxySelections::try_new(storage) {
+                Ok(selections) => {
+                    manager.manage(selections);
+                }
+                Err(e) => tracing::error!("failed to init the proxy selections: {e}"),
+            }
+        }
+        None => tracing::error!(
+            "the storage is not initialized, the latency history and the proxy selections are disabled"
+        ),
+    }
     let app_handle = manager.app_handle().clone();
 
+    // 订阅事件并发送到前端
+    forward_events(&app_handle, &ws_connector, "clash-connections-event");
+    forward_events(&app_handle, &traffic_connector, "clash-traffic-event");
+    forward_events(&app_handle, &memory_connector, "clash-memory-event");
+    forward_events(&app_handle, &logs_connector, "clash-logs-event");
+
     tauri::async_runtime::spawn(async move {
         // 等待 clash core 启动并就绪
         // 通过轮询 core 状态判断，而非硬编码延迟
@@ -59,51 +95,86 @@ pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> anyhow::Re
             }
         }
 
-        // 启动 WS 连接，带重试机制
-        let mut ws_retry_count = 0;
-        let max_ws_retries = 5;
+        start_connector(&ws_connector).await;
+        start_connector(&traffic_connector).await;
+        if crate::config::Config::verge()
+            .latest()
+            .enable_memory_usage
+            .unwrap_or(true)
+        {
+            start_connector(&memory_connector).await;
+        }
+        start_connector(&logs_connector).await;
+    });
+    Ok(())
+}
+
+/// the level of the logs streamed from the core, the same as the `log-level` of the clash config
+fn log_level() -> String {
+    crate::config::Config::clash()
+        .latest()
+        .0
+        .get("log-level")
+        .and_then(|level| level.as_str())
+        .unwrap_or("info")
+        .to_string()
+}
 
-        loop {
-            match ws_connector.start().await {
-                Ok(_) => {
-                    tracing::info!("WS connector started successfully");
-                    break;
-                }
-                Err(e) => {
-                    ws_retry_count += 1;
-                    if ws_retry_count >= max_ws_retries {
-                        tracing::error!(
-                            "Failed to start WS connector after {} attempts: {:?}. Connections monitoring will be unavailable.",
-                            max_ws_retries,
-                            e
-                        );
-                        break;
-                    }
-                    tracing::warn!(
-                        "WS connector failed to start (attempt {}/{}): {:?}, retrying in 3 seconds...",
-                        ws_retry_count,
+/// 启动 WS 连接，带重试机制
+async fn start_connector<S: ws::ClashWsStream>(connector: &ws::ClashWsConnector<S>) {
+    let mut ws_retry_count = 0;
+    let max_ws_retries = 5;
+
+    loop {
+        match connector.start().await {
+            Ok(_) => {
+                tracing::info!("WS connector of {} started successfully", S::NAME);
+                break;
+            }
+            Err(e) => {
+                ws_retry_count += 1;
+                if ws_retry_count >= max_ws_retries {
+                    tracing::error!(
+                        "Failed to start WS connector of {} after {} attempts: {:?}. The stream will be unavailable.",
+                        S::NAME,
                         max_ws_retries,
                         e
                     );
-                    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                    break;
                 }
+                tracing::warn!(
+                    "WS connector of {} failed to start (attempt {}/{}): {:?}, retrying in 3 seconds...",
+                    S::NAME,
+                    ws_retry_count,
+                    max_ws_retries,
+                    e
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
             }
         }
-
-        // 订阅事件并发送到前端
-        let mut rx = ws_connector.subscribe();
-        while let Ok(event) = rx.recv().await {
-            emit_clash_connections_event(&app_handle, event);
-        }
-    });
-    Ok(())
+    }
 }
 
-fn emit_clash_connections_event<R: tauri::Runtime>(
+fn forward_events<R: tauri::Runtime, S: ws::ClashWsStream>(
     app_handle: &tauri::AppHandle<R>,
-    event: ws::ClashConnectionsConnectorEvent,
-) {
-    if let Err(err) = app_handle.emit("clash-connections-event", event) {
-        tracing::error!("failed to emit clash connections event: {err}");
-    }
+    connector: &ws::ClashWsConnector<S>,
+    event_name: &'static str,
+) where
+    S::Event: serde::Serialize,
+{
+    let app_handle = app_handle.clone();
+    let mut rx = connector.subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Err(err) = app_handle.emit(event_name, event) {
+                        tracing::error!("failed to emit {event_name}: {err}");
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
 }
@@ -1,6 +1,7 @@
 /// This module is used to manage the proxies for the Tauri application.
 /// It is used to provide the unite interface between tray and frontend.
-/// TODO: add a diff algorithm to reduce the data transfer, and the rerendering of the tray menu.
+/// The changes between the snapshots are broadcast as [`ProxiesEvent`]s,
+/// so that the tray and the frontend could update the affected items only.
 use super::{CLASH_API_DEFAULT_BACKOFF_STRATEGY, api};
 use adler::adler32;
 use anyhow::Result;
@@ -10,7 +11,10 @@ use log::warn;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use std::sync::{Arc, OnceLock};
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+};
 use tokio::{sync::broadcast, try_join};
 use tracing_attributes::instrument;
 
@@ -56,6 +60,14 @@ impl From<api::ProxyItem> for ProxyGroupItem {
     }
 }
 
+/// The proxies of a http or file provider
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Default, Type)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderSnapshot {
+    pub proxies: Vec<String>,
+    pub updated_at: Option<String>, // Mihomo Only
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default, Type)]
 #[serde(rename_all = "camelCase")]
 pub struct Proxies {
@@ -64,6 +76,8 @@ pub struct Proxies {
     pub groups: Vec<ProxyGroupItem>,
     pub records: IndexMap<String, api::ProxyItem>,
     pub proxies: Vec<api::ProxyItem>,
+    #[serde(default)]
+    pub providers: IndexMap<String, ProviderSnapshot>,
 }
 
 async fn fetch_proxies() -> Result<(api::ProxiesRes, api::ProvidersProxiesRes)> {
@@ -78,25 +92,25 @@ impl Proxies {
             .await?;
         let inner_proxies = inner_proxies.proxies;
         // 1. filter out the Http or File type provider proxies
-        let providers_proxies: IndexMap<String, api::ProxyProviderItem> = {
-            let records = providers_proxies.providers;
-            records
-                .into_iter()
-                .filter(|(_k, v)| {
-                    matches!(
-                        v.vehicle_type,
-                        api::VehicleType::Http | api::VehicleType::File
-                    )
-                })
-                .collect()
-        };
+        let providers_proxies = providers_proxies.providers.into_iter().filter(|(_k, v)| {
+            matches!(
+                v.vehicle_type,
+                api::VehicleType::Http | api::VehicleType::File
+            )
+        });
 
         // 2. mapping provider => providerProxiesItem to name => ProxyItem
+        let mut providers = IndexMap::<String, ProviderSnapshot>::new();
         let mut provider_map = IndexMap::<String, api::ProxyItem>::new();
-        for (provider, record) in providers_proxies.iter() {
+        for (provider, mut record) in providers_proxies {
+            let snapshot = ProviderSnapshot {
+                proxies: record.proxies.iter().map(|p| p.name.clone()).collect(),
+                updated_at: record.updated_at.take(),
+            };
+            providers.insert(provider.clone(), snapshot);
             let name = record.name.clone();
-            let mut record: api::ProxyItem = record.clone().into();
-            record.provider = Some(provider.clone());
+            let mut record: api::ProxyItem = record.into();
+            record.provider = Some(provider);
             provider_map.insert(name, record);
         }
         let generate_item = |name: &str| {
@@ -114,6 +128,14 @@ impl Proxies {
                 }
             }
         };
+        // the group with its members resolved
+        let generate_group = |item: &api::ProxyItem| {
+            let mut item = item.clone();
+            let all = item.all.take().unwrap_or_default();
+            let mut item: ProxyGroupItem = item.into();
+            item.all = all.iter().map(|name| generate_item(name)).collect();
+            item
+        };
 
         let global = inner_proxies.get("GLOBAL");
         let direct = inner_proxies
@@ -127,41 +149,18 @@ impl Proxies {
 
         // 3. generate the proxies groups
         let groups: Vec<ProxyGroupItem> = match global {
-            Some(api::ProxyItem { all: Some(all), .. }) => {
-                let all = all.clone();
-                all.into_iter()
-                    .filter(|name| {
-                        matches!(
-                            inner_proxies.get(name),
-                            Some(api::ProxyItem { all: Some(_), .. })
-                        )
-                    })
-                    .map(|name| {
-                        let item = inner_proxies
-                            .get(&name)
-                            .unwrap_or(&api::ProxyItem::default())
-                            .clone();
-                        let item_all = item.all.clone().unwrap_or_default();
-                        let mut item: ProxyGroupItem = item.into();
-                        item.all = item_all
-                            .into_iter()
-                            .map(|name| generate_item(&name))
-                            .collect();
-                        item
-                    })
-                    .collect()
-            }
+            Some(api::ProxyItem { all: Some(all), .. }) => all
+                .iter()
+                .filter_map(|name| match inner_proxies.get(name) {
+                    Some(item @ api::ProxyItem { all: Some(_), .. }) => Some(generate_group(item)),
+                    _ => None,
+                })
+                .collect(),
             _ => {
                 let mut groups: Vec<ProxyGroupItem> = inner_proxies
-                    .clone()
-                    .into_values()
+                    .values()
                     .filter(|v| v.name == "GLOBAL" && v.all.is_some())
-                    .map(|v| {
-                        let all = v.all.clone().unwrap_or_default();
-                        let mut item: ProxyGroupItem = v.clone().into();
-                        item.all = all.into_iter().map(|name| generate_item(&name)).collect();
-                        item
-                    })
+                    .map(generate_group)
                     .collect();
                 groups.sort_by(|a, b| b.name.to_lowercase().cmp(&a.name.to_lowercase()));
                 groups
@@ -170,18 +169,18 @@ impl Proxies {
 
         // 4. generate the proxies
         let mut proxies: Vec<api::ProxyItem> = vec![direct.clone(), reject];
-        proxies.extend(inner_proxies.clone().into_values().filter(|v| {
-            matches!(v.name.as_str(), "DIRECT" | "REJECT")
-                && (v.all.is_none() || v.all.as_ref().unwrap().is_empty())
-        }));
+        proxies.extend(
+            inner_proxies
+                .values()
+                .filter(|v| {
+                    matches!(v.name.as_str(), "DIRECT" | "REJECT")
+                        && (v.all.is_none() || v.all.as_ref().unwrap().is_empty())
+                })
+                .cloned(),
+        );
 
         // 5. generate the global
-        let global: Option<ProxyGroupItem> = global.map(|v| {
-            let all = v.all.clone().unwrap_or_default();
-            let mut item: ProxyGroupItem = v.clone().into();
-            item.all = all.into_iter().map(|name| generate_item(&name)).collect();
-            item
-        });
+        let global: Option<ProxyGroupItem> = global.map(generate_group);
 
         Ok(Proxies {
             global: global.unwrap_or_default(),
@@ -189,15 +188,150 @@ impl Proxies {
             groups,
             records: inner_proxies,
             proxies,
+            providers,
         })
     }
 }
 
+/// A change between two snapshots of the proxies
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, Type)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ProxiesEvent {
+    /// the first snapshot, or the events are lost, the whole proxies should be reloaded
+    Reset,
+    ProxyAdded {
+        proxy: api::ProxyItem,
+    },
+    ProxyRemoved {
+        name: String,
+    },
+    /// the attributes other than the selection, the history and the members are changed
+    ProxyUpdated {
+        proxy: api::ProxyItem,
+    },
+    /// the members of the group are changed, the order of the groups is the members of `GLOBAL`
+    GroupMembersChanged {
+        group: String,
+        all: Vec<String>,
+    },
+    SelectionChanged {
+        group: String,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// the latency tests since the last snapshot
+    HistoryAppended {
+        proxy: String,
+        alive: Option<bool>,
+        history: Vec<api::ProxyItemHistory>,
+    },
+    ProviderRefreshed {
+        provider: String,
+        proxies: Vec<String>,
+        updated_at: Option<String>,
+    },
+    ProviderRemoved {
+        provider: String,
+    },
+}
+
+/// The events of an update, shared by the receivers
+pub type ProxiesEvents = Arc<Vec<ProxiesEvent>>;
+
+fn same_attributes(old: &api::ProxyItem, new: &api::ProxyItem) -> bool {
+    old.r#type == new.r#type
+        && old.udp == new.udp
+        && old.provider == new.provider
+        && old.xudp == new.xudp
+        && old.tfo == new.tfo
+        && old.icon == new.icon
+        && old.hidden == new.hidden
+}
+
+/// the history after the last entry of the old one
+fn appended_history<'a>(
+    old: &[api::ProxyItemHistory],
+    new: &'a [api::ProxyItemHistory],
+) -> &'a [api::ProxyItemHistory] {
+    match old.last() {
+        Some(last) => match new.iter().rposition(|item| item == last) {
+            Some(index) => &new[index + 1..],
+            None => new,
+        },
+        None => new,
+    }
+}
+
+/// the events turning the old snapshot into the new one
+pub fn diff(old: &Proxies, new: &Proxies) -> Vec<ProxiesEvent> {
+    if old.records.is_empty() {
+        return vec![ProxiesEvent::Reset];
+    }
+    let mut events = Vec::new();
+    for name in old.records.keys() {
+        if !new.records.contains_key(name) {
+            events.push(ProxiesEvent::ProxyRemoved { name: name.clone() });
+        }
+    }
+    for (name, item) in new.records.iter() {
+        let Some(old_item) = old.records.get(name) else {
+            events.push(ProxiesEvent::ProxyAdded {
+                proxy: item.clone(),
+            });
+            continue;
+        };
+        if !same_attributes(old_item, item) {
+            events.push(ProxiesEvent::ProxyUpdated {
+                proxy: item.clone(),
+            });
+        }
+        if old_item.all != item.all {
+            events.push(ProxiesEvent::GroupMembersChanged {
+                group: name.clone(),
+                all: item.all.clone().unwrap_or_default(),
+            });
+        }
+        if old_item.now != item.now {
+            events.push(ProxiesEvent::SelectionChanged {
+                group: name.clone(),
+                from: old_item.now.clone(),
+                to: item.now.clone(),
+            });
+        }
+        let history = appended_history(&old_item.history, &item.history);
+        if !history.is_empty() || old_item.alive != item.alive {
+            events.push(ProxiesEvent::HistoryAppended {
+                proxy: name.clone(),
+                alive: item.alive,
+                history: history.to_vec(),
+            });
+        }
+    }
+    for (provider, snapshot) in new.providers.iter() {
+        if old.providers.get(provider) != Some(snapshot) {
+            events.push(ProxiesEvent::ProviderRefreshed {
+                provider: provider.clone(),
+                proxies: snapshot.proxies.clone(),
+                updated_at: snapshot.updated_at.clone(),
+            });
+        }
+    }
+    let providers = new.providers.keys().collect::<HashSet<_>>();
+    for provider in old.providers.keys() {
+        if !providers.contains(provider) {
+            events.push(ProxiesEvent::ProviderRemoved {
+                provider: provider.clone(),
+            });
+        }
+    }
+    events
+}
+
 pub struct ProxiesGuard {
     inner: Proxies,
     checksum: Option<u32>,
     updated_at: u64,
-    sender: broadcast::Sender<()>,
+    sender: broadcast::Sender<ProxiesEvents>,
 }
 
 impl ProxiesGuard {
@@ -214,17 +348,22 @@ impl ProxiesGuard {
         })
     }
 
-    pub fn get_receiver(&self) -> broadcast::Receiver<()> {
+    /// the events of each update, a lagged receiver should reload the whole proxies
+    pub fn get_receiver(&self) -> broadcast::Receiver<ProxiesEvents> {
         self.sender.subscribe()
     }
 
     pub fn replace(&mut self, proxies: Proxies, checksum: u32) {
         let now = chrono::Utc::now().timestamp() as u64;
+        let events = diff(&self.inner, &proxies);
         self.inner = proxies;
         self.checksum = Some(checksum);
         self.updated_at = now;
 
-        if let Err(e) = self.sender.send(()) {
+        if events.is_empty() {
+            return;
+        }
+        if let Err(e) = self.sender.send(Arc::new(events)) {
             warn!(
                 target: "clash::proxies",
                 "send update signal failed: {e:?}"
@@ -276,7 +415,122 @@ impl ProxiesGuardExt for ProxiesGuardSingleton {
 
     async fn select_proxy(&self, group: &str, name: &str) -> Result<()> {
         api::update_proxy(group, name).await?;
+        super::selections::remember(group, name);
         self.update().await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn history(time: &str, delay: i64) -> api::ProxyItemHistory {
+        api::ProxyItemHistory {
+            time: time.to_string(),
+            delay,
+        }
+    }
+
+    fn proxy(name: &str) -> (String, api::ProxyItem) {
+        (
+            name.to_string(),
+            api::ProxyItem {
+                name: name.to_string(),
+                r#type: "Shadowsocks".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn group(name: &str, now: &str, all: &[&str]) -> (String, api::ProxyItem) {
+        (
+            name.to_string(),
+            api::ProxyItem {
+                name: name.to_string(),
+                r#type: "Selector".to_string(),
+                now: Some(now.to_string()),
+                all: Some(all.iter().map(|name| name.to_string()).collect()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn snapshot(records: Vec<(String, api::ProxyItem)>) -> Proxies {
+        Proxies {
+            records: records.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(
+            diff(&Proxies::default(), &snapshot(vec![proxy("HK")])),
+            vec![ProxiesEvent::Reset]
+        );
+
+        let mut old = snapshot(vec![
+            group("GLOBAL", "Proxy", &["Proxy"]),
+            group("Proxy", "HK", &["HK", "JP"]),
+            proxy("HK"),
+            proxy("JP"),
+        ]);
+        old.records["HK"].history = vec![history("1", 100), history("2", 120)];
+        old.providers.insert(
+            "sub".to_string(),
+            ProviderSnapshot {
+                proxies: vec!["US".to_string()],
+                updated_at: Some("1".to_string()),
+            },
+        );
+        assert!(diff(&old, &old).is_empty());
+
+        let mut new = snapshot(vec![
+            group("GLOBAL", "Proxy", &["Proxy"]),
+            group("Proxy", "US", &["HK", "US"]),
+            proxy("HK"),
+            proxy("US"),
+        ]);
+        new.records["HK"].history = vec![history("2", 120), history("3", 90)];
+        new.records["HK"].alive = Some(true);
+        new.providers.insert(
+            "sub".to_string(),
+            ProviderSnapshot {
+                proxies: vec!["US".to_string()],
+                updated_at: Some("2".to_string()),
+            },
+        );
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                ProxiesEvent::ProxyRemoved {
+                    name: "JP".to_string()
+                },
+                ProxiesEvent::GroupMembersChanged {
+                    group: "Proxy".to_string(),
+                    all: vec!["HK".to_string(), "US".to_string()],
+                },
+                ProxiesEvent::SelectionChanged {
+                    group: "Proxy".to_string(),
+                    from: Some("HK".to_string()),
+                    to: Some("US".to_string()),
+                },
+                ProxiesEvent::HistoryAppended {
+                    proxy: "HK".to_string(),
+                    alive: Some(true),
+                    history: vec![history("3", 90)],
+                },
+                ProxiesEvent::ProxyAdded {
+                    proxy: new.records["US"].clone(),
+                },
+                ProxiesEvent::ProviderRefreshed {
+                    provider: "sub".to_string(),
+                    proxies: vec!["US".to_string()],
+                    updated_at: Some("2".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,461 @@
+//! Read the v2ray geodata (`geosite.dat` and `geoip.dat`) shipped with the core.
+//! The files are protobuf messages, only the few fields used by the rules are decoded.
+use anyhow::{Result, bail};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// An ip network, e.g. `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            bail!("invalid prefix length {prefix} of {addr}");
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(addr), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(addr), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.split_once('/') {
+            Some((addr, prefix)) => Self::new(addr.parse()?, prefix.parse()?),
+            None => {
+                let addr: IpAddr = s.parse()?;
+                Self::new(addr, if addr.is_ipv4() { 32 } else { 128 })
+            }
+        }
+    }
+}
+
+/// The kinds of the domains in `geosite.dat`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoSiteDomainKind {
+    /// the domain contains the value
+    Plain,
+    Regex,
+    /// the domain is the value or its subdomain
+    Domain,
+    /// the domain is the value
+    Full,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeoSiteDomain {
+    pub kind: GeoSiteDomainKind,
+    pub value: String,
+    pub attributes: Vec<String>,
+    /// the compiled value of the regex domains, `None` if it is invalid
+    regex: Option<regex::Regex>,
+}
+
+impl GeoSiteDomain {
+    pub fn matches(&self, domain: &str) -> bool {
+        match self.kind {
+            GeoSiteDomainKind::Plain => domain.contains(&self.value),
+            GeoSiteDomainKind::Regex => self
+                .regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(domain)),
+            GeoSiteDomainKind::Domain => {
+                domain == self.value
+                    || domain
+                        .strip_suffix(&self.value)
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            }
+            GeoSiteDomainKind::Full => domain == self.value,
+        }
+    }
+}
+
+/// A wire field of a protobuf message
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+struct ProtoReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> ProtoReader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for (i, byte) in self.buf.iter().enumerate().take(10) {
+            value |= ((byte & 0x7f) as u64) << (i * 7);
+            if byte & 0x80 == 0 {
+                self.buf = &self.buf[i + 1..];
+                return Ok(value);
+            }
+        }
+        bail!("malformed varint");
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            bail!("unexpected end of the message");
+        }
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    /// the next field number and its value, the fixed size fields are skipped
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>> {
+        while !self.buf.is_empty() {
+            let key = self.varint()?;
+            let (number, wire_type) = (key >> 3, key & 0x7);
+            let field = match wire_type {
+                0 => Field::Varint(self.varint()?),
+                1 => {
+                    self.take(8)?;
+                    continue;
+                }
+                2 => {
+                    let len = self.varint()? as usize;
+                    Field::Bytes(self.take(len)?)
+                }
+                5 => {
+                    self.take(4)?;
+                    continue;
+                }
+                _ => bail!("unsupported wire type {wire_type}"),
+            };
+            return Ok(Some((number, field)));
+        }
+        Ok(None)
+    }
+}
+
+/// find the entry of the code in a `GeoSiteList` or a `GeoIPList`,
+/// both of them are `repeated entry = 1`, and the entries start with `string country_code = 1`
+fn find_entry<'a>(buf: &'a [u8], code: &str) -> Result<Option<&'a [u8]>> {
+    let mut list = ProtoReader::new(buf);
+    while let Some((number, field)) = list.next_field()? {
+        let (1, Field::Bytes(entry)) = (number, field) else {
+            continue;
+        };
+        let mut reader = ProtoReader::new(entry);
+        while let Some((number, field)) = reader.next_field()? {
+            if let (1, Field::Bytes(country_code)) = (number, field) {
+                if String::from_utf8_lossy(country_code).eq_ignore_ascii_case(code) {
+                    return Ok(Some(entry));
+                }
+                break;
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn parse_geosite_domain(buf: &[u8]) -> Result<GeoSiteDomain> {
+    let mut domain = GeoSiteDomain {
+        kind: GeoSiteDomainKind::Plain,
+        value: String::new(),
+        attributes: Vec::new(),
+        regex: None,
+    };
+    let mut reader = ProtoReader::new(buf);
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (1, Field::Varint(kind)) => {
+                domain.kind = match kind {
+                    0 => GeoSiteDomainKind::Plain,
+                    1 => GeoSiteDomainKind::Regex,
+                    2 => GeoSiteDomainKind::Domain,
+                    3 => GeoSiteDomainKind::Full,
+                    _ => bail!("unknown domain type {kind}"),
+                }
+            }
+            (2, Field::Bytes(value)) => {
+                domain.value = String::from_utf8_lossy(value).to_lowercase();
+            }
+            (3, Field::Bytes(attribute)) => {
+                let mut reader = ProtoReader::new(attribute);
+                while let Some((number, field)) = reader.next_field()? {
+                    if let (1, Field::Bytes(key)) = (number, field) {
+                        domain
+                            .attributes
+                            .push(String::from_utf8_lossy(key).to_lowercase());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if domain.kind == GeoSiteDomainKind::Regex {
+        domain.regex = regex::Regex::new(&domain.value).ok();
+    }
+    Ok(domain)
+}
+
+/// the domains of the code in `geosite.dat`, `None` if the code is not found
+pub fn parse_geosite(buf: &[u8], code: &str) -> Result<Option<Vec<GeoSiteDomain>>> {
+    let Some(entry) = find_entry(buf, code)? else {
+        return Ok(None);
+    };
+    let mut domains = Vec::new();
+    let mut reader = ProtoReader::new(entry);
+    while let Some((number, field)) = reader.next_field()? {
+        if let (2, Field::Bytes(domain)) = (number, field) {
+            domains.push(parse_geosite_domain(domain)?);
+        }
+    }
+    Ok(Some(domains))
+}
+
+/// The networks of a code in `geoip.dat`
+#[derive(Debug, Clone, Default)]
+pub struct GeoIp {
+    pub cidrs: Vec<Cidr>,
+    /// match the ips which are not in the networks
+    pub reverse: bool,
+}
+
+impl GeoIp {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.cidrs.iter().any(|cidr| cidr.contains(ip)) != self.reverse
+    }
+}
+
+/// the networks of the code in `geoip.dat`, `None` if the code is not found
+pub fn parse_geoip(buf: &[u8], code: &str) -> Result<Option<GeoIp>> {
+    let Some(entry) = find_entry(buf, code)? else {
+        return Ok(None);
+    };
+    let mut geoip = GeoIp::default();
+    let mut reader = ProtoReader::new(entry);
+    while let Some((number, field)) = reader.next_field()? {
+        match (number, field) {
+            (2, Field::Bytes(cidr)) => {
+                let (mut ip, mut prefix) = (None, 0);
+                let mut reader = ProtoReader::new(cidr);
+                while let Some((number, field)) = reader.next_field()? {
+                    match (number, field) {
+                        (1, Field::Bytes(bytes)) => {
+                            ip = match bytes.len() {
+                                4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes)?)),
+                                16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes)?)),
+                                len => bail!("invalid ip length {len}"),
+                            }
+                        }
+                        (2, Field::Varint(value)) => prefix = value as u8,
+                        _ => {}
+                    }
+                }
+                if let Some(ip) = ip {
+                    geoip.cidrs.push(Cidr::new(ip, prefix)?);
+                }
+            }
+            (3, Field::Varint(reverse)) => geoip.reverse = reverse != 0,
+            _ => {}
+        }
+    }
+    Ok(Some(geoip))
+}
+
+/// the file in the dir, the name is matched case-insensitively like the core does
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+    if path.exists() {
+        return Some(path);
+    }
+    std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(name)
+        })
+        .map(|entry| entry.path())
+}
+
+/// The geodata in the home dir of the core, the codes are loaded on demand
+pub struct GeoData {
+    dir: PathBuf,
+    geosite: HashMap<String, Option<Arc<Vec<GeoSiteDomain>>>>,
+    geoip: HashMap<String, Option<Arc<GeoIp>>>,
+}
+
+impl GeoData {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            geosite: HashMap::new(),
+            geoip: HashMap::new(),
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        match find_file(&self.dir, name) {
+            Some(path) => Ok(fs_err::read(path)?),
+            None => bail!("`{name}` is not found in {}", self.dir.display()),
+        }
+    }
+
+    /// the domains of the code, `None` if the code is not found
+    pub fn geosite(&mut self, code: &str) -> Result<Option<Arc<Vec<GeoSiteDomain>>>> {
+        let code = code.to_lowercase();
+        if let Some(domains) = self.geosite.get(&code) {
+            return Ok(domains.clone());
+        }
+        let domains = parse_geosite(&self.read("geosite.dat")?, &code)?.map(Arc::new);
+        self.geosite.insert(code, domains.clone());
+        Ok(domains)
+    }
+
+    /// the networks of the code, `None` if the code is not found
+    pub fn geoip(&mut self, code: &str) -> Result<Option<Arc<GeoIp>>> {
+        let code = code.to_lowercase();
+        if let Some(geoip) = self.geoip.get(&code) {
+            return Ok(geoip.clone());
+        }
+        let geoip = parse_geoip(&self.read("geoip.dat")?, &code)?.map(Arc::new);
+        self.geoip.insert(code, geoip.clone());
+        Ok(geoip)
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    fn varint(mut value: u64, buf: &mut Vec<u8>) {
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+    }
+
+    fn bytes_field(number: u64, bytes: &[u8], buf: &mut Vec<u8>) {
+        varint(number << 3 | 2, buf);
+        varint(bytes.len() as u64, buf);
+        buf.extend_from_slice(bytes);
+    }
+
+    fn varint_field(number: u64, value: u64, buf: &mut Vec<u8>) {
+        varint(number << 3, buf);
+        varint(value, buf);
+    }
+
+    /// a `geosite.dat` of the codes with the (type, value) domains
+    pub fn geosite_dat(sites: &[(&str, &[(u64, &str)])]) -> Vec<u8> {
+        let mut list = Vec::new();
+        for (code, domains) in sites {
+            let mut site = Vec::new();
+            bytes_field(1, code.to_uppercase().as_bytes(), &mut site);
+            for (kind, value) in domains.iter() {
+                let mut domain = Vec::new();
+                varint_field(1, *kind, &mut domain);
+                bytes_field(2, value.as_bytes(), &mut domain);
+                bytes_field(2, &domain, &mut site);
+            }
+            bytes_field(1, &site, &mut list);
+        }
+        list
+    }
+
+    /// a `geoip.dat` of the codes with the cidrs
+    pub fn geoip_dat(entries: &[(&str, &[&str])]) -> Vec<u8> {
+        let mut list = Vec::new();
+        for (code, cidrs) in entries {
+            let mut entry = Vec::new();
+            bytes_field(1, code.to_uppercase().as_bytes(), &mut entry);
+            for cidr in cidrs.iter() {
+                let (ip, prefix) = cidr.split_once('/').unwrap();
+                let ip: IpAddr = ip.parse().unwrap();
+                let ip = match ip {
+                    IpAddr::V4(ip) => ip.octets().to_vec(),
+                    IpAddr::V6(ip) => ip.octets().to_vec(),
+                };
+                let mut message = Vec::new();
+                bytes_field(1, &ip, &mut message);
+                varint_field(2, prefix.parse().unwrap(), &mut message);
+                bytes_field(2, &message, &mut entry);
+            }
+            bytes_field(1, &entry, &mut list);
+        }
+        list
+    }
+
+    #[test]
+    fn test_cidr() {
+        let cidr: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(cidr.contains("10.1.2.3".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:10.0.0.1".parse().unwrap()));
+        let cidr: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(cidr.contains("2001:db8::1".parse().unwrap()));
+        assert!(!cidr.contains("10.0.0.1".parse().unwrap()));
+        let all: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains("1.1.1.1".parse().unwrap()));
+        assert!("1.1.1.1/33".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_parse_geodata() {
+        let buf = geosite_dat(&[
+            ("cn", &[(2, "baidu.com")]),
+            (
+                "google",
+                &[
+                    (2, "google.com"),
+                    (3, "www.youtube.com"),
+                    (0, "gstatic"),
+                    (1, r"^ggpht\.[a-z]+$"),
+                    (1, "("),
+                ],
+            ),
+        ]);
+        let domains = parse_geosite(&buf, "google").unwrap().unwrap();
+        let matches = |domain| domains.iter().any(|item| item.matches(domain));
+        assert!(matches("google.com"));
+        assert!(matches("mail.google.com"));
+        assert!(!matches("notgoogle.com"));
+        assert!(matches("www.youtube.com"));
+        assert!(!matches("m.youtube.com"));
+        assert!(matches("fonts.gstatic.cn"));
+        assert!(matches("ggpht.cn"));
+        assert!(!matches("yt.ggpht.cn"));
+        assert!(parse_geosite(&buf, "netflix").unwrap().is_none());
+
+        let buf = geoip_dat(&[("private", &["10.0.0.0/8"]), ("cn", &["1.0.1.0/24"])]);
+        let geoip = parse_geoip(&buf, "CN").unwrap().unwrap();
+        assert!(geoip.contains("1.0.1.1".parse().unwrap()));
+        assert!(!geoip.contains("10.0.0.1".parse().unwrap()));
+    }
+}
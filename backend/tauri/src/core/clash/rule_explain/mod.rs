@@ -0,0 +1,938 @@
+//! Explain which rule of the runtime config a connection matches, and the proxies it goes through.
+//! The rules are walked in order like the core does, including the payloads of the cached
+//! `rule-providers`, the `sub-rules` and the local geodata. The rules which can not be evaluated
+//! locally, e.g. `IP-ASN` or a rule set in the `mrs` format, are skipped and reported.
+mod geodata;
+mod provider;
+
+use self::{
+    geodata::{Cidr, GeoData},
+    provider::{RuleSet, is_subdomain, load_rule_set, match_domain_entry},
+};
+use super::api::{ClashApi, ClashController, ProxyItem};
+use crate::{
+    config::{Config, RUNTIME_CONFIG},
+    utils::{dirs, help},
+};
+use anyhow::{Result, anyhow};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use specta::Type;
+use std::{collections::HashMap, net::IpAddr, path::PathBuf, sync::Arc};
+use strum::{Display, EnumString};
+
+/// the nesting depth of the `SUB-RULE`s and the logic rules at most
+const MAX_DEPTH: usize = 16;
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, Type, EnumString, Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum RuleNetwork {
+    #[default]
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Type)]
+pub struct RuleMatchRequest {
+    /// the domain or the ip of the destination
+    pub host: String,
+    pub port: Option<u16>,
+    /// the name or the path of the process, no process is assumed if it is not given
+    pub process: Option<String>,
+    /// `tcp` by default
+    pub network: Option<RuleNetwork>,
+}
+
+/// A rule in the `rules` or a `sub-rules`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub struct RuleStep {
+    /// `rules`, or the name of the `sub-rules`
+    pub rules: String,
+    pub index: usize,
+    pub rule: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, Type)]
+pub struct SkippedRule {
+    #[serde(flatten)]
+    pub step: RuleStep,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Type)]
+pub struct RuleExplanation {
+    /// `rule`, `global` or `direct`
+    pub mode: String,
+    /// the resolved ip of the domain, which the ip rules are matched against
+    pub ip: Option<String>,
+    /// the matched rule, led by the `SUB-RULE`s it is in. It is empty if no rule is matched
+    pub matched: Vec<RuleStep>,
+    pub target: String,
+    /// the target and the proxies selected by the groups in turn
+    pub chain: Vec<String>,
+    /// the rules before the matched one, which could not be evaluated
+    pub skipped: Vec<SkippedRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Verdict {
+    Match,
+    NoMatch,
+    Unknown(String),
+}
+
+impl Verdict {
+    fn from_bool(matched: bool) -> Self {
+        if matched { Self::Match } else { Self::NoMatch }
+    }
+}
+
+/// The connection to match
+#[derive(Debug, Clone)]
+struct Metadata {
+    domain: Option<String>,
+    ip: Option<IpAddr>,
+    port: Option<u16>,
+    network: RuleNetwork,
+    process_name: Option<String>,
+    process_path: Option<String>,
+}
+
+impl Metadata {
+    fn new(request: &RuleMatchRequest, resolved: Option<IpAddr>) -> Self {
+        let host = request.host.trim().trim_end_matches('.').to_lowercase();
+        let (domain, ip) = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            Ok(ip) => (None, Some(ip)),
+            Err(_) => (Some(host), resolved),
+        };
+        let (process_name, process_path) = match request.process.as_deref() {
+            Some(process) if process.contains(['/', '\\']) => (
+                process.rsplit(['/', '\\']).next().map(str::to_string),
+                Some(process.to_string()),
+            ),
+            Some(process) => (Some(process.to_string()), None),
+            None => (None, None),
+        };
+        Self {
+            domain,
+            ip,
+            port: request.port,
+            network: request.network.unwrap_or_default(),
+            process_name,
+            process_path,
+        }
+    }
+}
+
+/// A rule split into its parts, e.g. `IP-CIDR,10.0.0.0/8,DIRECT,no-resolve`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RuleLine<'a> {
+    kind: String,
+    payload: &'a str,
+    target: Option<&'a str>,
+    params: Vec<&'a str>,
+}
+
+/// the index of the parenthesis closing the one at the start
+fn closing_paren(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// split a rule, the rules in the logic rules and the classical rule sets have no targets
+fn parse_rule(line: &str, with_target: bool) -> Option<RuleLine<'_>> {
+    let (kind, rest) = line.split_once(',').unwrap_or((line, ""));
+    let kind = kind.trim().to_ascii_uppercase();
+    let rest = rest.trim();
+    if kind == "MATCH" || kind == "FINAL" {
+        let target = with_target.then(|| rest.split(',').next().map(str::trim));
+        return Some(RuleLine {
+            kind: "MATCH".to_string(),
+            payload: "",
+            target: target.flatten().filter(|target| !target.is_empty()),
+            params: Vec::new(),
+        });
+    }
+    let (payload, rest) = if rest.starts_with('(') {
+        let end = closing_paren(rest)?;
+        (
+            &rest[..=end],
+            rest[end + 1..].trim_start_matches([',', ' ']),
+        )
+    } else {
+        rest.split_once(',').unwrap_or((rest, ""))
+    };
+    let mut parts = rest.split(',').map(str::trim).filter(|s| !s.is_empty());
+    let target = if with_target {
+        Some(parts.next()?)
+    } else {
+        None
+    };
+    Some(RuleLine {
+        kind,
+        payload: payload.trim(),
+        target,
+        params: parts.collect(),
+    })
+}
+
+/// the conditions of a logic rule, e.g. `((DOMAIN,a.com),(NETWORK,udp))`
+fn split_conditions(payload: &str) -> Option<Vec<&str>> {
+    let inner = payload.trim().strip_prefix('(')?.strip_suffix(')')?;
+    let mut conditions = Vec::new();
+    let mut rest = inner.trim();
+    while !rest.is_empty() {
+        let end = closing_paren(rest)?;
+        conditions.push(&rest[1..end]);
+        rest = rest[end + 1..].trim_start_matches([',', ' ']);
+    }
+    Some(conditions)
+}
+
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    match pattern.chars().next() {
+        None => s.is_empty(),
+        Some('*') => (0..=s.len())
+            .filter(|i| s.is_char_boundary(*i))
+            .any(|i| wildcard_match(&pattern[1..], &s[i..])),
+        Some(c) => {
+            let mut chars = s.chars();
+            chars.next().is_some_and(|first| c == '?' || c == first)
+                && wildcard_match(&pattern[c.len_utf8()..], chars.as_str())
+        }
+    }
+}
+
+/// whether the port is in the ranges, e.g. `80/443/8000-9000`
+fn match_ports(ranges: &str, port: u16) -> Option<bool> {
+    for range in ranges.split(['/', ',']).map(str::trim) {
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let port = range.parse().ok()?;
+                (port, port)
+            }
+        };
+        if (start..=end).contains(&port) {
+            return Some(true);
+        }
+    }
+    Some(false)
+}
+
+fn is_lan(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+        }
+    }
+}
+
+/// the target and the proxies selected by the groups in turn
+pub fn resolve_chain(target: &str, proxies: &IndexMap<String, ProxyItem>) -> Vec<String> {
+    let mut chain = vec![target.to_string()];
+    while let Some(now) = proxies
+        .get(chain.last().unwrap())
+        .and_then(|item| item.now.as_deref())
+        .filter(|now| !now.is_empty())
+    {
+        if chain.iter().any(|name| name == now) {
+            break;
+        }
+        chain.push(now.to_string());
+    }
+    chain
+}
+
+/// Walks the rules of a runtime config
+pub struct RuleExplainer {
+    mode: String,
+    rules: Vec<String>,
+    sub_rules: IndexMap<String, Vec<String>>,
+    providers: Mapping,
+    home_dir: PathBuf,
+    rule_sets: HashMap<String, Result<Arc<RuleSet>, String>>,
+    geodata: GeoData,
+}
+
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl RuleExplainer {
+    /// `home_dir` is the home dir of the core, where the geodata and the providers are cached
+    pub fn new(config: &Mapping, home_dir: PathBuf) -> Self {
+        let sub_rules = config
+            .get("sub-rules")
+            .and_then(Value::as_mapping)
+            .map(|mapping| {
+                mapping
+                    .iter()
+                    .filter_map(|(name, rules)| {
+                        Some((name.as_str()?.to_string(), string_list(Some(rules))))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            mode: config
+                .get("mode")
+                .and_then(Value::as_str)
+                .unwrap_or("rule")
+                .to_lowercase(),
+            rules: string_list(config.get("rules")),
+            sub_rules,
+            providers: config
+                .get("rule-providers")
+                .and_then(Value::as_mapping)
+                .cloned()
+                .unwrap_or_default(),
+            geodata: GeoData::new(home_dir.clone()),
+            home_dir,
+            rule_sets: HashMap::new(),
+        }
+    }
+
+    /// whether an ip rule has to resolve the domain
+    pub fn needs_ip(&self) -> bool {
+        self.mode == "rule"
+    }
+
+    /// match the request, `resolved` is the ip of the domain if it is resolved.
+    /// The chain only contains the target, see [`resolve_chain`]
+    pub fn explain(
+        &mut self,
+        request: &RuleMatchRequest,
+        resolved: Option<IpAddr>,
+    ) -> RuleExplanation {
+        let metadata = Metadata::new(request, resolved);
+        let mut explanation = RuleExplanation {
+            mode: self.mode.clone(),
+            ip: metadata.ip.map(|ip| ip.to_string()),
+            matched: Vec::new(),
+            target: "DIRECT".to_string(),
+            chain: Vec::new(),
+            skipped: Vec::new(),
+        };
+        match self.mode.as_str() {
+            "global" => explanation.target = "GLOBAL".to_string(),
+            "direct" => {}
+            _ => {
+                let rules = self.rules.clone();
+                if let Some(target) = self.walk(
+                    "rules",
+                    &rules,
+                    &metadata,
+                    &mut explanation.matched,
+                    &mut explanation.skipped,
+                    0,
+                ) {
+                    explanation.target = target;
+                }
+            }
+        }
+        explanation.chain = vec![explanation.target.clone()];
+        explanation
+    }
+
+    /// walk the rules, return the target of the first matched rule
+    fn walk(
+        &mut self,
+        name: &str,
+        rules: &[String],
+        metadata: &Metadata,
+        matched: &mut Vec<RuleStep>,
+        skipped: &mut Vec<SkippedRule>,
+        depth: usize,
+    ) -> Option<String> {
+        for (index, rule) in rules.iter().enumerate() {
+            let step = RuleStep {
+                rules: name.to_string(),
+                index,
+                rule: rule.clone(),
+            };
+            let Some(line) = parse_rule(rule, true) else {
+                skipped.push(SkippedRule {
+                    step,
+                    reason: "invalid rule".to_string(),
+                });
+                continue;
+            };
+            let condition = if line.kind == "SUB-RULE" {
+                let condition = line
+                    .payload
+                    .strip_prefix('(')
+                    .and_then(|condition| condition.strip_suffix(')'))
+                    .unwrap_or(line.payload);
+                match parse_rule(condition, false) {
+                    Some(condition) => self.eval(&condition, metadata, depth + 1),
+                    None => Verdict::Unknown("invalid condition".to_string()),
+                }
+            } else {
+                self.eval(&line, metadata, depth)
+            };
+            match condition {
+                Verdict::NoMatch => {}
+                Verdict::Unknown(reason) => skipped.push(SkippedRule { step, reason }),
+                Verdict::Match if line.kind == "SUB-RULE" => {
+                    let sub_name = line.target.unwrap_or_default();
+                    let Some(sub_rules) = self.sub_rules.get(sub_name).cloned() else {
+                        skipped.push(SkippedRule {
+                            step,
+                            reason: format!("sub-rules `{sub_name}` not found"),
+                        });
+                        continue;
+                    };
+                    if depth >= MAX_DEPTH {
+                        skipped.push(SkippedRule {
+                            step,
+                            reason: "the sub-rules are nested too deep".to_string(),
+                        });
+                        continue;
+                    }
+                    matched.push(step);
+                    // the following rules are matched if none of the sub-rules matches
+                    match self.walk(sub_name, &sub_rules, metadata, matched, skipped, depth + 1) {
+                        Some(target) => return Some(target),
+                        None => {
+                            matched.pop();
+                        }
+                    }
+                }
+                Verdict::Match => {
+                    matched.push(step);
+                    return line.target.map(str::to_string);
+                }
+            }
+        }
+        None
+    }
+
+    fn rule_set(&mut self, name: &str) -> Result<Arc<RuleSet>, String> {
+        if let Some(rule_set) = self.rule_sets.get(name) {
+            return rule_set.clone();
+        }
+        let rule_set = match self.providers.get(name).and_then(Value::as_mapping) {
+            Some(provider) => load_rule_set(provider, &self.home_dir)
+                .map(Arc::new)
+                .map_err(|err| format!("{err:#}")),
+            None => Err(format!("rule provider `{name}` not found")),
+        };
+        self.rule_sets.insert(name.to_string(), rule_set.clone());
+        rule_set
+    }
+
+    /// match the ip rules, the domain is not resolved for the rules with `no-resolve`
+    fn eval_ip(
+        &self,
+        metadata: &Metadata,
+        params: &[&str],
+        f: impl FnOnce(IpAddr) -> Verdict,
+    ) -> Verdict {
+        let no_resolve = params
+            .iter()
+            .any(|param| param.eq_ignore_ascii_case("no-resolve"));
+        match metadata.ip {
+            _ if metadata.domain.is_some() && no_resolve => Verdict::NoMatch,
+            Some(ip) => f(ip),
+            None => Verdict::Unknown("the domain is not resolved".to_string()),
+        }
+    }
+
+    fn eval(&mut self, line: &RuleLine<'_>, metadata: &Metadata, depth: usize) -> Verdict {
+        let payload = line.payload;
+        let domain = metadata.domain.as_deref();
+        match line.kind.as_str() {
+            "MATCH" => Verdict::Match,
+            "DOMAIN" => Verdict::from_bool(domain.is_some_and(|d| d == payload.to_lowercase())),
+            "DOMAIN-SUFFIX" => {
+                let suffix = payload.to_lowercase();
+                Verdict::from_bool(domain.is_some_and(|d| d == suffix || is_subdomain(d, &suffix)))
+            }
+            "DOMAIN-KEYWORD" => {
+                Verdict::from_bool(domain.is_some_and(|d| d.contains(&payload.to_lowercase())))
+            }
+            "DOMAIN-WILDCARD" => Verdict::from_bool(
+                domain.is_some_and(|d| wildcard_match(&payload.to_lowercase(), d)),
+            ),
+            "DOMAIN-REGEX" => match regex::Regex::new(payload) {
+                Ok(regex) => Verdict::from_bool(domain.is_some_and(|d| regex.is_match(d))),
+                Err(err) => Verdict::Unknown(format!("invalid regex: {err}")),
+            },
+            "GEOSITE" => {
+                let Some(domain) = domain else {
+                    return Verdict::NoMatch;
+                };
+                let (code, attribute) = payload
+                    .split_once('@')
+                    .map_or((payload, None), |(code, attr)| (code, Some(attr)));
+                match self.geodata.geosite(code) {
+                    Ok(Some(domains)) => Verdict::from_bool(domains.iter().any(|item| {
+                        attribute.is_none_or(|attr| {
+                            item.attributes.iter().any(|a| a.eq_ignore_ascii_case(attr))
+                        }) && item.matches(domain)
+                    })),
+                    Ok(None) => Verdict::Unknown(format!("`{code}` is not found in geosite")),
+                    Err(err) => Verdict::Unknown(format!("{err:#}")),
+                }
+            }
+            "IP-CIDR" | "IP-CIDR6" => match payload.parse::<Cidr>() {
+                Ok(cidr) => self.eval_ip(metadata, &line.params, |ip| {
+                    Verdict::from_bool(cidr.contains(ip))
+                }),
+                Err(err) => Verdict::Unknown(format!("invalid cidr: {err}")),
+            },
+            "GEOIP" => {
+                if payload.eq_ignore_ascii_case("lan") {
+                    return self
+                        .eval_ip(metadata, &line.params, |ip| Verdict::from_bool(is_lan(ip)));
+                }
+                let geoip = match self.geodata.geoip(payload) {
+                    Ok(Some(geoip)) => geoip,
+                    Ok(None) => {
+                        return Verdict::Unknown(format!("`{payload}` is not found in geoip"));
+                    }
+                    Err(err) => return Verdict::Unknown(format!("{err:#}")),
+                };
+                self.eval_ip(metadata, &line.params, |ip| {
+                    Verdict::from_bool(geoip.contains(ip))
+                })
+            }
+            "DST-PORT" => match metadata.port.map(|port| match_ports(payload, port)) {
+                Some(Some(matched)) => Verdict::from_bool(matched),
+                Some(None) => Verdict::Unknown("invalid port range".to_string()),
+                None => Verdict::Unknown("the port is not given".to_string()),
+            },
+            "NETWORK" => {
+                Verdict::from_bool(payload.eq_ignore_ascii_case(&metadata.network.to_string()))
+            }
+            "PROCESS-NAME" => Verdict::from_bool(
+                metadata
+                    .process_name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(payload)),
+            ),
+            "PROCESS-PATH" => Verdict::from_bool(
+                metadata
+                    .process_path
+                    .as_deref()
+                    .is_some_and(|path| path.eq_ignore_ascii_case(payload)),
+            ),
+            "PROCESS-NAME-REGEX" | "PROCESS-PATH-REGEX" => {
+                let target = if line.kind == "PROCESS-NAME-REGEX" {
+                    metadata.process_name.as_deref()
+                } else {
+                    metadata.process_path.as_deref()
+                };
+                match regex::Regex::new(payload) {
+                    Ok(regex) => Verdict::from_bool(target.is_some_and(|t| regex.is_match(t))),
+                    Err(err) => Verdict::Unknown(format!("invalid regex: {err}")),
+                }
+            }
+            "RULE-SET" => match self.rule_set(payload).as_deref() {
+                Ok(RuleSet::Domain(entries)) => Verdict::from_bool(
+                    domain
+                        .is_some_and(|d| entries.iter().any(|entry| match_domain_entry(entry, d))),
+                ),
+                Ok(RuleSet::IpCidr(cidrs)) => self.eval_ip(metadata, &line.params, |ip| {
+                    Verdict::from_bool(cidrs.iter().any(|cidr| cidr.contains(ip)))
+                }),
+                Ok(RuleSet::Classical(rules)) => self.eval_any(
+                    rules.iter().map(String::as_str),
+                    &line.params,
+                    metadata,
+                    depth,
+                ),
+                Err(reason) => Verdict::Unknown(reason.clone()),
+            },
+            "AND" | "OR" | "NOT" => {
+                if depth >= MAX_DEPTH {
+                    return Verdict::Unknown("the logic rule is nested too deep".to_string());
+                }
+                let Some(conditions) = split_conditions(payload) else {
+                    return Verdict::Unknown("invalid logic rule".to_string());
+                };
+                match line.kind.as_str() {
+                    "OR" => self.eval_any(conditions.into_iter(), &[], metadata, depth + 1),
+                    "AND" => {
+                        let mut verdict = Verdict::Match;
+                        for condition in conditions {
+                            match self.eval_str(condition, &[], metadata, depth + 1) {
+                                Verdict::NoMatch => return Verdict::NoMatch,
+                                Verdict::Unknown(reason) => verdict = Verdict::Unknown(reason),
+                                Verdict::Match => {}
+                            }
+                        }
+                        verdict
+                    }
+                    _ => match conditions.as_slice() {
+                        [condition] => match self.eval_str(condition, &[], metadata, depth + 1) {
+                            Verdict::Match => Verdict::NoMatch,
+                            Verdict::NoMatch => Verdict::Match,
+                            unknown => unknown,
+                        },
+                        _ => Verdict::Unknown("`NOT` takes a single condition".to_string()),
+                    },
+                }
+            }
+            kind => Verdict::Unknown(format!("`{kind}` rules can not be evaluated locally")),
+        }
+    }
+
+    /// evaluate a rule without target, the params of the outer rule (e.g. `no-resolve`
+    /// of a `RULE-SET`) are applied to it as well
+    fn eval_str(
+        &mut self,
+        rule: &str,
+        params: &[&str],
+        metadata: &Metadata,
+        depth: usize,
+    ) -> Verdict {
+        match parse_rule(rule, false) {
+            Some(mut line) => {
+                line.params.extend_from_slice(params);
+                self.eval(&line, metadata, depth)
+            }
+            None => Verdict::Unknown(format!("invalid rule `{rule}`")),
+        }
+    }
+
+    /// matched if any rule is matched, unknown if none is matched but some are unknown
+    fn eval_any<'a>(
+        &mut self,
+        rules: impl Iterator<Item = &'a str>,
+        params: &[&str],
+        metadata: &Metadata,
+        depth: usize,
+    ) -> Verdict {
+        let mut verdict = Verdict::NoMatch;
+        for rule in rules {
+            match self.eval_str(rule, params, metadata, depth) {
+                Verdict::Match => return Verdict::Match,
+                Verdict::Unknown(reason) => verdict = Verdict::Unknown(reason),
+                Verdict::NoMatch => {}
+            }
+        }
+        verdict
+    }
+}
+
+/// the runtime config in use, which is read from the generated file if the app is not running
+fn runtime_config() -> Result<Mapping> {
+    if let Some(config) = Config::runtime().latest().config.clone() {
+        return Ok(config);
+    }
+    help::read_yaml(&dirs::app_config_dir()?.join(RUNTIME_CONFIG))
+}
+
+/// explain the request against the runtime config. The domain is resolved
+/// and the chain is followed through the running core, if it is reachable
+pub async fn explain(request: RuleMatchRequest) -> Result<RuleExplanation> {
+    let config = runtime_config()?;
+    let home_dir = dirs::app_data_dir()?;
+    let controller = ClashController::current().ok();
+
+    let mut explainer = RuleExplainer::new(&config, home_dir);
+    let is_domain = request
+        .host
+        .trim()
+        .trim_matches(['[', ']'])
+        .parse::<IpAddr>()
+        .is_err();
+    let resolved = match &controller {
+        Some(controller) if is_domain && explainer.needs_ip() => {
+            resolve(controller, request.host.trim()).await
+        }
+        _ => None,
+    };
+
+    let mut explanation =
+        tokio::task::spawn_blocking(move || explainer.explain(&request, resolved))
+            .await
+            .map_err(|err| anyhow!("failed to explain the rules: {err}"))?;
+
+    if let Some(controller) = &controller {
+        match controller.get_proxies().await {
+            Ok(proxies) => explanation.chain = resolve_chain(&explanation.target, &proxies.proxies),
+            Err(err) => log::debug!(target: "app", "failed to get the proxies: {err:?}"),
+        }
+    }
+    Ok(explanation)
+}
+
+/// resolve the domain by the DNS of the core, like the core does for the ip rules
+async fn resolve(controller: &ClashController, domain: &str) -> Option<IpAddr> {
+    for record_type in ["A", "AAAA"] {
+        match controller.query_dns(domain, Some(record_type)).await {
+            Ok(res) => {
+                if let Some(ip) = res
+                    .answer
+                    .iter()
+                    .find_map(|answer| answer.data.parse().ok())
+                {
+                    return Some(ip);
+                }
+            }
+            Err(err) => {
+                log::debug!(target: "app", "failed to resolve `{domain}`: {err:?}");
+                return None;
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{geodata::tests::*, *};
+    use pretty_assertions::assert_eq;
+
+    fn request(host: &str) -> RuleMatchRequest {
+        RuleMatchRequest {
+            host: host.to_string(),
+            port: Some(443),
+            ..Default::default()
+        }
+    }
+
+    fn step(rules: &str, index: usize, rule: &str) -> RuleStep {
+        RuleStep {
+            rules: rules.to_string(),
+            index,
+            rule: rule.to_string(),
+        }
+    }
+
+    fn new_explainer(yaml: &str) -> (tempfile::TempDir, RuleExplainer) {
+        let dir = tempfile::tempdir().unwrap();
+        fs_err::write(
+            dir.path().join("GeoSite.dat"),
+            geosite_dat(&[("google", &[(2, "google.com")])]),
+        )
+        .unwrap();
+        fs_err::write(
+            dir.path().join("geoip.dat"),
+            geoip_dat(&[("cn", &["1.0.1.0/24"])]),
+        )
+        .unwrap();
+        fs_err::write(
+            dir.path().join("ads.txt"),
+            "DOMAIN-SUFFIX,ads.com\nAND,((NETWORK,udp),(DST-PORT,443))\n",
+        )
+        .unwrap();
+        let config: Mapping = serde_yaml::from_str(yaml).unwrap();
+        let explainer = RuleExplainer::new(&config, dir.path().to_path_buf());
+        (dir, explainer)
+    }
+
+    const CONFIG: &str = r#"
+rule-providers:
+  ads:
+    type: file
+    behavior: classical
+    format: text
+    path: ./ads.txt
+  missing:
+    type: http
+    behavior: domain
+    url: https://example.com/missing.yaml
+sub-rules:
+  stream:
+    - DOMAIN-KEYWORD,netflix,Netflix
+    - DST-PORT,8080/9000-9100,Stream
+rules:
+  - RULE-SET,ads,REJECT
+  - RULE-SET,missing,DIRECT
+  - GEOSITE,google,Proxy
+  - SUB-RULE,(OR,((NETWORK,tcp),(NETWORK,udp))),stream
+  - IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+  - GEOIP,CN,DIRECT
+  - NOT,((DOMAIN-WILDCARD,*.example.?om)),Final
+  - MATCH,Proxy
+"#;
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            parse_rule("IP-CIDR,10.0.0.0/8,DIRECT,no-resolve", true),
+            Some(RuleLine {
+                kind: "IP-CIDR".to_string(),
+                payload: "10.0.0.0/8",
+                target: Some("DIRECT"),
+                params: vec!["no-resolve"],
+            })
+        );
+        assert_eq!(
+            parse_rule("AND,((DOMAIN,a.com),(NETWORK,udp)),REJECT", true).map(|l| l.payload),
+            Some("((DOMAIN,a.com),(NETWORK,udp))")
+        );
+        assert_eq!(
+            split_conditions("((DOMAIN,a.com),(NOT,((NETWORK,udp))))"),
+            Some(vec!["DOMAIN,a.com", "NOT,((NETWORK,udp))"])
+        );
+        assert_eq!(parse_rule("DOMAIN,a.com", true), None);
+        assert_eq!(
+            parse_rule("MATCH,Proxy", true).and_then(|l| l.target),
+            Some("Proxy")
+        );
+        assert!(wildcard_match("*.example.?om", "www.example.com"));
+        assert!(!wildcard_match("*.example.?om", "example.com"));
+        assert_eq!(match_ports("80/8000-9000", 8443), Some(true));
+        assert_eq!(match_ports("80/8000-9000", 443), Some(false));
+    }
+
+    #[test]
+    fn test_explain() {
+        let (_dir, mut explainer) = new_explainer(CONFIG);
+
+        let explanation = explainer.explain(&request("tracker.ads.com"), None);
+        assert_eq!(explanation.target, "REJECT");
+        assert_eq!(
+            explanation.matched,
+            vec![step("rules", 0, "RULE-SET,ads,REJECT")]
+        );
+
+        let explanation = explainer.explain(&request("mail.google.com"), None);
+        assert_eq!(explanation.target, "Proxy");
+        assert_eq!(explanation.matched[0].index, 2);
+        // the rule set is not cached
+        assert_eq!(explanation.skipped.len(), 1);
+        assert_eq!(explanation.skipped[0].step.index, 1);
+
+        let explanation = explainer.explain(&request("www.netflix.com"), None);
+        assert_eq!(explanation.target, "Netflix");
+        assert_eq!(
+            explanation.matched,
+            vec![
+                step(
+                    "rules",
+                    3,
+                    "SUB-RULE,(OR,((NETWORK,tcp),(NETWORK,udp))),stream"
+                ),
+                step("stream", 0, "DOMAIN-KEYWORD,netflix,Netflix"),
+            ]
+        );
+
+        // the sub-rules does not match, and the domain is not resolved
+        let explanation = explainer.explain(&request("www.example.com"), None);
+        assert_eq!(explanation.target, "Proxy");
+        assert_eq!(explanation.matched[0].index, 7);
+        assert_eq!(
+            explanation
+                .skipped
+                .iter()
+                .map(|skipped| skipped.step.index)
+                .collect::<Vec<_>>(),
+            vec![1, 5]
+        );
+
+        // the ip rule with `no-resolve` is not matched by the resolved domain
+        let explanation = explainer.explain(&request("cn.example.org"), "10.0.0.1".parse().ok());
+        assert_eq!(explanation.target, "Final");
+        let explanation = explainer.explain(&request("10.0.0.1"), None);
+        assert_eq!(explanation.target, "DIRECT");
+        assert_eq!(explanation.matched[0].index, 4);
+        let explanation = explainer.explain(&request("1.0.1.1"), None);
+        assert_eq!(explanation.matched[0].index, 5);
+
+        let mut udp = request("1.1.1.1");
+        udp.network = Some(RuleNetwork::Udp);
+        let explanation = explainer.explain(&udp, None);
+        assert_eq!(explanation.target, "REJECT");
+        assert_eq!(explanation.ip.as_deref(), Some("1.1.1.1"));
+
+        let (_dir, mut explainer) = new_explainer("{mode: global, rules: ['MATCH,DIRECT']}");
+        assert_eq!(explainer.explain(&request("a.com"), None).target, "GLOBAL");
+    }
+
+    #[test]
+    fn test_rule_set_no_resolve() {
+        let config = r#"
+rule-providers:
+  lan:
+    type: file
+    behavior: classical
+    format: text
+    path: ./lan.txt
+rules:
+  - RULE-SET,lan,DIRECT,no-resolve
+  - MATCH,Proxy
+"#;
+        let (dir, mut explainer) = new_explainer(config);
+        fs_err::write(dir.path().join("lan.txt"), "IP-CIDR,10.0.0.0/8\n").unwrap();
+        // the `no-resolve` of the rule set applies to its ip rules
+        let explanation = explainer.explain(&request("nas.example.org"), "10.0.0.1".parse().ok());
+        assert_eq!(explanation.target, "Proxy");
+        assert_eq!(
+            explainer.explain(&request("10.0.0.1"), None).target,
+            "DIRECT"
+        );
+
+        let (dir, mut explainer) = new_explainer(&config.replace(",no-resolve", ""));
+        fs_err::write(dir.path().join("lan.txt"), "IP-CIDR,10.0.0.0/8\n").unwrap();
+        let explanation = explainer.explain(&request("nas.example.org"), "10.0.0.1".parse().ok());
+        assert_eq!(explanation.target, "DIRECT");
+    }
+
+    #[test]
+    fn test_resolve_chain() {
+        let group = |name: &str, now: &str| {
+            (
+                name.to_string(),
+                ProxyItem {
+                    name: name.to_string(),
+                    now: Some(now.to_string()),
+                    ..Default::default()
+                },
+            )
+        };
+        let proxies = IndexMap::from([
+            group("Proxy", "Auto"),
+            group("Auto", "HK"),
+            group("Loop", "Loop"),
+        ]);
+        assert_eq!(
+            resolve_chain("Proxy", &proxies),
+            vec!["Proxy", "Auto", "HK"]
+        );
+        assert_eq!(resolve_chain("Loop", &proxies), vec!["Loop"]);
+        assert_eq!(resolve_chain("DIRECT", &proxies), vec!["DIRECT"]);
+    }
+}
@@ -0,0 +1,206 @@
+//! Load the payloads of the `rule-providers` from the caches of the core.
+use super::geodata::Cidr;
+use crate::core::provider_mirror::ProviderMirror;
+use anyhow::{Context, Result, anyhow, bail};
+use md5::{Digest, Md5};
+use serde_yaml::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+/// The payload of a `rule-provider`
+#[derive(Debug, Clone)]
+pub enum RuleSet {
+    /// `+.example.com`, `.example.com`, `*.example.com` or `example.com`
+    Domain(Vec<String>),
+    IpCidr(Vec<Cidr>),
+    /// the rules without targets, e.g. `DOMAIN-SUFFIX,example.com`
+    Classical(Vec<String>),
+}
+
+impl RuleSet {
+    pub fn new(behavior: &str, payload: Vec<String>) -> Result<Self> {
+        Ok(match behavior.to_ascii_lowercase().as_str() {
+            "domain" => Self::Domain(payload.into_iter().map(|s| s.to_lowercase()).collect()),
+            "ipcidr" => Self::IpCidr(
+                payload
+                    .iter()
+                    .map(|s| s.parse())
+                    .collect::<Result<_>>()
+                    .context("invalid ipcidr payload")?,
+            ),
+            "classical" => Self::Classical(payload),
+            _ => bail!("unknown behavior `{behavior}`"),
+        })
+    }
+}
+
+/// whether the domain matches an entry of a `domain` rule set
+pub fn match_domain_entry(entry: &str, domain: &str) -> bool {
+    if let Some(suffix) = entry.strip_prefix("+.") {
+        domain == suffix || is_subdomain(domain, suffix)
+    } else if let Some(suffix) = entry.strip_prefix('.') {
+        is_subdomain(domain, suffix)
+    } else if let Some(suffix) = entry.strip_prefix("*.") {
+        domain
+            .strip_suffix(suffix)
+            .and_then(|prefix| prefix.strip_suffix('.'))
+            .is_some_and(|label| !label.is_empty() && !label.contains('.'))
+    } else {
+        domain == entry
+    }
+}
+
+pub fn is_subdomain(domain: &str, suffix: &str) -> bool {
+    domain
+        .strip_suffix(suffix)
+        .is_some_and(|prefix| prefix.len() > 1 && prefix.ends_with('.'))
+}
+
+/// the lines of a `text` payload, or the `payload` of a `yaml` one
+fn parse_payload(content: &str, format: &str) -> Result<Vec<String>> {
+    match format {
+        "text" => Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect()),
+        "yaml" => {
+            let value: Value = serde_yaml::from_str(content)?;
+            payload_of(value.get("payload"))
+        }
+        "mrs" => bail!("the `mrs` format is not supported"),
+        _ => bail!("unknown format `{format}`"),
+    }
+}
+
+fn payload_of(value: Option<&Value>) -> Result<Vec<String>> {
+    value
+        .and_then(Value::as_sequence)
+        .ok_or_else(|| anyhow!("the payload is not a list"))?
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(|s| s.trim().to_string())
+                .ok_or_else(|| anyhow!("the payload item is not a string"))
+        })
+        .collect()
+}
+
+/// the file the core reads the payload from
+fn cached_path(provider: &Mapping, home_dir: &Path) -> Result<PathBuf> {
+    if let Some(path) = provider.get("path").and_then(Value::as_str) {
+        return Ok(home_dir.join(path));
+    }
+    let url = provider
+        .get("url")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("neither `path` nor `url` is set"))?;
+    if let Ok(parsed) = url::Url::parse(url)
+        && let Some(key) = crate::enhance::mirrored_key(&parsed)
+        && let Some(path) = ProviderMirror::global()
+            .ok()
+            .and_then(|mirror| mirror.cached_path(&key))
+    {
+        return Ok(path);
+    }
+    // the core caches the http providers without `path` by the md5 of the url
+    let hash = hex::encode(Md5::digest(url.as_bytes()));
+    Ok(home_dir.join("rules").join(hash))
+}
+
+/// load the rule set of the provider, the relative paths are resolved against `home_dir`
+pub fn load_rule_set(provider: &Mapping, home_dir: &Path) -> Result<RuleSet> {
+    let behavior = provider
+        .get("behavior")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("`behavior` is not set"))?;
+    let payload = match provider.get("type").and_then(Value::as_str) {
+        Some("inline") => payload_of(provider.get("payload"))?,
+        _ => {
+            let path = cached_path(provider, home_dir)?;
+            let format = provider
+                .get("format")
+                .and_then(Value::as_str)
+                .unwrap_or("yaml");
+            let content = fs_err::read_to_string(&path)
+                .with_context(|| format!("the payload is not cached at {}", path.display()))?;
+            parse_payload(&content, format)?
+        }
+    };
+    RuleSet::new(behavior, payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_match_domain_entry() {
+        assert!(match_domain_entry("+.google.com", "google.com"));
+        assert!(match_domain_entry("+.google.com", "a.b.google.com"));
+        assert!(!match_domain_entry("+.google.com", "notgoogle.com"));
+        assert!(!match_domain_entry(".google.com", "google.com"));
+        assert!(match_domain_entry(".google.com", "a.b.google.com"));
+        assert!(match_domain_entry("*.google.com", "www.google.com"));
+        assert!(!match_domain_entry("*.google.com", "a.b.google.com"));
+        assert!(match_domain_entry("google.com", "google.com"));
+        assert!(!match_domain_entry("google.com", "www.google.com"));
+    }
+
+    #[test]
+    fn test_load_rule_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://example.com/cn.txt";
+        let hash = hex::encode(Md5::digest(url.as_bytes()));
+        fs_err::create_dir_all(dir.path().join("rules")).unwrap();
+        fs_err::write(
+            dir.path().join("rules").join(hash),
+            "# comment\n1.0.1.0/24\n\n2001:db8::/32\n",
+        )
+        .unwrap();
+        fs_err::write(
+            dir.path().join("ads.yaml"),
+            "payload:\n  - DOMAIN-SUFFIX,ads.com\n  - DOMAIN,tracker.net\n",
+        )
+        .unwrap();
+
+        let provider = |yaml: &str| serde_yaml::from_str::<Mapping>(yaml).unwrap();
+        let RuleSet::IpCidr(cidrs) = load_rule_set(
+            &provider(&format!(
+                "{{type: http, behavior: ipcidr, format: text, url: '{url}'}}"
+            )),
+            dir.path(),
+        )
+        .unwrap() else {
+            panic!("expected an ipcidr rule set");
+        };
+        assert_eq!(cidrs.len(), 2);
+
+        let RuleSet::Classical(rules) = load_rule_set(
+            &provider("{type: file, behavior: classical, path: ./ads.yaml}"),
+            dir.path(),
+        )
+        .unwrap() else {
+            panic!("expected a classical rule set");
+        };
+        assert_eq!(rules, vec!["DOMAIN-SUFFIX,ads.com", "DOMAIN,tracker.net"]);
+
+        let RuleSet::Domain(domains) = load_rule_set(
+            &provider("{type: inline, behavior: domain, payload: ['+.Example.com']}"),
+            dir.path(),
+        )
+        .unwrap() else {
+            panic!("expected a domain rule set");
+        };
+        assert_eq!(domains, vec!["+.example.com"]);
+
+        assert!(
+            load_rule_set(
+                &provider("{type: file, behavior: domain, path: ./missing.yaml}"),
+                dir.path()
+            )
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Remember the selected proxies of the `Selector` groups per profile,
+//! and restore them after the core restarts or reloads the config,
+//! the groups revert to their first proxies unless the cache file of the core survives.
+use super::{
+    CLASH_API_DEFAULT_BACKOFF_STRATEGY,
+    api::{self, ProxyItem},
+};
+use crate::{
+    config::{Config, profile::item_type::ProfileUid},
+    core::{
+        handle::Handle,
+        storage::{Storage, StorageOperationError},
+    },
+};
+use anyhow::Result;
+use backon::Retryable;
+use indexmap::IndexMap;
+use redb::{ReadableDatabase, TableDefinition};
+use std::result::Result as StdResult;
+use tauri::Manager;
+
+/// (profile uid, group) -> proxy
+pub const PROXY_SELECTIONS_TABLE: TableDefinition<(&str, &str), &str> =
+    TableDefinition::new("proxy-selections");
+
+#[derive(Clone)]
+pub struct ProxySelections {
+    storage: Storage,
+}
+
+impl ProxySelections {
+    pub fn try_new(storage: Storage) -> StdResult<Self, StorageOperationError> {
+        let write_txn = storage.get_instance().begin_write()?;
+        write_txn.open_table(PROXY_SELECTIONS_TABLE)?;
+        write_txn.commit()?;
+        Ok(Self { storage })
+    }
+
+    pub fn set(
+        &self,
+        profile: &str,
+        group: &str,
+        proxy: &str,
+    ) -> StdResult<(), StorageOperationError> {
+        let write_txn = self.storage.get_instance().begin_write()?;
+        {
+            let mut table = write_txn.open_table(PROXY_SELECTIONS_TABLE)?;
+            table.insert((profile, group), proxy)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// the (group, proxy) selections of the profile
+    pub fn get_all(
+        &self,
+        profile: &str,
+    ) -> StdResult<Vec<(String, String)>, StorageOperationError> {
+        let read_txn = self.storage.get_instance().begin_read()?;
+        let table = read_txn.open_table(PROXY_SELECTIONS_TABLE)?;
+        let mut selections = Vec::new();
+        for entry in table.range((profile, "")..)? {
+            let (key, value) = entry?;
+            let (key_profile, group) = key.value();
+            if key_profile != profile {
+                break;
+            }
+            selections.push((group.to_string(), value.value().to_string()));
+        }
+        Ok(selections)
+    }
+}
+
+/// the selections belong to the first current profile, which the groups are defined by mostly
+fn current_profile() -> Option<ProfileUid> {
+    Config::profiles().latest().get_current().first().cloned()
+}
+
+fn instance() -> Option<ProxySelections> {
+    let app_handle = Handle::global().app_handle.lock();
+    let selections = app_handle.as_ref()?.try_state::<ProxySelections>()?;
+    Some(selections.inner().clone())
+}
+
+/// remember the proxy selected in the group of the current profile
+pub fn remember(group: &str, proxy: &str) {
+    let (Some(selections), Some(profile)) = (instance(), current_profile()) else {
+        return;
+    };
+    if let Err(err) = selections.set(&profile, group, proxy) {
+        log::warn!(target: "app", "failed to remember the selection of `{group}`: {err}");
+    }
+}
+
+/// the selections to apply, the missing groups and proxies, the other kinds of groups
+/// and the groups which select the proxy already are skipped
+fn plan(
+    selections: Vec<(String, String)>,
+    proxies: &IndexMap<String, ProxyItem>,
+) -> Vec<(String, String)> {
+    selections
+        .into_iter()
+        .filter(|(group, proxy)| {
+            proxies.get(group).is_some_and(|item| {
+                item.r#type == "Selector"
+                    && item.now.as_ref() != Some(proxy)
+                    && item.all.as_ref().is_some_and(|all| all.contains(proxy))
+            })
+        })
+        .collect()
+}
+
+/// restore the selections of the current profile once `/proxies` is reachable,
+/// return the count of the restored groups
+pub async fn restore() -> Result<usize> {
+    let proxies = api::get_proxies
+        .retry(*CLASH_API_DEFAULT_BACKOFF_STRATEGY)
+        .await?
+        .proxies;
+    let (Some(selections), Some(profile)) = (instance(), current_profile()) else {
+        return Ok(0);
+    };
+    let mut restored = 0;
+    for (group, proxy) in plan(selections.get_all(&profile)?, &proxies) {
+        match api::update_proxy(&group, &proxy).await {
+            Ok(_) => restored += 1,
+            Err(err) => {
+                log::warn!(target: "app", "failed to restore the selection of `{group}`: {err:?}");
+            }
+        }
+    }
+    if restored > 0 {
+        log::info!(target: "app", "restored the selections of {restored} groups");
+        crate::feat::update_proxies_buff(None);
+    }
+    Ok(restored)
+}
+
+/// restore the selections in the background, the core may be not ready yet
+pub fn restore_in_background() {
+    tauri::async_runtime::spawn(async {
+        if let Err(err) = restore().await {
+            log::warn!(target: "app", "failed to restore the proxy selections: {err:?}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn selection(group: &str, proxy: &str) -> (String, String) {
+        (group.to_string(), proxy.to_string())
+    }
+
+    #[test]
+    fn test_selections_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::try_new(&dir.path().join("storage.db")).unwrap();
+        let selections = ProxySelections::try_new(storage).unwrap();
+        selections.set("a", "Proxy", "HK").unwrap();
+        selections.set("a", "Auto", "JP").unwrap();
+        selections.set("a", "Proxy", "US").unwrap();
+        selections.set("ab", "Proxy", "SG").unwrap();
+        selections.set("b", "Proxy", "TW").unwrap();
+        assert_eq!(
+            selections.get_all("a").unwrap(),
+            vec![selection("Auto", "JP"), selection("Proxy", "US")]
+        );
+        assert!(selections.get_all("c").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan() {
+        let item = |name: &str, kind: &str, now: &str, all: &[&str]| {
+            (
+                name.to_string(),
+                ProxyItem {
+                    name: name.to_string(),
+                    r#type: kind.to_string(),
+                    now: Some(now.to_string()),
+                    all: Some(all.iter().map(|name| name.to_string()).collect()),
+                    ..Default::default()
+                },
+            )
+        };
+        let proxies = IndexMap::from([
+            item("Proxy", "Selector", "HK", &["HK", "JP"]),
+            item("Stream", "Selector", "HK", &["HK", "JP"]),
+            item("Auto", "URLTest", "HK", &["HK", "JP"]),
+        ]);
+        let selections = vec![
+            selection("Proxy", "JP"),
+            // selected already
+            selection("Stream", "HK"),
+            // not a selector
+            selection("Auto", "JP"),
+            // the proxy is removed
+            selection("Stream", "US"),
+            // the group is removed
+            selection("Removed", "JP"),
+        ];
+        assert_eq!(plan(selections, &proxies), vec![selection("Proxy", "JP")]);
+    }
+}
@@ -8,8 +8,9 @@ use anyhow::Context;
 use atomic_enum::atomic_enum;
 use backon::Retryable;
 use futures::stream::StreamExt;
+use indexmap::IndexMap;
 use parking_lot::Mutex;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use specta::Type;
 use tokio::{sync::mpsc::Receiver, task::JoinHandle};
 use tokio_tungstenite::{
@@ -20,7 +21,7 @@ use tokio_tungstenite::{
 use crate::log_err;
 
 #[tracing::instrument]
-async fn connect_clash_server<T: serde::de::DeserializeOwned + Send + Sync + 'static>(
+async fn connect_clash_server<T: DeserializeOwned + Send + Sync + 'static>(
     endpoint: Request,
 ) -> anyhow::Result<Receiver<T>> {
     // 添加连接超时：30 秒
@@ -67,10 +68,141 @@ async fn connect_clash_server<T: serde::de::DeserializeOwned + Send + Sync + 'st
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct ClashConnectionsMessage {
+pub struct ClashConnectionsMessage {
     download_total: u64,
     upload_total: u64,
-    // other fields are omitted
+    /// it is `null` if there are no connections
+    #[serde(default)]
+    connections: Option<Vec<ClashConnection>>,
+}
+
+/// A connection of the `/connections` payload of mihomo
+#[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClashConnection {
+    pub id: String,
+    pub metadata: ClashConnectionMetadata,
+    /// the uploaded bytes of the connection
+    pub upload: u64,
+    /// the downloaded bytes of the connection
+    pub download: u64,
+    /// the RFC 3339 time when the connection is opened
+    pub start: String,
+    /// the proxies the connection goes through, from the outbound proxy to the matched group
+    #[serde(default)]
+    pub chains: Vec<String>,
+    #[serde(default)]
+    pub rule: String,
+    #[serde(default)]
+    pub rule_payload: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClashConnectionMetadata {
+    /// `tcp` or `udp`
+    pub network: String,
+    /// the inbound type, e.g. `HTTP`, `Socks5`, `Tun`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(rename = "sourceIP")]
+    pub source_ip: String,
+    #[serde(rename = "destinationIP")]
+    pub destination_ip: String,
+    /// the ports are strings in the payload
+    pub source_port: String,
+    pub destination_port: String,
+    #[serde(rename = "inboundIP")]
+    pub inbound_ip: String,
+    pub inbound_port: String,
+    pub inbound_name: String,
+    pub inbound_user: String,
+    pub host: String,
+    pub dns_mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    pub process: String,
+    pub process_path: String,
+    pub special_proxy: String,
+    pub special_rules: String,
+    pub remote_destination: String,
+    pub sniff_host: String,
+}
+
+/// A connection in the connection table, with the speed computed between the frames
+#[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedConnection {
+    #[serde(flatten)]
+    pub connection: ClashConnection,
+    /// bytes per frame, the core pushes a frame per second
+    pub download_speed: u64,
+    pub upload_speed: u64,
+}
+
+/// The changes of the connection table between two frames
+#[derive(Debug, Clone, Default, PartialEq, Eq, Type, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClashConnectionsDiff {
+    pub opened: Vec<TrackedConnection>,
+    /// the connections whose traffic changed
+    pub updated: Vec<TrackedConnection>,
+    /// the ids of the closed connections
+    pub closed: Vec<String>,
+}
+
+impl ClashConnectionsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.opened.is_empty() && self.updated.is_empty() && self.closed.is_empty()
+    }
+}
+
+/// The alive connections, keyed by the id, in the order they are opened
+#[derive(Debug, Default)]
+struct ConnectionTable(IndexMap<String, TrackedConnection>);
+
+impl ConnectionTable {
+    /// replace the table with the connections of a frame, and return the changes
+    fn apply(&mut self, connections: Vec<ClashConnection>) -> ClashConnectionsDiff {
+        let mut diff = ClashConnectionsDiff::default();
+        let mut table = IndexMap::with_capacity(connections.len());
+        for connection in connections {
+            let tracked = match self.0.swap_remove(&connection.id) {
+                Some(previous) => {
+                    let tracked = TrackedConnection {
+                        download_speed: connection
+                            .download
+                            .saturating_sub(previous.connection.download),
+                        upload_speed: connection.upload.saturating_sub(previous.connection.upload),
+                        connection,
+                    };
+                    if tracked != previous {
+                        diff.updated.push(tracked.clone());
+                    }
+                    tracked
+                }
+                None => {
+                    let tracked = TrackedConnection {
+                        connection,
+                        download_speed: 0,
+                        upload_speed: 0,
+                    };
+                    diff.opened.push(tracked.clone());
+                    tracked
+                }
+            };
+            table.insert(tracked.connection.id.clone(), tracked);
+        }
+        diff.closed = std::mem::replace(&mut self.0, table).into_keys().collect();
+        diff
+    }
+
+    fn clear(&mut self) -> ClashConnectionsDiff {
+        ClashConnectionsDiff {
+            closed: std::mem::take(&mut self.0).into_keys().collect(),
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Copy, Type, Serialize, Deserialize)]
@@ -88,6 +220,17 @@ pub struct ClashConnectionsInfo {
 pub enum ClashConnectionsConnectorEvent {
     StateChanged(ClashConnectionsConnectorState),
     Update(ClashConnectionsInfo),
+    /// the connections opened, updated and closed since the last frame
+    ConnectionsChanged(ClashConnectionsDiff),
+}
+
+/// The event of the streams which only forward the messages, e.g. `/traffic`, `/memory` and `/logs`
+#[derive(Debug, Clone, Type, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind", content = "data")]
+pub enum ClashWsEvent<T> {
+    StateChanged(ClashConnectionsConnectorState),
+    Update(T),
 }
 
 #[derive(PartialEq, Eq, Type, Serialize, Deserialize)]
@@ -99,40 +242,236 @@ pub enum ClashConnectionsConnectorState {
     Connected,
 }
 
-pub struct ClashConnectionsConnectorInner {
+/// A websocket endpoint of the core, and how its messages are turned into the events
+pub trait ClashWsStream: Send + Sync + 'static {
+    type Message: DeserializeOwned + Send + Sync + 'static;
+    type Event: Clone + Send + Sync + 'static;
+
+    /// the name used in the logs
+    const NAME: &'static str;
+
+    /// the path with the query of the endpoint, e.g. `/logs?level=info`
+    fn path(&self) -> String;
+
+    fn state_changed(state: ClashConnectionsConnectorState) -> Self::Event;
+
+    /// turn a message into the events to broadcast
+    fn handle(&self, msg: Self::Message) -> Vec<Self::Event>;
+
+    /// drop the state of the stopped connection, return the events to broadcast
+    fn reset(&self) -> Vec<Self::Event> {
+        Vec::new()
+    }
+}
+
+/// `/connections`, the totals and the connection table
+#[derive(Default)]
+pub struct ClashConnectionsStream {
+    info: Mutex<ClashConnectionsInfo>,
+    connections: Mutex<ConnectionTable>,
+}
+
+impl ClashWsStream for ClashConnectionsStream {
+    type Message = ClashConnectionsMessage;
+    type Event = ClashConnectionsConnectorEvent;
+
+    const NAME: &'static str = "connections";
+
+    fn path(&self) -> String {
+        "/connections".to_string()
+    }
+
+    fn state_changed(state: ClashConnectionsConnectorState) -> Self::Event {
+        ClashConnectionsConnectorEvent::StateChanged(state)
+    }
+
+    fn handle(&self, msg: Self::Message) -> Vec<Self::Event> {
+        let mut events = Vec::with_capacity(2);
+        let diff = self
+            .connections
+            .lock()
+            .apply(msg.connections.unwrap_or_default());
+        if !diff.is_empty() {
+            events.push(ClashConnectionsConnectorEvent::ConnectionsChanged(diff));
+        }
+
+        let mut info = self.info.lock();
+        let previous_download_total =
+            std::mem::replace(&mut info.download_total, msg.download_total);
+        let previous_upload_total = std::mem::replace(&mut info.upload_total, msg.upload_total);
+        info.download_speed = msg
+            .download_total
+            .checked_sub(previous_download_total)
+            .unwrap_or_default();
+        info.upload_speed = msg
+            .upload_total
+            .checked_sub(previous_upload_total)
+            .unwrap_or_default();
+        events.push(ClashConnectionsConnectorEvent::Update(*info));
+        events
+    }
+
+    fn reset(&self) -> Vec<Self::Event> {
+        // the connections are unknown while disconnected
+        let diff = self.connections.lock().clear();
+        if diff.is_empty() {
+            Vec::new()
+        } else {
+            vec![ClashConnectionsConnectorEvent::ConnectionsChanged(diff)]
+        }
+    }
+}
+
+/// The speed pushed by `/traffic` every second
+#[derive(Debug, Clone, Copy, Default, Type, Serialize, Deserialize)]
+pub struct ClashTraffic {
+    /// bytes per second
+    pub up: u64,
+    pub down: u64,
+}
+
+/// `/traffic`
+#[derive(Default)]
+pub struct ClashTrafficStream;
+
+impl ClashWsStream for ClashTrafficStream {
+    type Message = ClashTraffic;
+    type Event = ClashWsEvent<ClashTraffic>;
+
+    const NAME: &'static str = "traffic";
+
+    fn path(&self) -> String {
+        "/traffic".to_string()
+    }
+
+    fn state_changed(state: ClashConnectionsConnectorState) -> Self::Event {
+        ClashWsEvent::StateChanged(state)
+    }
+
+    fn handle(&self, msg: Self::Message) -> Vec<Self::Event> {
+        vec![ClashWsEvent::Update(msg)]
+    }
+}
+
+/// The memory usage pushed by `/memory` every second, mihomo only
+#[derive(Debug, Clone, Copy, Default, Type, Serialize, Deserialize)]
+pub struct ClashMemory {
+    /// bytes in use
+    pub inuse: u64,
+    /// the memory limit of the os, `0` if there is no limit
+    pub oslimit: u64,
+}
+
+/// `/memory`
+#[derive(Default)]
+pub struct ClashMemoryStream;
+
+impl ClashWsStream for ClashMemoryStream {
+    type Message = ClashMemory;
+    type Event = ClashWsEvent<ClashMemory>;
+
+    const NAME: &'static str = "memory";
+
+    fn path(&self) -> String {
+        "/memory".to_string()
+    }
+
+    fn state_changed(state: ClashConnectionsConnectorState) -> Self::Event {
+        ClashWsEvent::StateChanged(state)
+    }
+
+    fn handle(&self, msg: Self::Message) -> Vec<Self::Event> {
+        vec![ClashWsEvent::Update(msg)]
+    }
+}
+
+/// A log line pushed by `/logs`
+#[derive(Debug, Clone, Type, Serialize, Deserialize)]
+pub struct ClashLog {
+    /// `debug`, `info`, `warning` or `error`
+    #[serde(rename = "type")]
+    pub level: String,
+    pub payload: String,
+}
+
+/// `/logs`, the logs at or above the level
+pub struct ClashLogsStream {
+    level: String,
+}
+
+impl ClashLogsStream {
+    pub fn new(level: impl Into<String>) -> Self {
+        Self {
+            level: level.into(),
+        }
+    }
+}
+
+impl ClashWsStream for ClashLogsStream {
+    type Message = ClashLog;
+    type Event = ClashWsEvent<ClashLog>;
+
+    const NAME: &'static str = "logs";
+
+    fn path(&self) -> String {
+        format!("/logs?level={}", self.level)
+    }
+
+    fn state_changed(state: ClashConnectionsConnectorState) -> Self::Event {
+        ClashWsEvent::StateChanged(state)
+    }
+
+    fn handle(&self, msg: Self::Message) -> Vec<Self::Event> {
+        vec![ClashWsEvent::Update(msg)]
+    }
+}
+
+pub struct ClashWsConnectorInner<S: ClashWsStream> {
     state: AtomicClashConnectionsConnectorState,
     connection_handler: Mutex<Option<JoinHandle<()>>>,
-    broadcast_tx: tokio::sync::broadcast::Sender<ClashConnectionsConnectorEvent>,
-    info: Mutex<ClashConnectionsInfo>,
+    broadcast_tx: tokio::sync::broadcast::Sender<S::Event>,
+    stream: S,
 }
 
-// TODO:
-#[derive(Clone)]
-pub struct ClashConnectionsConnector {
-    inner: Arc<ClashConnectionsConnectorInner>,
+/// Keep a websocket connection to the core, reconnect with backoff when it is closed
+pub struct ClashWsConnector<S: ClashWsStream> {
+    inner: Arc<ClashWsConnectorInner<S>>,
 }
 
-impl Deref for ClashConnectionsConnector {
-    type Target = ClashConnectionsConnectorInner;
+pub type ClashConnectionsConnector = ClashWsConnector<ClashConnectionsStream>;
+pub type ClashTrafficConnector = ClashWsConnector<ClashTrafficStream>;
+pub type ClashMemoryConnector = ClashWsConnector<ClashMemoryStream>;
+pub type ClashLogsConnector = ClashWsConnector<ClashLogsStream>;
+
+impl<S: ClashWsStream> Clone for ClashWsConnector<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<S: ClashWsStream> Deref for ClashWsConnector<S> {
+    type Target = ClashWsConnectorInner<S>;
 
     fn deref(&self) -> &Self::Target {
         &self.inner
     }
 }
 
-impl ClashConnectionsConnector {
-    pub fn new() -> Self {
+impl<S: ClashWsStream> ClashWsConnector<S> {
+    pub fn new(stream: S) -> Self {
         Self {
-            inner: Arc::new(ClashConnectionsConnectorInner::new()),
+            inner: Arc::new(ClashWsConnectorInner::new(stream)),
         }
     }
 
-    pub fn endpoint() -> anyhow::Result<Request> {
+    pub fn endpoint(&self) -> anyhow::Result<Request> {
         let (server, secret) = {
             let info = crate::Config::clash().data().get_client_info();
             (info.server, info.secret)
         };
-        let url = format!("ws://{server}/connections");
+        let url = format!("ws://{server}{}", self.stream.path());
         let mut request = url
             .into_client_request()
             .context("failed to create client request")?;
@@ -150,12 +489,12 @@ impl ClashConnectionsConnector {
     #[allow(clippy::manual_async_fn)]
     // FIXME: move to async fn while rust new solver got merged
     // ref: https://github.com/rust-lang/rust/issues/123072
-    fn start_internal(&self) -> impl Future<Output = anyhow::Result<()>> + Send + use<'_> {
+    fn start_internal(&self) -> impl Future<Output = anyhow::Result<()>> + Send + use<'_, S> {
         async {
             self.dispatch_state_changed(ClashConnectionsConnectorState::Connecting);
-            let endpoint = Self::endpoint().context("failed to create endpoint")?;
-            log::debug!("connecting to clash connections ws server: {endpoint:?}");
-            let mut rx = connect_clash_server::<ClashConnectionsMessage>(endpoint).await?;
+            let endpoint = self.endpoint().context("failed to create endpoint")?;
+            log::debug!("connecting to clash {} ws server: {endpoint:?}", S::NAME);
+            let mut rx = connect_clash_server::<S::Message>(endpoint).await?;
             self.dispatch_state_changed(ClashConnectionsConnectorState::Connected);
             let this = self.clone();
             let mut connection_handler = self.connection_handler.lock();
@@ -166,7 +505,10 @@ impl ClashConnectionsConnector {
                             this.update(msg);
                         }
                         None => {
-                            tracing::info!("clash ws server closed connection, trying to restart");
+                            tracing::info!(
+                                "clash {} ws server closed connection, trying to restart",
+                                S::NAME
+                            );
                             // The connection was closed, let's restart the connector
                             this.dispatch_state_changed(
                                 ClashConnectionsConnectorState::Disconnected,
@@ -178,7 +520,10 @@ impl ClashConnectionsConnector {
                                         .retry(backon::ExponentialBuilder::default())
                                         .sleep(tokio::time::sleep)
                                         .await
-                                        .context("failed to restart clash connections")
+                                        .with_context(|| format!(
+                                            "failed to restart clash {}",
+                                            S::NAME
+                                        ))
                                 );
                             });
                             break;
@@ -203,15 +548,15 @@ impl ClashConnectionsConnector {
     }
 }
 
-impl ClashConnectionsConnectorInner {
-    pub fn new() -> Self {
+impl<S: ClashWsStream> ClashWsConnectorInner<S> {
+    pub fn new(stream: S) -> Self {
         Self {
             state: AtomicClashConnectionsConnectorState::new(
                 ClashConnectionsConnectorState::Disconnected,
             ),
             connection_handler: Mutex::new(None),
-            broadcast_tx: tokio::sync::broadcast::channel(5).0,
-            info: Mutex::new(ClashConnectionsInfo::default()),
+            broadcast_tx: tokio::sync::broadcast::channel(16).0,
+            stream,
         }
     }
 
@@ -219,53 +564,50 @@ impl ClashConnectionsConnectorInner {
         self.state.load(Ordering::Acquire)
     }
 
-    fn dispatch_state_changed(&self, state: ClashConnectionsConnectorState) {
-        self.state.store(state, Ordering::Release);
+    fn broadcast(&self, event: S::Event) {
         // SAFETY: the failures only there no active receivers,
         // so that the message will be dropped directly
-        let _ = self
-            .broadcast_tx
-            .send(ClashConnectionsConnectorEvent::StateChanged(state));
+        let _ = self.broadcast_tx.send(event);
     }
 
-    /// Subscribe to the ClashConnectionsConnectorEvent
-    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<ClashConnectionsConnectorEvent> {
-        self.broadcast_tx.subscribe()
+    fn dispatch_state_changed(&self, state: ClashConnectionsConnectorState) {
+        self.state.store(state, Ordering::Release);
+        self.broadcast(S::state_changed(state));
     }
 
-    fn update(&self, msg: ClashConnectionsMessage) {
-        let mut info = self.info.lock();
-        let previous_download_total =
-            std::mem::replace(&mut info.download_total, msg.download_total);
-        let previous_upload_total = std::mem::replace(&mut info.upload_total, msg.upload_total);
-        info.download_speed = msg
-            .download_total
-            .checked_sub(previous_download_total)
-            .unwrap_or_default();
-        info.upload_speed = msg
-            .upload_total
-            .checked_sub(previous_upload_total)
-            .unwrap_or_default();
+    /// Subscribe to the events of the stream
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<S::Event> {
+        self.broadcast_tx.subscribe()
+    }
 
-        // SAFETY: the failures only there no active receivers,
-        // so that the message will be dropped directly
-        let _ = self
-            .broadcast_tx
-            .send(ClashConnectionsConnectorEvent::Update(*info));
+    fn update(&self, msg: S::Message) {
+        for event in self.stream.handle(msg) {
+            self.broadcast(event);
+        }
     }
 
     pub async fn stop(&self) {
-        log::info!("stopping clash connections ws server");
+        log::info!("stopping clash {} ws server", S::NAME);
         let handle = self.connection_handler.lock().take();
         if let Some(handle) = handle {
             handle.abort();
             let _ = handle.await;
         }
+        for event in self.stream.reset() {
+            self.broadcast(event);
+        }
         self.dispatch_state_changed(ClashConnectionsConnectorState::Disconnected);
     }
 }
 
-impl Drop for ClashConnectionsConnectorInner {
+impl ClashWsConnectorInner<ClashConnectionsStream> {
+    /// the alive connections of the latest frame
+    pub fn connections(&self) -> Vec<TrackedConnection> {
+        self.stream.connections.lock().0.values().cloned().collect()
+    }
+}
+
+impl<S: ClashWsStream> Drop for ClashWsConnectorInner<S> {
     fn drop(&mut self) {
         let cleanup = async move {
             self.stop().await;
@@ -280,3 +622,112 @@ impl Drop for ClashConnectionsConnectorInner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn connection(id: &str, upload: u64, download: u64) -> ClashConnection {
+        ClashConnection {
+            id: id.to_string(),
+            metadata: ClashConnectionMetadata::default(),
+            upload,
+            download,
+            start: "2024-01-01T00:00:00Z".to_string(),
+            chains: vec!["DIRECT".to_string()],
+            rule: "MATCH".to_string(),
+            rule_payload: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_connections_message() {
+        let msg = r#"{
+            "downloadTotal": 2048,
+            "uploadTotal": 1024,
+            "connections": [{
+                "id": "5b1b1a2e",
+                "metadata": {
+                    "network": "tcp",
+                    "type": "HTTP",
+                    "sourceIP": "127.0.0.1",
+                    "destinationIP": "1.1.1.1",
+                    "sourcePort": "53211",
+                    "destinationPort": "443",
+                    "host": "one.one.one.one",
+                    "dnsMode": "normal",
+                    "process": "curl",
+                    "processPath": "/usr/bin/curl"
+                },
+                "upload": 512,
+                "download": 1024,
+                "start": "2024-01-01T00:00:00.000000000+08:00",
+                "chains": ["HK", "Proxy"],
+                "rule": "DomainSuffix",
+                "rulePayload": "one.one"
+            }],
+            "memory": 1234
+        }"#;
+        let msg: ClashConnectionsMessage = serde_json::from_str(msg).unwrap();
+        let connections = msg.connections.unwrap();
+        assert_eq!(connections[0].metadata.host, "one.one.one.one");
+        assert_eq!(connections[0].metadata.kind, "HTTP");
+        assert_eq!(connections[0].chains, vec!["HK", "Proxy"]);
+
+        let msg: ClashConnectionsMessage =
+            serde_json::from_str(r#"{"downloadTotal":0,"uploadTotal":0,"connections":null}"#)
+                .unwrap();
+        assert!(msg.connections.is_none());
+    }
+
+    #[test]
+    fn test_forwarding_streams() {
+        let traffic = serde_json::from_str(r#"{"up":128,"down":4096}"#).unwrap();
+        let events = ClashTrafficStream.handle(traffic);
+        assert!(matches!(
+            events.as_slice(),
+            [ClashWsEvent::Update(ClashTraffic {
+                up: 128,
+                down: 4096
+            })]
+        ));
+
+        let stream = ClashLogsStream::new("warning");
+        assert_eq!(stream.path(), "/logs?level=warning");
+        let log = serde_json::from_str(r#"{"type":"warning","payload":"dial failed"}"#).unwrap();
+        let events = stream.handle(log);
+        let [ClashWsEvent::Update(log)] = events.as_slice() else {
+            panic!("unexpected events: {events:?}");
+        };
+        assert_eq!(log.level, "warning");
+        assert_eq!(log.payload, "dial failed");
+    }
+
+    #[test]
+    fn test_connection_table_diff() {
+        let mut table = ConnectionTable::default();
+        let diff = table.apply(vec![connection("a", 10, 20), connection("b", 0, 0)]);
+        assert_eq!(diff.opened.len(), 2);
+        assert!(diff.updated.is_empty() && diff.closed.is_empty());
+
+        let diff = table.apply(vec![
+            connection("a", 15, 120),
+            connection("b", 0, 0),
+            connection("c", 0, 0),
+        ]);
+        assert_eq!(diff.opened[0].connection.id, "c");
+        assert_eq!(diff.updated.len(), 1);
+        assert_eq!(diff.updated[0].upload_speed, 5);
+        assert_eq!(diff.updated[0].download_speed, 100);
+
+        // the speed drops to zero, which is an update too
+        let diff = table.apply(vec![connection("a", 15, 120), connection("c", 0, 0)]);
+        assert_eq!(diff.closed, vec!["b"]);
+        assert_eq!(diff.updated[0].download_speed, 0);
+
+        let diff = table.apply(vec![connection("a", 15, 120), connection("c", 0, 0)]);
+        assert!(diff.is_empty());
+        assert_eq!(table.clear().closed, vec!["a", "c"]);
+    }
+}
@@ -1,21 +1,19 @@
-use crate::{config::Config, core::clash::api};
+use crate::{
+    config::Config,
+    core::clash::api::{self, ClashApi, ClashController},
+};
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
-use specta::Type;
-
-#[derive(Debug, Clone, Deserialize, Serialize, Type)]
-#[allow(dead_code)]
-pub struct ConnectionInfo {
-    pub id: String,
-    pub chains: Vec<String>,
-}
+use futures::StreamExt;
+
+/// the connections closed at the same time at most
+const MAX_CONCURRENT_CLOSE: usize = 16;
 
 /// Connection interruption service that handles closing connections based on configuration settings
 pub struct ConnectionInterruptionService;
 
 impl ConnectionInterruptionService {
-    /// Interrupt connections when proxy changes
-    pub async fn on_proxy_change() -> Result<()> {
+    /// Interrupt connections when the proxy of the group changes, `previous` is the proxy selected before
+    pub async fn on_proxy_change(group: &str, previous: Option<&str>) -> Result<()> {
         let config = Config::verge().data().clone();
         let break_when = config.break_when_proxy_change.unwrap_or_default();
 
@@ -25,10 +23,7 @@ impl ConnectionInterruptionService {
                 Ok(())
             }
             crate::config::nyanpasu::BreakWhenProxyChange::Chain => {
-                // TODO: Implement chain-based connection interruption
-                // This would require tracking which connections use which proxy chains
-                // For now, we'll fall back to closing all connections
-                api::delete_connections(None).await
+                Self::interrupt_by_chain(group, previous).await.map(|_| ())
             }
             crate::config::nyanpasu::BreakWhenProxyChange::All => {
                 api::delete_connections(None).await
@@ -68,15 +63,123 @@ impl ConnectionInterruptionService {
         api::delete_connections(None).await
     }
 
-    /// Interrupt connections based on proxy chain (not yet implemented)
-    #[allow(dead_code)]
-    pub async fn interrupt_by_chain(_chain: &[String]) -> Result<()> {
-        // TODO: Implement chain-based connection interruption
-        // This would require:
-        // 1. Getting the current connections from the Clash API
-        // 2. Filtering connections that use the specified proxy chain
-        // 3. Closing only those connections
-        // For now, we'll close all connections as a fallback
-        api::delete_connections(None).await
+    /// Interrupt the connections whose chains include the group or the proxy it selected before,
+    /// return the number of the closed connections
+    pub async fn interrupt_by_chain(group: &str, previous: Option<&str>) -> Result<usize> {
+        let controller = ClashController::current()?;
+        interrupt_by_chain_with(&controller, group, previous).await
+    }
+}
+
+async fn interrupt_by_chain_with(
+    controller: &impl ClashApi,
+    group: &str,
+    previous: Option<&str>,
+) -> Result<usize> {
+    let ids = controller
+        .get_connections()
+        .await?
+        .connections
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|connection| {
+            connection
+                .chains
+                .iter()
+                .any(|proxy| proxy == group || previous == Some(proxy.as_str()))
+        })
+        .map(|connection| connection.id)
+        .collect::<Vec<_>>();
+
+    let failures = futures::stream::iter(ids.iter())
+        .map(|id| controller.delete_connections(Some(id)))
+        .buffer_unordered(MAX_CONCURRENT_CLOSE)
+        .filter_map(|result| async move { result.err() })
+        .collect::<Vec<_>>()
+        .await;
+    if let Some(err) = failures.first() {
+        tracing::warn!(
+            "failed to close {} of {} connections of `{group}`: {err:?}",
+            failures.len(),
+            ids.len()
+        );
+    }
+    Ok(ids.len() - failures.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json, Router,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        routing::{delete, get},
+    };
+    use parking_lot::Mutex;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+
+    fn connection(id: &str, chains: &[&str]) -> Value {
+        json!({
+            "id": id,
+            "metadata": { "network": "tcp", "host": "example.com" },
+            "upload": 0,
+            "download": 0,
+            "start": "2024-01-01T00:00:00Z",
+            "chains": chains,
+            "rule": "Match",
+            "rulePayload": "",
+        })
+    }
+
+    fn snapshot() -> Value {
+        json!({
+            "downloadTotal": 0,
+            "uploadTotal": 0,
+            "connections": [
+                connection("a", &["HK", "Auto"]),
+                connection("b", &["JP", "Proxy"]),
+                connection("c", &["DIRECT"]),
+                connection("d", &["US", "Auto", "Proxy"]),
+            ],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_by_chain() {
+        let closed = Arc::new(Mutex::new(Vec::<String>::new()));
+        let app = Router::new()
+            .route("/connections", get(|| async { Json(snapshot()) }))
+            .route(
+                "/connections/{id}",
+                delete(
+                    |State(closed): State<Arc<Mutex<Vec<String>>>>,
+                     headers: HeaderMap,
+                     Path(id): Path<String>| async move {
+                        if headers
+                            .get("Authorization")
+                            .is_none_or(|value| value != "Bearer secret")
+                        {
+                            return StatusCode::UNAUTHORIZED;
+                        }
+                        closed.lock().push(id);
+                        StatusCode::NO_CONTENT
+                    },
+                ),
+            )
+            .with_state(closed.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let controller = ClashController::new(format!("http://{addr}"), Some("secret")).unwrap();
+        let count = interrupt_by_chain_with(&controller, "Proxy", Some("HK"))
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        let mut closed = closed.lock().clone();
+        closed.sort();
+        assert_eq!(closed, vec!["a", "b", "d"]);
     }
 }
@@ -1,11 +1,15 @@
 use super::tray::Tray;
-use crate::log_err;
+use crate::{
+    config::{SubscriptionAlertKind, SubscriptionInfo, profile::item_type::ProfileUid},
+    log_err,
+};
 use anyhow::{Result, bail};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter, Manager, WebviewWindow, Wry};
+use tauri_plugin_notification::NotificationExt;
 #[derive(Debug, Default, Clone)]
 pub struct Handle {
     pub app_handle: Arc<Mutex<Option<AppHandle>>>,
@@ -24,6 +28,19 @@ pub enum StateChanged {
 #[serde(rename_all = "snake_case")]
 pub enum Message {
     SetConfig(Result<(), String>),
+    SubscriptionAlert(SubscriptionAlert),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionAlert {
+    pub uid: ProfileUid,
+    pub name: String,
+    pub kind: SubscriptionAlertKind,
+    pub info: SubscriptionInfo,
+    /// the fallback profile which could be activated instead
+    pub fallback: Option<ProfileUid>,
+    /// whether the fallback profile was activated automatically
+    pub switched: bool,
 }
 
 const STATE_CHANGED_URI: &str = "nyanpasu://mutation";
@@ -61,7 +78,6 @@ impl Handle {
         }
     }
 
-    #[allow(unused)]
     pub fn refresh_profiles() {
         if let Some(window) = Self::global().get_window() {
             log_err!(window.emit(STATE_CHANGED_URI, StateChanged::Profiles));
@@ -80,6 +96,21 @@ impl Handle {
         }
     }
 
+    /// show a desktop notification, used when the main window may be closed
+    pub fn notify_system(title: &str, body: &str) {
+        let app_handle = Self::global().app_handle.lock();
+        if let Some(app_handle) = app_handle.as_ref() {
+            log_err!(
+                app_handle
+                    .notification()
+                    .builder()
+                    .title(title)
+                    .body(body)
+                    .show()
+            );
+        }
+    }
+
     pub fn update_systray() -> Result<()> {
         // let app_handle = Self::global().app_handle.lock();
         // if app_handle.is_none() {
@@ -5,6 +5,7 @@ use std::{borrow::Cow, collections::HashMap};
 
 mod unit_160;
 mod unit_200;
+mod unit_300;
 
 pub static UNITS: Lazy<HashMap<&'static Version, Unit<'static, DynMigration>>> = Lazy::new(|| {
     let mut units: HashMap<&'static Version, Unit<'static, DynMigration>> = HashMap::new();
@@ -12,6 +13,8 @@ pub static UNITS: Lazy<HashMap<&'static Version, Unit<'static, DynMigration>>> =
     units.insert(unit.version(), unit);
     let unit = Unit::Batch(Cow::Borrowed(&unit_200::UNITS));
     units.insert(unit.version(), unit);
+    let unit = Unit::Batch(Cow::Borrowed(&unit_300::UNITS));
+    units.insert(unit.version(), unit);
     units
 });
 
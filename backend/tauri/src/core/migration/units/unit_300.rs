@@ -0,0 +1,237 @@
+use std::borrow::Cow;
+
+use once_cell::sync::Lazy;
+use semver::Version;
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    core::migration::{DynMigration, Migration, MigrationExt},
+    utils::{dirs, help},
+};
+
+pub static UNITS: Lazy<Vec<DynMigration>> = Lazy::new(|| vec![MigrateProfileGroups.boxed()]);
+
+pub static VERSION: Lazy<semver::Version> = Lazy::new(|| semver::Version::parse("3.0.0").unwrap());
+
+#[derive(Debug, Clone, Copy)]
+/// 为 profiles.yaml 添加分组
+/// - 根据各项的 `group` 生成顶层的 `groups` 排序列表
+/// - 将单个字符串形式的 `tags` 展开为列表，并去除空白与重复项
+pub struct MigrateProfileGroups;
+
+impl Migration<'_> for MigrateProfileGroups {
+    fn version(&self) -> &'static Version {
+        &VERSION
+    }
+
+    fn name(&self) -> Cow<'static, str> {
+        Cow::Borrowed("MigrateProfileGroups")
+    }
+
+    fn migrate(&self) -> std::io::Result<()> {
+        let profiles_path = dirs::profiles_path().map_err(std::io::Error::other)?;
+        if !profiles_path.exists() {
+            eprintln!("profiles file not found, skipping migration");
+            return Ok(());
+        }
+        let profiles = std::fs::read_to_string(&profiles_path)?;
+        let profiles: Mapping = serde_yaml::from_str(&profiles)
+            .map_err(|e| std::io::Error::other(format!("failed to parse profiles: {e}")))?;
+        let profiles = migrate_profile_data(profiles);
+        help::save_yaml(
+            &profiles_path,
+            &profiles,
+            Some("# Profiles Config for Clash Nyanpasu"),
+        )
+        .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+
+    fn discard(&self) -> std::io::Result<()> {
+        let profiles_path = dirs::profiles_path().map_err(std::io::Error::other)?;
+        if !profiles_path.exists() {
+            eprintln!("profiles file not found, skipping discard");
+            return Ok(());
+        }
+        let profiles = std::fs::read_to_string(&profiles_path)?;
+        let profiles: Mapping = serde_yaml::from_str(&profiles)
+            .map_err(|e| std::io::Error::other(format!("failed to parse profiles: {e}")))?;
+        let profiles = discard_profile_data(profiles);
+        help::save_yaml(
+            &profiles_path,
+            &profiles,
+            Some("# Profiles Config for Clash Nyanpasu"),
+        )
+        .map_err(std::io::Error::other)?;
+        Ok(())
+    }
+}
+
+fn migrate_profile_data(mut mapping: Mapping) -> Mapping {
+    let mut groups = mapping
+        .get("groups")
+        .and_then(|groups| groups.as_sequence())
+        .map(|groups| {
+            groups
+                .iter()
+                .filter_map(|group| group.as_str().map(str::to_string))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if let Some(items) = mapping.get_mut("items")
+        && let Some(items) = items.as_sequence_mut()
+    {
+        for item in items.iter_mut().filter_map(|item| item.as_mapping_mut()) {
+            if let Some(group) = item.get("group").and_then(|group| group.as_str())
+                && !groups.iter().any(|g| g == group)
+            {
+                groups.push(group.to_string());
+            }
+            let tags = match item.get("tags") {
+                Some(Value::String(tag)) => vec![tag.clone()],
+                Some(Value::Sequence(tags)) => tags
+                    .iter()
+                    .filter_map(|tag| tag.as_str().map(str::to_string))
+                    .collect(),
+                _ => continue,
+            };
+            let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+            for tag in tags.iter().map(|tag| tag.trim()) {
+                if !tag.is_empty() && !normalized.iter().any(|t| t == tag) {
+                    normalized.push(tag.to_string());
+                }
+            }
+            if normalized.is_empty() {
+                item.remove("tags");
+            } else {
+                item.insert(
+                    "tags".into(),
+                    Value::Sequence(normalized.into_iter().map(Value::from).collect()),
+                );
+            }
+        }
+    }
+
+    mapping.insert(
+        "groups".into(),
+        Value::Sequence(groups.into_iter().map(Value::from).collect()),
+    );
+    mapping
+}
+
+fn discard_profile_data(mut mapping: Mapping) -> Mapping {
+    mapping.remove("groups");
+    if let Some(items) = mapping.get_mut("items")
+        && let Some(items) = items.as_sequence_mut()
+    {
+        for item in items.iter_mut().filter_map(|item| item.as_mapping_mut()) {
+            item.remove("group");
+            item.remove("tags");
+        }
+    }
+    mapping
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{ProfileMetaGetter, Profiles};
+
+    use super::*;
+    use pretty_assertions::assert_str_eq;
+
+    const ORIGINAL_SAMPLE: &str = r#"current:
+- rIWXPHuafvEM
+chain: []
+valid:
+- dns
+items:
+- uid: rIWXPHuafvEM
+  type: remote
+  name: 🌸云
+  file: rIWXPHuafvEM.yaml
+  desc: null
+  updated: 1758110672
+  url: https://example.com
+  option:
+    with_proxy: false
+    self_proxy: true
+    update_interval: 1440
+  chain: []
+  group: work
+  tags: ' airport '
+- uid: lkvV5JXfzO34
+  type: local
+  name: New Profile
+  file: lkvV5JXfzO34.yaml
+  desc: ''
+  updated: 1725587682
+  chain: []
+  group: home
+  tags:
+  - test
+  - test
+  - ''
+"#;
+
+    const MIGRATED_SAMPLE: &str = r#"current:
+- rIWXPHuafvEM
+chain: []
+valid:
+- dns
+items:
+- uid: rIWXPHuafvEM
+  type: remote
+  name: 🌸云
+  file: rIWXPHuafvEM.yaml
+  desc: null
+  updated: 1758110672
+  url: https://example.com
+  option:
+    with_proxy: false
+    self_proxy: true
+    update_interval: 1440
+  chain: []
+  group: work
+  tags:
+  - airport
+- uid: lkvV5JXfzO34
+  type: local
+  name: New Profile
+  file: lkvV5JXfzO34.yaml
+  desc: ''
+  updated: 1725587682
+  chain: []
+  group: home
+  tags:
+  - test
+groups:
+- work
+- home
+"#;
+
+    #[test]
+    fn test_migrate_existing_data() {
+        let original_data = serde_yaml::from_str::<Mapping>(ORIGINAL_SAMPLE).unwrap();
+        let migrated_data = migrate_profile_data(original_data);
+        let output_data = serde_yaml::to_string(&migrated_data).unwrap();
+        assert_str_eq!(output_data, MIGRATED_SAMPLE);
+    }
+
+    #[test]
+    fn test_discard_existing_data() {
+        let migrated_data = serde_yaml::from_str::<Mapping>(MIGRATED_SAMPLE).unwrap();
+        let discarded_data = discard_profile_data(migrated_data);
+        let profiles = serde_yaml::from_value::<Profiles>(Value::Mapping(discarded_data)).unwrap();
+        assert!(profiles.groups.is_empty());
+        assert!(profiles.items.iter().all(|item| item.group().is_none()));
+    }
+
+    #[test]
+    fn test_profile_parse_migrated_data() {
+        let profiles = serde_yaml::from_str::<Profiles>(MIGRATED_SAMPLE).unwrap();
+        assert_eq!(profiles.groups, vec!["work", "home"]);
+        assert_eq!(profiles.get_group_items("work").len(), 1);
+        assert_eq!(profiles.get_tagged_items("test").len(), 1);
+    }
+}
@@ -1,3 +1,4 @@
+pub mod auto_select;
 pub mod clash;
 pub mod connection_interruption;
 pub mod handle;
@@ -5,14 +6,18 @@ pub mod hotkey;
 pub mod logger;
 pub mod manager;
 pub mod migration;
+pub mod network_rules;
 pub mod pac;
 pub mod privilege;
+pub mod profiles_watcher;
+pub mod provider_mirror;
 pub mod service;
 pub mod state;
 pub mod state_v2;
 pub mod storage;
 pub mod sysopt;
 pub mod tasks;
+pub mod traffic_history;
 pub mod tray;
 pub mod updater;
 #[cfg(windows)]
@@ -0,0 +1,381 @@
+//! Switch the profiles and the clash mode by the network the machine is connected to.
+//! The network is checked periodically and on the NetworkManager events,
+//! the rules are evaluated only when the network changes, so a manual switch is kept until then.
+mod provider;
+
+pub use provider::{NetworkInfoProvider, SystemNetworkInfo};
+
+use crate::{
+    config::{
+        Config,
+        nyanpasu::{NetworkCondition, NetworkRule, NetworkRulesConfig},
+        profile::item_type::ProfileUid,
+    },
+    feat,
+    utils::config::get_current_clash_mode,
+};
+use anyhow::Result;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::Serialize;
+use specta::Type;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::IpAddr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+const DECISIONS_LEN: usize = 50;
+
+/// The facts the rules are evaluated against
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Type)]
+pub struct NetworkInfo {
+    pub ssid: Option<String>,
+    pub gateway_mac: Option<String>,
+    /// the host names of the rules -> the addresses they resolve to, the fake-ip answers are dropped
+    pub resolved: BTreeMap<String, Vec<IpAddr>>,
+}
+
+/// the fake-ip range of the core, the answers in it say nothing about the network
+fn is_fake_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            a == 198 && (b & 0xfe) == 18
+        }
+        IpAddr::V6(_) => false,
+    }
+}
+
+fn normalize_mac(mac: &str) -> String {
+    mac.trim().replace('-', ":").to_lowercase()
+}
+
+fn collect_hosts<'a>(condition: &'a NetworkCondition, hosts: &mut Vec<&'a str>) {
+    match condition {
+        NetworkCondition::Resolves { host, .. } => hosts.push(host),
+        NetworkCondition::Not { condition } => collect_hosts(condition, hosts),
+        _ => {}
+    }
+}
+
+impl NetworkInfo {
+    /// query the facts needed by the rules, the failed queries are treated as absent
+    pub async fn collect(provider: &dyn NetworkInfoProvider, rules: &[NetworkRule]) -> Self {
+        let mut hosts = Vec::new();
+        for condition in rules.iter().flat_map(|rule| rule.conditions.iter()) {
+            collect_hosts(condition, &mut hosts);
+        }
+        hosts.sort_unstable();
+        hosts.dedup();
+
+        let ssid = provider.wifi_ssid().await.unwrap_or_else(|err| {
+            log::debug!(target: "app", "failed to get the wifi ssid: {err:?}");
+            None
+        });
+        let gateway_mac = provider
+            .gateway_mac()
+            .await
+            .unwrap_or_else(|err| {
+                log::debug!(target: "app", "failed to get the gateway mac: {err:?}");
+                None
+            })
+            .map(|mac| normalize_mac(&mac));
+        let mut resolved = BTreeMap::new();
+        for host in hosts {
+            let mut addrs = provider.resolve(host).await.unwrap_or_default();
+            addrs.retain(|addr| !is_fake_ip(addr));
+            addrs.sort_unstable();
+            resolved.insert(host.to_string(), addrs);
+        }
+        Self {
+            ssid,
+            gateway_mac,
+            resolved,
+        }
+    }
+
+    pub fn matches(&self, condition: &NetworkCondition) -> bool {
+        match condition {
+            NetworkCondition::Ssid { ssid } => self.ssid.as_deref() == Some(ssid.as_str()),
+            NetworkCondition::GatewayMac { mac } => {
+                self.gateway_mac.as_deref() == Some(normalize_mac(mac).as_str())
+            }
+            NetworkCondition::Resolves { host, address } => {
+                self.resolved.get(host).is_some_and(|addrs| match address {
+                    Some(address) => addrs.contains(address),
+                    None => !addrs.is_empty(),
+                })
+            }
+            NetworkCondition::Not { condition } => !self.matches(condition),
+        }
+    }
+}
+
+/// the first rule whose conditions all match
+pub fn evaluate<'a>(rules: &'a [NetworkRule], info: &NetworkInfo) -> Option<&'a NetworkRule> {
+    rules.iter().find(|rule| {
+        rule.conditions
+            .iter()
+            .all(|condition| info.matches(condition))
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Type)]
+pub struct NetworkDecision {
+    pub timestamp: i64,
+    pub info: NetworkInfo,
+    /// the name of the matched rule
+    pub rule: Option<String>,
+    /// the profiles activated by the rule, `None` if they are active already
+    pub profiles: Option<Vec<ProfileUid>>,
+    /// the clash mode switched to by the rule, `None` if it is the current one already
+    pub mode: Option<String>,
+    pub error: Option<String>,
+}
+
+/// apply the matched rule, only the changed parts are applied
+async fn apply(rule: &NetworkRule, decision: &mut NetworkDecision) -> Result<()> {
+    if let Some(profiles) = &rule.profiles
+        && feat::activate_profiles(profiles).await?
+    {
+        decision.profiles = Some(profiles.clone());
+    }
+    if let Some(mode) = rule.mode
+        && get_current_clash_mode() != mode.as_str()
+    {
+        feat::change_clash_mode(mode.as_str().to_string());
+        decision.mode = Some(mode.as_str().to_string());
+    }
+    Ok(())
+}
+
+pub struct NetworkRules {
+    provider: Arc<dyn NetworkInfoProvider>,
+    trigger: Mutex<Option<mpsc::UnboundedSender<()>>>,
+    decisions: Mutex<VecDeque<NetworkDecision>>,
+}
+
+impl NetworkRules {
+    pub fn global() -> &'static NetworkRules {
+        static NETWORK_RULES: OnceCell<NetworkRules> = OnceCell::new();
+
+        NETWORK_RULES.get_or_init(|| NetworkRules::new(Arc::new(SystemNetworkInfo::new())))
+    }
+
+    pub fn new(provider: Arc<dyn NetworkInfoProvider>) -> Self {
+        Self {
+            provider,
+            trigger: Mutex::new(None),
+            decisions: Mutex::new(VecDeque::with_capacity(DECISIONS_LEN)),
+        }
+    }
+
+    /// the latest decisions, the newest first
+    pub fn decisions(&self) -> Vec<NetworkDecision> {
+        self.decisions.lock().iter().rev().cloned().collect()
+    }
+
+    /// evaluate the rules against the current network, regardless of whether it is changed
+    pub fn check_now(&self) {
+        if let Some(trigger) = self.trigger.lock().as_ref() {
+            let _ = trigger.send(());
+        }
+    }
+
+    /// evaluate the rules, the matched one is applied if `apply_rule` is set
+    pub async fn decide(
+        &self,
+        config: &NetworkRulesConfig,
+        info: NetworkInfo,
+        apply_rule: bool,
+    ) -> NetworkDecision {
+        let rule = evaluate(&config.rules, &info);
+        let mut decision = NetworkDecision {
+            timestamp: chrono::Local::now().timestamp(),
+            info,
+            rule: rule.map(|rule| rule.name.clone()),
+            profiles: None,
+            mode: None,
+            error: None,
+        };
+        if let Some(rule) = rule
+            && apply_rule
+            && let Err(err) = apply(rule, &mut decision).await
+        {
+            decision.error = Some(format!("{err:#}"));
+        }
+        match &decision.error {
+            Some(error) => {
+                log::error!(target: "app", "network rules: {decision:?}, failed: {error}")
+            }
+            None => log::info!(target: "app", "network rules: {decision:?}"),
+        }
+        let mut decisions = self.decisions.lock();
+        if decisions.len() >= DECISIONS_LEN {
+            decisions.pop_front();
+        }
+        decisions.push_back(decision.clone());
+        decision
+    }
+
+    async fn run(&self, mut trigger: mpsc::UnboundedReceiver<()>) {
+        let mut last: Option<NetworkInfo> = None;
+        loop {
+            let config = Config::verge().latest().get_network_rules();
+            let forced = tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(config.interval.max(1))) => false,
+                received = trigger.recv() => match received {
+                    Some(_) => true,
+                    None => break,
+                },
+            };
+            // the latest config, it may be the one which triggers the check
+            let config = Config::verge().latest().get_network_rules();
+            if !config.enable || config.rules.is_empty() {
+                last = None;
+                continue;
+            }
+            let info = NetworkInfo::collect(self.provider.as_ref(), &config.rules).await;
+            if !forced && last.as_ref() == Some(&info) {
+                continue;
+            }
+            last = Some(info.clone());
+            self.decide(&config, info, true).await;
+        }
+    }
+}
+
+pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(_manager: &M) -> Result<()> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let rules = NetworkRules::global();
+    *rules.trigger.lock() = Some(tx);
+    tauri::async_runtime::spawn(async move { rules.run(rx).await });
+    // check once the core is up
+    rules.check_now();
+    #[cfg(target_os = "linux")]
+    tauri::async_runtime::spawn(async {
+        // the networks are settling for a while after the events
+        let notify = || {
+            tauri::async_runtime::spawn(async {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                NetworkRules::global().check_now();
+            });
+        };
+        if let Err(err) = provider::watch_changes(notify).await {
+            log::warn!(target: "app", "failed to watch the NetworkManager events: {err:?}");
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::nyanpasu::ClashMode;
+    use async_trait::async_trait;
+    use std::net::Ipv4Addr;
+
+    struct MockNetworkInfo {
+        ssid: Option<&'static str>,
+        gateway_mac: Option<&'static str>,
+        hosts: Vec<(&'static str, IpAddr)>,
+    }
+
+    #[async_trait]
+    impl NetworkInfoProvider for MockNetworkInfo {
+        async fn wifi_ssid(&self) -> Result<Option<String>> {
+            Ok(self.ssid.map(str::to_string))
+        }
+
+        async fn gateway_mac(&self) -> Result<Option<String>> {
+            Ok(self.gateway_mac.map(str::to_string))
+        }
+
+        async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>> {
+            Ok(self
+                .hosts
+                .iter()
+                .filter(|(name, _)| *name == host)
+                .map(|(_, addr)| *addr)
+                .collect())
+        }
+    }
+
+    fn rules() -> Vec<NetworkRule> {
+        serde_yaml::from_str(
+            r#"
+- name: office
+  conditions:
+    - type: resolves
+      host: intranet.corp.example
+      address: 10.0.0.8
+  profiles: [work]
+  mode: rule
+- name: home
+  conditions:
+    - type: ssid
+      ssid: HomeWiFi
+    - type: not
+      condition:
+        type: gateway_mac
+        mac: 00:11:22:33:44:55
+  profiles: [home]
+- name: elsewhere
+  mode: global
+"#,
+        )
+        .unwrap()
+    }
+
+    async fn decide(provider: MockNetworkInfo) -> NetworkDecision {
+        let config = NetworkRulesConfig {
+            enable: true,
+            rules: rules(),
+            ..Default::default()
+        };
+        let info = NetworkInfo::collect(&provider, &config.rules).await;
+        NetworkRules::new(Arc::new(provider))
+            .decide(&config, info, false)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_network_rules() {
+        let office = decide(MockNetworkInfo {
+            ssid: Some("HomeWiFi"),
+            gateway_mac: None,
+            hosts: vec![("intranet.corp.example", Ipv4Addr::new(10, 0, 0, 8).into())],
+        })
+        .await;
+        assert_eq!(office.rule.as_deref(), Some("office"));
+
+        let home = decide(MockNetworkInfo {
+            ssid: Some("HomeWiFi"),
+            gateway_mac: Some("AA-BB-CC-DD-EE-FF"),
+            // the fake-ip answer of the core while the tun mode is on
+            hosts: vec![("intranet.corp.example", Ipv4Addr::new(198, 18, 0, 3).into())],
+        })
+        .await;
+        assert_eq!(home.rule.as_deref(), Some("home"));
+        assert_eq!(home.info.gateway_mac.as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+        assert_eq!(home.info.resolved["intranet.corp.example"], vec![]);
+
+        let elsewhere = decide(MockNetworkInfo {
+            ssid: Some("HomeWiFi"),
+            gateway_mac: Some("00:11:22:33:44:55"),
+            hosts: vec![],
+        })
+        .await;
+        assert_eq!(elsewhere.rule.as_deref(), Some("elsewhere"));
+        assert_eq!(rules()[2].mode, Some(ClashMode::Global));
+    }
+
+    #[test]
+    fn test_evaluate_without_catch_all() {
+        let rules = &rules()[..2];
+        assert!(evaluate(rules, &NetworkInfo::default()).is_none());
+    }
+}
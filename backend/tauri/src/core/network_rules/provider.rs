@@ -0,0 +1,192 @@
+//! Where the network facts come from, the rule engine only sees the [`NetworkInfoProvider`] trait,
+//! so it could be driven by a mock in the tests.
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{net::IpAddr, time::Duration};
+
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[async_trait]
+pub trait NetworkInfoProvider: Send + Sync {
+    /// the SSID of the connected Wi-Fi
+    async fn wifi_ssid(&self) -> Result<Option<String>>;
+    /// the MAC address of the default gateway, lowercase and colon separated
+    async fn gateway_mac(&self) -> Result<Option<String>>;
+    /// the addresses the host name resolves to
+    async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>>;
+}
+
+/// Reads the facts from the system, the SSID and the gateway MAC are only available on Linux,
+/// through NetworkManager and procfs respectively
+#[derive(Default)]
+pub struct SystemNetworkInfo {
+    #[cfg(target_os = "linux")]
+    dbus: tokio::sync::OnceCell<zbus::Connection>,
+}
+
+impl SystemNetworkInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn dbus(&self) -> Result<&zbus::Connection> {
+        Ok(self.dbus.get_or_try_init(zbus::Connection::system).await?)
+    }
+}
+
+#[async_trait]
+impl NetworkInfoProvider for SystemNetworkInfo {
+    #[cfg(target_os = "linux")]
+    async fn wifi_ssid(&self) -> Result<Option<String>> {
+        linux::wifi_ssid(self.dbus().await?).await
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn wifi_ssid(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn gateway_mac(&self) -> Result<Option<String>> {
+        let route = tokio::fs::read_to_string("/proc/net/route").await?;
+        let Some(gateway) = linux::default_gateway(&route) else {
+            return Ok(None);
+        };
+        let arp = tokio::fs::read_to_string("/proc/net/arp").await?;
+        Ok(linux::neighbour_mac(&arp, gateway.into()))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn gateway_mac(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    async fn resolve(&self, host: &str) -> Result<Vec<IpAddr>> {
+        let addrs =
+            tokio::time::timeout(RESOLVE_TIMEOUT, tokio::net::lookup_host((host, 0))).await??;
+        Ok(addrs.map(|addr| addr.ip()).collect())
+    }
+}
+
+/// watch the NetworkManager for the connectivity changes, `notify` is called on every change
+#[cfg(target_os = "linux")]
+pub async fn watch_changes(notify: impl Fn() + Send + Sync) -> Result<()> {
+    use futures::StreamExt;
+
+    let conn = zbus::Connection::system().await?;
+    let nm = zbus::Proxy::new(&conn, linux::NM, linux::NM_PATH, linux::NM).await?;
+    let mut states = nm.receive_signal("StateChanged").await?;
+    let mut primaries = nm
+        .receive_property_changed::<zbus::zvariant::OwnedObjectPath>("PrimaryConnection")
+        .await;
+    loop {
+        tokio::select! {
+            Some(_) = states.next() => notify(),
+            Some(_) = primaries.next() => notify(),
+            else => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use anyhow::Result;
+    use std::net::{IpAddr, Ipv4Addr};
+    use zbus::{Proxy, zvariant::OwnedObjectPath};
+
+    pub const NM: &str = "org.freedesktop.NetworkManager";
+    pub const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+    const NM_DEVICE: &str = "org.freedesktop.NetworkManager.Device";
+    const NM_WIRELESS: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+    const NM_ACCESS_POINT: &str = "org.freedesktop.NetworkManager.AccessPoint";
+    const NM_DEVICE_TYPE_WIFI: u32 = 2;
+
+    /// the SSID of the access point of the first connected Wi-Fi device
+    pub async fn wifi_ssid(conn: &zbus::Connection) -> Result<Option<String>> {
+        let nm = Proxy::new(conn, NM, NM_PATH, NM).await?;
+        let devices: Vec<OwnedObjectPath> = nm.get_property("Devices").await?;
+        for device in devices.iter() {
+            let proxy = Proxy::new(conn, NM, device.as_str(), NM_DEVICE).await?;
+            let kind: u32 = proxy.get_property("DeviceType").await?;
+            if kind != NM_DEVICE_TYPE_WIFI {
+                continue;
+            }
+            let wireless = Proxy::new(conn, NM, device.as_str(), NM_WIRELESS).await?;
+            let access_point: OwnedObjectPath = wireless.get_property("ActiveAccessPoint").await?;
+            if access_point.as_str() == "/" {
+                continue;
+            }
+            let access_point = Proxy::new(conn, NM, access_point.as_str(), NM_ACCESS_POINT).await?;
+            let ssid: Vec<u8> = access_point.get_property("Ssid").await?;
+            if !ssid.is_empty() {
+                return Ok(Some(String::from_utf8_lossy(&ssid).into_owned()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// the gateway of the default route in `/proc/net/route`
+    pub fn default_gateway(route: &str) -> Option<Ipv4Addr> {
+        const RTF_UP: u32 = 0x1;
+        const RTF_GATEWAY: u32 = 0x2;
+        route
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                let (destination, gateway, flags) =
+                    (fields.get(1)?, fields.get(2)?, fields.get(3)?);
+                let flags = u32::from_str_radix(flags, 16).ok()?;
+                if *destination != "00000000"
+                    || flags & (RTF_UP | RTF_GATEWAY) != RTF_UP | RTF_GATEWAY
+                {
+                    return None;
+                }
+                // the address is in the host byte order, which is little endian on the supported targets
+                let gateway = u32::from_str_radix(gateway, 16).ok()?;
+                Some(Ipv4Addr::from(gateway.to_le_bytes()))
+            })
+            .next()
+    }
+
+    /// the MAC address of the neighbour in `/proc/net/arp`
+    pub fn neighbour_mac(arp: &str, ip: IpAddr) -> Option<String> {
+        let ip = ip.to_string();
+        arp.lines().skip(1).find_map(|line| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let mac = fields.get(3)?;
+            (*fields.first()? == ip && *mac != "00:00:00:00:00:00").then(|| mac.to_lowercase())
+        })
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_gateway_mac() {
+            let route = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+wlan0\t0000A8C0\t00000000\t0001\t0\t0\t600\t00FFFFFF\t0\t0\t0
+wlan0\t00000000\t0100A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+";
+            let arp = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.0.23     0x1         0x0         00:00:00:00:00:00     *        wlan0
+192.168.0.1      0x1         0x2         AA:BB:CC:00:11:22     *        wlan0
+";
+            let gateway = default_gateway(route).unwrap();
+            assert_eq!(gateway, Ipv4Addr::new(192, 168, 0, 1));
+            assert_eq!(
+                neighbour_mac(arp, gateway.into()).as_deref(),
+                Some("aa:bb:cc:00:11:22")
+            );
+            assert_eq!(
+                neighbour_mac(arp, Ipv4Addr::new(192, 168, 0, 23).into()),
+                None
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Watch the profiles dir, and re-apply the config when the files in use
+//! are edited outside the app.
+use crate::{
+    config::{Config, Profile, ProfileMetaGetter},
+    core::{CoreManager, handle::Handle},
+    utils::dirs,
+};
+use anyhow::Result;
+use notify_debouncer_full::{
+    DebounceEventResult, Debouncer, RecommendedCache, new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+};
+use parking_lot::Mutex;
+use rust_i18n::t;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// editors usually write a file in several steps, wait for them to settle
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(800);
+
+pub struct ProfilesWatcher {
+    _debouncer: Mutex<Debouncer<RecommendedWatcher, RecommendedCache>>,
+}
+
+/// the files of the current profiles, their chains and the global chain
+fn files_in_use() -> HashSet<String> {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let mut uids = profiles
+        .get_current()
+        .iter()
+        .chain(profiles.chain.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+    for uid in profiles.get_current() {
+        match profiles.get_item(uid) {
+            Ok(Profile::Remote(item)) => uids.extend(item.chain.iter().cloned()),
+            Ok(Profile::Local(item)) => uids.extend(item.chain.iter().cloned()),
+            _ => {}
+        }
+    }
+    uids.iter()
+        .filter_map(|uid| profiles.get_item(uid).ok())
+        .map(|item| item.file().to_string())
+        .collect()
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let content = std::fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// regenerate the runtime config, check it and reload the core,
+/// the previous runtime config is kept if any step fails
+async fn reapply(changed: &[String]) {
+    // the files written by the app itself (e.g. subscription updates) are applied already
+    if let Err(err) = Config::generate().await {
+        log::error!(target: "app", "failed to generate the config: {err:?}");
+    }
+    let generated = Config::runtime().draft().config.clone();
+    let applied = Config::runtime().data().config.clone();
+    if generated == applied {
+        Config::runtime().discard();
+        log::debug!(target: "app", "profile files changed: {changed:?}, the config is up to date");
+        return;
+    }
+    log::info!(target: "app", "profile files changed outside the app: {changed:?}, re-applying the config");
+    let files = changed.join(", ");
+    match CoreManager::global().update_config().await {
+        Ok(_) => {
+            Config::runtime().apply();
+            Handle::refresh_clash();
+            Handle::notify_system(
+                &t!("notification.profiles_watcher.title"),
+                &t!("notification.profiles_watcher.reloaded", files = files),
+            );
+        }
+        Err(err) => {
+            Config::runtime().discard();
+            log::error!(target: "app", "failed to re-apply the changed profiles: {err:?}");
+            Handle::notify_system(
+                &t!("notification.profiles_watcher.title"),
+                &t!(
+                    "notification.profiles_watcher.failed",
+                    files = files,
+                    error = format!("{err:#}")
+                ),
+            );
+        }
+    }
+}
+
+async fn handle_changes(mut rx: mpsc::UnboundedReceiver<Vec<PathBuf>>) {
+    // the content hashes of the files when they were applied last time,
+    // so that the writes without changes (e.g. touch, save without editing) are ignored
+    let mut applied = HashMap::<String, u64>::new();
+    if let Ok(profiles_dir) = dirs::app_profiles_dir() {
+        for name in files_in_use() {
+            if let Some(hash) = hash_file(&profiles_dir.join(&name)) {
+                applied.insert(name, hash);
+            }
+        }
+    }
+    while let Some(paths) = rx.recv().await {
+        let in_use = files_in_use();
+        let mut changed = Vec::new();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !in_use.contains(name) || changed.iter().any(|c| c == name) {
+                continue;
+            }
+            let Some(hash) = hash_file(&path) else {
+                continue;
+            };
+            if applied.insert(name.to_string(), hash) != Some(hash) {
+                changed.push(name.to_string());
+            }
+        }
+        if !changed.is_empty() {
+            reapply(&changed).await;
+        }
+    }
+}
+
+impl ProfilesWatcher {
+    pub fn new() -> Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(
+            DEBOUNCE_TIMEOUT,
+            None,
+            move |result: DebounceEventResult| match result {
+                Ok(events) => {
+                    let paths = events
+                        .into_iter()
+                        .filter(|event| event.kind.is_create() || event.kind.is_modify())
+                        .flat_map(|event| event.event.paths)
+                        .collect::<Vec<_>>();
+                    if !paths.is_empty() {
+                        let _ = tx.send(paths);
+                    }
+                }
+                Err(errors) => {
+                    log::error!(target: "app", "profiles watcher error: {errors:?}");
+                }
+            },
+        )?;
+        debouncer.watch(dirs::app_profiles_dir()?, RecursiveMode::NonRecursive)?;
+        tauri::async_runtime::spawn(handle_changes(rx));
+        Ok(Self {
+            _debouncer: Mutex::new(debouncer),
+        })
+    }
+}
+
+pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> Result<()> {
+    let watcher = ProfilesWatcher::new()?;
+    manager.manage(watcher);
+    Ok(())
+}
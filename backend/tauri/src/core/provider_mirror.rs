@@ -0,0 +1,269 @@
+//! Local mirrors of the http `proxy-providers` and `rule-providers`,
+//! they are downloaded through the subscription client instead of the core,
+//! so that the core could start without reaching the upstream.
+use crate::{
+    config::{Config, RemoteProfileOptions, content_hash, request_url},
+    utils::{dirs, help},
+};
+use anyhow::{Context, Result, bail};
+use indexmap::{IndexMap, IndexSet};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use url::Url;
+
+const INDEX_FILE: &str = "index.yaml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    Proxy,
+    Rule,
+}
+
+impl ProviderKind {
+    /// the field of the providers in the clash config
+    pub fn field(&self) -> &'static str {
+        match self {
+            ProviderKind::Proxy => "proxy-providers",
+            ProviderKind::Rule => "rule-providers",
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderKind::Proxy => write!(f, "proxy"),
+            ProviderKind::Rule => write!(f, "rule"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MirrorEntry {
+    pub url: Url,
+    pub kind: ProviderKind,
+    /// the uid of the profile which the provider belongs to, `None` for the global chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// the options to download the provider, along with the `header` of the provider.
+    /// They are not persisted for the credentials in them, and set again by the next enhance
+    #[serde(skip)]
+    pub option: Option<RemoteProfileOptions>,
+    /// refresh interval in seconds
+    pub interval: u64,
+    /// the names of the providers in the current config
+    #[serde(default)]
+    pub names: IndexSet<String>,
+    /// sha256 of the cached payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// timestamp of the last download
+    #[serde(default)]
+    pub updated: i64,
+}
+
+impl MirrorEntry {
+    pub fn new(
+        url: Url,
+        kind: ProviderKind,
+        profile: Option<String>,
+        option: RemoteProfileOptions,
+        interval: u64,
+    ) -> Self {
+        Self {
+            url,
+            kind,
+            profile,
+            option: Some(option),
+            interval,
+            names: IndexSet::new(),
+            hash: None,
+            updated: 0,
+        }
+    }
+
+    fn is_outdated(&self, now: i64) -> bool {
+        now - self.updated >= self.interval as i64
+    }
+
+    /// the options to download the provider, or those of the profile if it is loaded from the index
+    fn options(&self) -> RemoteProfileOptions {
+        if let Some(option) = &self.option {
+            return option.clone();
+        }
+        self.profile
+            .as_deref()
+            .and_then(|uid| {
+                Config::profiles()
+                    .latest()
+                    .get_item(uid)
+                    .ok()
+                    .and_then(|item| item.as_remote())
+                    .map(|item| item.option.clone())
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The cached payloads are stored as `<sha256 of the url>` under `profiles/providers`,
+/// along with an index of their upstreams.
+pub struct ProviderMirror {
+    dir: PathBuf,
+    index: Mutex<IndexMap<String, MirrorEntry>>,
+}
+
+impl ProviderMirror {
+    pub fn global() -> Result<&'static ProviderMirror> {
+        static MIRROR: OnceCell<ProviderMirror> = OnceCell::new();
+
+        MIRROR.get_or_try_init(|| Self::new(dirs::app_profiles_dir()?.join("providers")))
+    }
+
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs_err::create_dir_all(&dir)?;
+        let index_path = dir.join(INDEX_FILE);
+        let index = if index_path.exists() {
+            help::read_yaml(&index_path).unwrap_or_else(|err| {
+                log::error!(target: "app", "failed to read the provider mirror index: {err:?}");
+                IndexMap::new()
+            })
+        } else {
+            IndexMap::new()
+        };
+        Ok(Self {
+            dir,
+            index: Mutex::new(index),
+        })
+    }
+
+    pub fn key(url: &Url) -> String {
+        content_hash(url.as_str().as_bytes())
+    }
+
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    /// the path of a cached payload, the key is only accepted if it is in the index
+    pub fn cached_path(&self, key: &str) -> Option<PathBuf> {
+        if !self.index.lock().contains_key(key) {
+            return None;
+        }
+        let path = self.path(key);
+        path.exists().then_some(path)
+    }
+
+    fn save_index(&self) -> Result<()> {
+        let index = self.index.lock().clone();
+        help::save_yaml(
+            self.dir.join(INDEX_FILE),
+            &index,
+            Some("# Provider mirrors of Clash Nyanpasu"),
+        )
+    }
+
+    /// register the provider, and download it if there is no cached payload yet
+    pub async fn mirror(&self, entry: MirrorEntry) -> Result<String> {
+        let key = Self::key(&entry.url);
+        let cached = {
+            let mut index = self.index.lock();
+            let cached = index.get(&key).is_some_and(|cached| cached.hash.is_some());
+            let registered = index.entry(key.clone()).or_insert_with(|| entry.clone());
+            registered.kind = entry.kind;
+            registered.profile = entry.profile;
+            registered.option = entry.option;
+            registered.interval = entry.interval;
+            cached && self.path(&key).exists()
+        };
+        if cached {
+            self.save_index()?;
+        } else {
+            self.download(&key).await?;
+        }
+        Ok(key)
+    }
+
+    /// download the payload of the provider, returns whether it is changed
+    pub async fn download(&self, key: &str) -> Result<bool> {
+        let entry = self
+            .index
+            .lock()
+            .get(key)
+            .cloned()
+            .with_context(|| format!("provider mirror `{key}` is not registered"))?;
+        let resp = request_url(&entry.url, &entry.options(), HeaderMap::new()).await?;
+        let payload = resp
+            .bytes()
+            .await
+            .context("failed to read the provider payload")?;
+        if payload.is_empty() {
+            bail!("the provider payload is empty");
+        }
+
+        let hash = content_hash(&payload);
+        let path = self.path(key);
+        let changed = {
+            let index = self.index.lock();
+            index.get(key).and_then(|entry| entry.hash.as_ref()) != Some(&hash) || !path.exists()
+        };
+        if changed {
+            // write to a temporary file first, the core may read the payload at any time
+            let tmp = self.dir.join(format!("{key}.tmp"));
+            tokio::fs::write(&tmp, &payload).await?;
+            tokio::fs::rename(&tmp, &path).await?;
+        }
+        if let Some(entry) = self.index.lock().get_mut(key) {
+            entry.hash = Some(hash);
+            entry.updated = chrono::Local::now().timestamp();
+        }
+        self.save_index()?;
+        Ok(changed)
+    }
+
+    /// the providers which should be refreshed
+    pub fn outdated(&self) -> Vec<String> {
+        let now = chrono::Local::now().timestamp();
+        self.index
+            .lock()
+            .iter()
+            .filter(|(_, entry)| entry.is_outdated(now))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<MirrorEntry> {
+        self.index.lock().get(key).cloned()
+    }
+
+    /// keep the providers of the current config only, `used` is key -> provider names
+    pub fn retain(&self, used: &IndexMap<String, IndexSet<String>>) -> Result<()> {
+        let removed = {
+            let mut index = self.index.lock();
+            let mut removed = Vec::new();
+            index.retain(|key, entry| match used.get(key) {
+                Some(names) => {
+                    entry.names = names.clone();
+                    true
+                }
+                None => {
+                    removed.push(key.clone());
+                    false
+                }
+            });
+            removed
+        };
+        for key in removed {
+            let path = self.path(&key);
+            if path.exists()
+                && let Err(err) = fs_err::remove_file(&path)
+            {
+                log::warn!(target: "app", "failed to remove the provider mirror: {err}");
+            }
+        }
+        self.save_index()
+    }
+}
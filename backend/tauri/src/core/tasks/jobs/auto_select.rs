@@ -0,0 +1,47 @@
+use super::JobExt;
+use crate::core::{
+    auto_select::AutoSelect,
+    tasks::{
+        executor::{AsyncJobExecutor, TaskExecutor},
+        task::{Task, TaskSchedule},
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+const AUTO_SELECT_TASK_NAME: &str = "Auto Select";
+
+/// Run the auto selection policies of the `Selector` groups,
+/// the task ticks often and each policy is run on its own interval
+#[derive(Clone, Default)]
+pub struct AutoSelectJob;
+
+impl AutoSelectJob {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl AsyncJobExecutor for AutoSelectJob {
+    async fn execute(&self) -> Result<()> {
+        AutoSelect::global().run_due().await;
+        Ok(())
+    }
+}
+
+impl JobExt for AutoSelectJob {
+    fn name(&self) -> &'static str {
+        AUTO_SELECT_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        Some(Task {
+            name: AUTO_SELECT_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(30)),
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
@@ -1,6 +1,10 @@
+mod auto_select;
 mod events_rotate;
 mod logger;
+mod profile_schedules;
 mod profiles;
+mod provider_mirror;
+mod subscription_alert;
 
 use super::{
     task::{Task, TaskManager},
@@ -8,6 +12,7 @@ use super::{
 };
 use anyhow::anyhow;
 use parking_lot::RwLock;
+pub use profile_schedules::ProfileSchedulesJobGuard;
 pub use profiles::ProfilesJobGuard;
 use std::sync::Arc;
 #[allow(dead_code)]
@@ -30,9 +35,14 @@ impl JobsManager {
     }
 
     pub fn setup(&mut self) -> anyhow::Result<()> {
-        let jobs: Vec<Box<dyn JobExt + Send + Sync>> = vec![Box::new(
-            events_rotate::EventsRotateJob::new(self.task_manager.read().get_inner_task_storage()),
-        )];
+        let jobs: Vec<Box<dyn JobExt + Send + Sync>> = vec![
+            Box::new(events_rotate::EventsRotateJob::new(
+                self.task_manager.read().get_inner_task_storage(),
+            )),
+            Box::new(subscription_alert::SubscriptionAlertJob::new()),
+            Box::new(provider_mirror::ProviderMirrorJob::new()),
+            Box::new(auto_select::AutoSelectJob::new()),
+        ];
         for job in jobs {
             let task = job.setup();
             if let Some(task) = task {
@@ -0,0 +1,136 @@
+use super::super::{
+    executor::{AsyncJobExecutor, TaskExecutor},
+    task::{Task, TaskID, TaskManager, TaskSchedule},
+};
+use crate::{
+    config::{Config, ProfileSchedule},
+    feat,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use rustc_hash::FxHasher;
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    sync::Arc,
+};
+
+const INITIAL_TASK_ID: TaskID = 20000000; // 避免和其他任务的 ID 冲突
+
+#[derive(Clone)]
+pub struct ProfileActivator(ProfileSchedule);
+
+#[async_trait]
+impl AsyncJobExecutor for ProfileActivator {
+    async fn execute(&self) -> Result<()> {
+        log::info!(target: "app", "running profile schedule `{}`", self.0.name);
+        feat::apply_profile_schedule(&self.0).await.inspect_err(|err| {
+            log::error!(target: "app", "failed to apply profile schedule `{}`: {err:?}", self.0.name);
+        })
+    }
+}
+
+/// Keep a cron task per enabled schedule rule of `profiles.yaml`
+pub struct ProfileSchedulesJob {
+    task_map: HashMap<ProfileSchedule, TaskID>,
+    task_manager: Arc<RwLock<TaskManager>>,
+}
+
+pub struct ProfileSchedulesJobGuard {
+    job: Arc<RwLock<ProfileSchedulesJob>>,
+}
+
+impl ProfileSchedulesJobGuard {
+    pub fn new(task_manager: Arc<RwLock<TaskManager>>) -> Self {
+        Self {
+            job: Arc::new(RwLock::new(ProfileSchedulesJob::new(task_manager))),
+        }
+    }
+}
+
+impl Deref for ProfileSchedulesJobGuard {
+    type Target = Arc<RwLock<ProfileSchedulesJob>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.job
+    }
+}
+
+impl ProfileSchedulesJob {
+    pub fn new(task_manager: Arc<RwLock<TaskManager>>) -> Self {
+        Self {
+            task_map: HashMap::new(),
+            task_manager,
+        }
+    }
+
+    /// Sync the tasks with the schedules, the changed schedules are treated as new ones
+    pub fn refresh(&mut self) {
+        let schedules = Config::profiles()
+            .latest()
+            .schedules
+            .iter()
+            .filter(|schedule| schedule.enable)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let removed = self
+            .task_map
+            .keys()
+            .filter(|schedule| !schedules.contains(schedule))
+            .cloned()
+            .collect::<Vec<_>>();
+        for schedule in removed {
+            if let Some(task_id) = self.task_map.remove(&schedule) {
+                crate::log_err!(self.task_manager.write().remove_task(task_id));
+            }
+        }
+
+        for schedule in schedules {
+            if self.task_map.contains_key(&schedule) {
+                continue;
+            }
+            let task = match new_task(&schedule) {
+                Ok(task) => task,
+                Err(err) => {
+                    log::error!(target: "app", "skip profile schedule `{}`: {err:?}", schedule.name);
+                    continue;
+                }
+            };
+            let task_id = task.id;
+            match self.task_manager.write().add_task(task) {
+                Ok(_) => {
+                    self.task_map.insert(schedule, task_id);
+                }
+                Err(err) => {
+                    log::error!(target: "app", "failed to add profile schedule `{}`: {err:?}", schedule.name);
+                }
+            }
+        }
+    }
+}
+
+/// get a u64 task id by the content of the schedule
+fn get_task_id(schedule: &ProfileSchedule) -> TaskID {
+    let mut hasher = FxHasher::default();
+    schedule.hash(&mut hasher);
+    let task_id = hasher.finish();
+    if task_id < INITIAL_TASK_ID {
+        INITIAL_TASK_ID + task_id
+    } else {
+        task_id
+    }
+}
+
+fn new_task(schedule: &ProfileSchedule) -> Result<Task> {
+    schedule.validate()?;
+    Ok(Task {
+        id: get_task_id(schedule),
+        name: format!("profile-schedule-{}", schedule.name),
+        executor: TaskExecutor::Async(Box::new(ProfileActivator(schedule.clone()))),
+        schedule: TaskSchedule::Cron(schedule.timer_cron()?),
+        ..Task::default()
+    })
+}
@@ -3,7 +3,7 @@ use super::super::{
     task::{Task, TaskID, TaskManager, TaskSchedule},
 };
 use crate::{
-    config::{Config, ProfileMetaGetter},
+    config::{Config, Profile, ProfileMetaGetter, SourceSyncOutcome},
     feat,
 };
 use anyhow::Result;
@@ -31,7 +31,21 @@ impl ProfileUpdater {
 impl AsyncJobExecutor for ProfileUpdater {
     async fn execute(&self) -> Result<()> {
         log::info!(target: "app", "running timer task `{}`", self.0);
-        match feat::update_profile(self.0.clone(), None).await {
+        let is_source = Config::profiles()
+            .latest()
+            .get_item(&self.0)
+            .is_ok_and(|item| item.source().is_some());
+        // the url backed merge and script are kept pinned, only the newer content is recorded
+        let result = if is_source {
+            feat::sync_profile_source(&self.0, false).await.map(|outcome| {
+                if let SourceSyncOutcome::Available(hash) = outcome {
+                    log::info!(target: "app", "a newer content of profile `{}` is available: {hash}", self.0);
+                }
+            })
+        } else {
+            feat::update_profile(self.0.clone(), None).await
+        };
+        match result {
             Ok(_) => Ok(()),
             Err(err) => {
                 log::error!(target: "app", "failed to update profile: {err:?}");
@@ -92,12 +106,8 @@ impl ProfilesJob {
             .items
             .iter()
             .filter_map(|item| {
-                if !item.is_remote() {
-                    return None;
-                }
-                let item = item.as_remote().unwrap();
                 // mins to seconds
-                let interval = ((item.option.update_interval) as i64) * 60;
+                let interval = (update_interval(item)? as i64) * 60;
                 let updated = item.updated() as i64;
 
                 if interval > 0 && cur_timestamp - updated >= interval {
@@ -181,10 +191,10 @@ fn gen_map() -> HashMap<ProfileUID, Minutes> {
         .latest()
         .get_items()
         .iter()
-        .filter_map(|item| item.as_remote())
         .for_each(|item| {
-            let interval = item.option.update_interval;
-            if interval > 0 {
+            if let Some(interval) = update_interval(item)
+                && interval > 0
+            {
                 new_map.insert(item.uid().to_string(), interval);
             }
         });
@@ -192,6 +202,14 @@ fn gen_map() -> HashMap<ProfileUID, Minutes> {
     new_map
 }
 
+/// the update interval of the remote profiles and the url backed merge and script
+fn update_interval(item: &Profile) -> Option<Minutes> {
+    match item.as_remote() {
+        Some(item) => Some(item.option.update_interval),
+        None => item.source().map(|source| source.option.update_interval),
+    }
+}
+
 /// get_task_id Get a u64 task id by profile uid
 fn get_task_id(uid: &str) -> TaskID {
     let mut hasher = FxHasher::default();
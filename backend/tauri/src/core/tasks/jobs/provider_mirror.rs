@@ -0,0 +1,76 @@
+use super::JobExt;
+use crate::{
+    config::Config,
+    core::{
+        clash::api,
+        provider_mirror::{ProviderKind, ProviderMirror},
+        tasks::{
+            executor::{AsyncJobExecutor, TaskExecutor},
+            task::{Task, TaskSchedule},
+        },
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+const PROVIDER_MIRROR_TASK_NAME: &str = "Provider Mirror";
+
+/// Refresh the outdated provider mirrors, and ask the core to reload the changed ones
+#[derive(Clone, Default)]
+pub struct ProviderMirrorJob;
+
+impl ProviderMirrorJob {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl AsyncJobExecutor for ProviderMirrorJob {
+    async fn execute(&self) -> Result<()> {
+        if !Config::verge().latest().get_provider_mirror().enable {
+            return Ok(());
+        }
+        let mirror = ProviderMirror::global()?;
+        for key in mirror.outdated() {
+            match mirror.download(&key).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    log::warn!(target: "app", "failed to refresh provider mirror `{key}`: {err:?}");
+                    continue;
+                }
+            }
+            let Some(entry) = mirror.get(&key) else {
+                continue;
+            };
+            log::info!(target: "app", "provider mirror of {:?} is updated", entry.names);
+            for name in entry.names.iter() {
+                let result = match entry.kind {
+                    ProviderKind::Proxy => api::update_providers_proxies_group(name).await,
+                    ProviderKind::Rule => api::update_providers_rules_group(name).await,
+                };
+                if let Err(err) = result {
+                    log::warn!(target: "app", "failed to reload the {} provider `{name}`: {err:?}", entry.kind);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl JobExt for ProviderMirrorJob {
+    fn name(&self) -> &'static str {
+        PROVIDER_MIRROR_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        Some(Task {
+            name: PROVIDER_MIRROR_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(10 * 60)),
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
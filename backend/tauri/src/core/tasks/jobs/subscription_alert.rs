@@ -0,0 +1,163 @@
+use super::JobExt;
+use crate::{
+    config::{Config, ProfileMetaGetter, SubscriptionAlertKind},
+    core::{
+        handle::{Handle, Message, SubscriptionAlert},
+        tasks::{
+            executor::{AsyncJobExecutor, TaskExecutor},
+            task::{Task, TaskSchedule},
+        },
+    },
+    feat,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rust_i18n::t;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+const SUBSCRIPTION_ALERT_TASK_NAME: &str = "Subscription Alert";
+
+type ProfileUID = String;
+
+/// Watch the `subscription-userinfo` of the current remote profiles,
+/// and notify the user when the quota or the expiry is near the thresholds.
+#[derive(Clone, Default)]
+pub struct SubscriptionAlertJob {
+    /// the latest alert of each profile, used to avoid notifying the same state repeatedly
+    notified: Arc<Mutex<HashMap<ProfileUID, SubscriptionAlertKind>>>,
+}
+
+impl SubscriptionAlertJob {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn collect_alerts(&self) -> Vec<SubscriptionAlert> {
+        let config = Config::verge().latest().get_subscription_alert();
+        let mut notified = self.notified.lock();
+        if !config.enable {
+            // notify the pending states again once the alerts are re-enabled
+            notified.clear();
+            return Vec::new();
+        }
+        let now = chrono::Local::now().timestamp();
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let fallback = config
+            .fallback_profile
+            .filter(|uid| profiles.get_item(uid).is_ok());
+
+        // the states under the current thresholds, the profiles which are no longer current
+        // or no longer reach a threshold are forgotten
+        let previous = std::mem::take(&mut *notified);
+        profiles
+            .get_items()
+            .iter()
+            .filter_map(|item| item.as_remote())
+            .filter(|item| profiles.get_current().iter().any(|uid| uid == item.uid()))
+            .filter_map(|item| {
+                let kind = item.extra.evaluate(
+                    now,
+                    config.quota_threshold_percent,
+                    config.expire_threshold_days,
+                )?;
+                notified.insert(item.uid().to_string(), kind);
+                if previous.get(item.uid()) == Some(&kind) {
+                    return None;
+                }
+                Some(SubscriptionAlert {
+                    uid: item.uid().to_string(),
+                    name: item.name().to_string(),
+                    kind,
+                    info: item.extra,
+                    fallback: fallback
+                        .clone()
+                        .filter(|uid| kind == SubscriptionAlertKind::Expired && uid != item.uid()),
+                    switched: false,
+                })
+            })
+            .collect()
+    }
+}
+
+fn alert_body(alert: &SubscriptionAlert) -> String {
+    match alert.kind {
+        SubscriptionAlertKind::QuotaLow => {
+            let remaining = alert.info.remaining().unwrap_or_default();
+            t!(
+                "notification.subscription.quota_low",
+                name = alert.name,
+                remaining = humansize::format_size(remaining, humansize::BINARY)
+            )
+            .to_string()
+        }
+        SubscriptionAlertKind::QuotaExhausted => t!(
+            "notification.subscription.quota_exhausted",
+            name = alert.name
+        )
+        .to_string(),
+        SubscriptionAlertKind::ExpiringSoon => {
+            let expire = chrono::DateTime::from_timestamp(alert.info.expire as i64, 0)
+                .map(|time| {
+                    time.with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            t!(
+                "notification.subscription.expiring_soon",
+                name = alert.name,
+                expire = expire
+            )
+            .to_string()
+        }
+        SubscriptionAlertKind::Expired => {
+            t!("notification.subscription.expired", name = alert.name).to_string()
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncJobExecutor for SubscriptionAlertJob {
+    async fn execute(&self) -> Result<()> {
+        let auto_switch = Config::verge()
+            .latest()
+            .get_subscription_alert()
+            .auto_switch_fallback;
+        for mut alert in self.collect_alerts() {
+            log::warn!(target: "app", "subscription `{}` alert: {:?}", alert.uid, alert.kind);
+            if auto_switch && let Some(fallback) = alert.fallback.as_deref() {
+                match feat::switch_to_fallback_profile(&alert.uid, fallback).await {
+                    Ok(_) => alert.switched = true,
+                    Err(err) => {
+                        log::error!(target: "app", "failed to switch to the fallback profile: {err:?}");
+                    }
+                }
+            }
+            let mut body = alert_body(&alert);
+            if alert.switched {
+                body.push('\n');
+                body.push_str(&t!("notification.subscription.switched"));
+            }
+            Handle::notify_system(&t!("notification.subscription.title"), &body);
+            Handle::notice_message(&Message::SubscriptionAlert(alert));
+        }
+        Ok(())
+    }
+}
+
+impl JobExt for SubscriptionAlertJob {
+    fn name(&self) -> &'static str {
+        SUBSCRIPTION_ALERT_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        Some(Task {
+            name: SUBSCRIPTION_ALERT_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(10 * 60)),
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
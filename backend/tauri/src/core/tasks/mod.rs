@@ -28,6 +28,10 @@ pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(
         profiles_job.init()?;
     }
     app.manage(profiles_job);
+    // profile schedules job
+    let profile_schedules_job = jobs::ProfileSchedulesJobGuard::new(task_manager.clone());
+    profile_schedules_job.write().refresh();
+    app.manage(profile_schedules_job);
     app.manage(task_manager);
     Ok(())
 }
@@ -37,8 +37,7 @@ pub enum TaskRunResult {
 pub enum TaskSchedule {
     Once(Duration),     // 一次性执行
     Interval(Duration), // 按间隔执行
-    #[allow(dead_code)]
-    Cron(String), // 按 cron 表达式执行
+    Cron(String),       // 按 cron 表达式执行
 }
 
 impl Default for TaskSchedule {
@@ -0,0 +1,749 @@
+//! Historical traffic accounting.
+//! The traffic of the connections stream is aggregated in memory, and flushed into the storage
+//! every minute as minute, hour and day buckets, per proxy, rule, process and host.
+use crate::{
+    config::{Config, nyanpasu::TrafficHistoryConfig},
+    core::{
+        clash::ws::{ClashConnection, ClashConnectionsConnector, ClashConnectionsConnectorEvent},
+        storage::{Storage, StorageOperationError},
+    },
+};
+use parking_lot::Mutex;
+use redb::{ReadableDatabase, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::{collections::HashMap, fmt, result::Result as StdResult, sync::Arc, time::Duration};
+use tauri::Manager;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// `{granularity}:{dimension}:{bucket start}:{name}` -> json encoded [`TrafficBytes`]
+pub const TRAFFIC_HISTORY_TABLE: TableDefinition<&str, &[u8]> =
+    TableDefinition::new("traffic-history");
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// how long the bytes of a closed connection are remembered, in seconds.
+/// The connections are closed when the websocket is reconnected, and opened again once it is back
+const CLOSED_RETENTION: u64 = 10 * 60;
+
+type Result<T> = StdResult<T, StorageOperationError>;
+
+/// What the traffic is grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Type, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficDimension {
+    /// the traffic of the core, the name is always empty
+    Total,
+    /// the outbound proxy of the connections
+    Proxy,
+    /// the matched rule, e.g. `DomainSuffix(example.com)`
+    Rule,
+    Process,
+    /// the host, or the destination ip if the host is unknown
+    Host,
+}
+
+impl TrafficDimension {
+    pub const ALL: [Self; 5] = [
+        Self::Total,
+        Self::Proxy,
+        Self::Rule,
+        Self::Process,
+        Self::Host,
+    ];
+}
+
+impl fmt::Display for TrafficDimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Total => "total",
+            Self::Proxy => "proxy",
+            Self::Rule => "rule",
+            Self::Process => "process",
+            Self::Host => "host",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Type, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficGranularity {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl TrafficGranularity {
+    pub const ALL: [Self; 3] = [Self::Minute, Self::Hour, Self::Day];
+
+    pub fn seconds(&self) -> u64 {
+        match self {
+            Self::Minute => 60,
+            Self::Hour => 60 * 60,
+            Self::Day => 24 * 60 * 60,
+        }
+    }
+
+    /// the start of the bucket the timestamp falls in, the days are in UTC
+    pub fn bucket_start(&self, timestamp: u64) -> u64 {
+        timestamp - timestamp % self.seconds()
+    }
+
+    /// the minute buckets only record the total and the proxies, to keep the storage small
+    pub fn records(&self, dimension: TrafficDimension) -> bool {
+        !matches!(self, Self::Minute)
+            || matches!(dimension, TrafficDimension::Total | TrafficDimension::Proxy)
+    }
+
+    fn retention(&self, config: &TrafficHistoryConfig) -> u64 {
+        match self {
+            Self::Minute => config.minute_buckets,
+            Self::Hour => config.hour_buckets,
+            Self::Day => config.day_buckets,
+        }
+    }
+}
+
+impl fmt::Display for TrafficGranularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Minute => "minute",
+            Self::Hour => "hour",
+            Self::Day => "day",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Type, Serialize, Deserialize)]
+pub struct TrafficBytes {
+    pub up: u64,
+    pub down: u64,
+}
+
+impl TrafficBytes {
+    fn add(&mut self, other: TrafficBytes) {
+        self.up = self.up.saturating_add(other.up);
+        self.down = self.down.saturating_add(other.down);
+    }
+
+    fn total(&self) -> u64 {
+        self.up.saturating_add(self.down)
+    }
+
+    fn saturating_sub(&self, other: TrafficBytes) -> TrafficBytes {
+        TrafficBytes {
+            up: self.up.saturating_sub(other.up),
+            down: self.down.saturating_sub(other.down),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
+pub struct TrafficBucket {
+    /// the unix timestamp of the bucket start
+    pub start: u64,
+    pub name: String,
+    #[serde(flatten)]
+    pub bytes: TrafficBytes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Type, Serialize, Deserialize)]
+pub struct TrafficUsage {
+    pub name: String,
+    #[serde(flatten)]
+    pub bytes: TrafficBytes,
+}
+
+fn bucket_key(
+    granularity: TrafficGranularity,
+    dimension: TrafficDimension,
+    start: u64,
+    name: &str,
+) -> String {
+    // the start is padded, so that the keys are sorted by time
+    format!("{granularity}:{dimension}:{start:012}:{name}")
+}
+
+fn parse_bucket_key(key: &str) -> Option<(u64, &str)> {
+    let mut parts = key.splitn(4, ':');
+    let start = parts.nth(2)?.parse().ok()?;
+    Some((start, parts.next()?))
+}
+
+type PendingTraffic = HashMap<(u64, TrafficDimension, String), TrafficBytes>;
+
+/// The bytes of a connection when it is seen last time
+#[derive(Debug, Clone, Copy)]
+struct SeenConnection {
+    bytes: TrafficBytes,
+    /// the timestamp when it is closed
+    closed_at: Option<u64>,
+}
+
+/// The traffic not flushed yet, keyed by the minute it is recorded in
+#[derive(Debug, Default)]
+struct TrafficAccumulator {
+    pending: PendingTraffic,
+    /// the totals of the core in the last frame, `None` before the first frame
+    totals: Option<TrafficBytes>,
+    /// keyed by the connection id, only the delta is recorded when a connection is seen again
+    connections: HashMap<String, SeenConnection>,
+}
+
+impl TrafficAccumulator {
+    fn add(
+        &mut self,
+        timestamp: u64,
+        dimension: TrafficDimension,
+        name: String,
+        bytes: TrafficBytes,
+    ) {
+        if bytes.total() == 0 {
+            return;
+        }
+        let minute = TrafficGranularity::Minute.bucket_start(timestamp);
+        self.pending
+            .entry((minute, dimension, name))
+            .or_default()
+            .add(bytes);
+    }
+
+    fn add_connection(
+        &mut self,
+        timestamp: u64,
+        connection: &ClashConnection,
+        bytes: TrafficBytes,
+    ) {
+        let metadata = &connection.metadata;
+        let proxy = connection.chains.first().cloned().unwrap_or_default();
+        let rule = if connection.rule_payload.is_empty() {
+            connection.rule.clone()
+        } else {
+            format!("{}({})", connection.rule, connection.rule_payload)
+        };
+        let process = if metadata.process.is_empty() {
+            "unknown".to_string()
+        } else {
+            metadata.process.clone()
+        };
+        let host = if metadata.host.is_empty() {
+            metadata.destination_ip.clone()
+        } else {
+            metadata.host.clone()
+        };
+        self.add(timestamp, TrafficDimension::Proxy, proxy, bytes);
+        self.add(timestamp, TrafficDimension::Rule, rule, bytes);
+        self.add(timestamp, TrafficDimension::Process, process, bytes);
+        self.add(timestamp, TrafficDimension::Host, host, bytes);
+    }
+
+    fn record(&mut self, timestamp: u64, event: &ClashConnectionsConnectorEvent) {
+        match event {
+            ClashConnectionsConnectorEvent::Update(info) => {
+                let totals = TrafficBytes {
+                    up: info.upload_total,
+                    down: info.download_total,
+                };
+                // the traffic before the first frame is not known to be in this minute
+                if let Some(previous) = self.totals.replace(totals) {
+                    let bytes = totals.saturating_sub(previous);
+                    self.add(timestamp, TrafficDimension::Total, String::new(), bytes);
+                }
+            }
+            ClashConnectionsConnectorEvent::ConnectionsChanged(diff) => {
+                // the opened connections may carry the traffic before they are seen,
+                // e.g. the connections alive when the app starts, they are counted as a whole,
+                // unless they are seen before the websocket is reconnected
+                for tracked in diff.opened.iter().chain(diff.updated.iter()) {
+                    let connection = &tracked.connection;
+                    let bytes = TrafficBytes {
+                        up: connection.upload,
+                        down: connection.download,
+                    };
+                    let previous = self.connections.insert(
+                        connection.id.clone(),
+                        SeenConnection {
+                            bytes,
+                            closed_at: None,
+                        },
+                    );
+                    let delta = match previous {
+                        Some(previous) => bytes.saturating_sub(previous.bytes),
+                        None => bytes,
+                    };
+                    self.add_connection(timestamp, connection, delta);
+                }
+                for id in &diff.closed {
+                    if let Some(seen) = self.connections.get_mut(id) {
+                        seen.closed_at = Some(timestamp);
+                    }
+                }
+            }
+            ClashConnectionsConnectorEvent::StateChanged(_) => {}
+        }
+    }
+
+    /// take the pending traffic, and forget the connections closed long ago
+    fn take(&mut self, now: u64) -> PendingTraffic {
+        self.connections.retain(|_, seen| {
+            seen.closed_at
+                .is_none_or(|closed_at| now.saturating_sub(closed_at) < CLOSED_RETENTION)
+        });
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// The traffic buckets in the storage
+#[derive(Clone)]
+pub struct TrafficHistory {
+    storage: Storage,
+    pending: Arc<Mutex<TrafficAccumulator>>,
+}
+
+impl TrafficHistory {
+    pub fn try_new(storage: Storage) -> Result<Self> {
+        let write_txn = storage.get_instance().begin_write()?;
+        write_txn.open_table(TRAFFIC_HISTORY_TABLE)?;
+        write_txn.commit()?;
+        Ok(Self {
+            storage,
+            pending: Arc::default(),
+        })
+    }
+
+    /// write the traffic not flushed yet into the storage, e.g. before the app exits
+    pub fn flush_pending(&self) -> Result<()> {
+        let pending = self
+            .pending
+            .lock()
+            .take(chrono::Utc::now().timestamp() as u64);
+        self.flush(pending)
+    }
+
+    /// add the pending traffic into the buckets of every granularity
+    fn flush(&self, pending: PendingTraffic) -> Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let write_txn = self.storage.get_instance().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TRAFFIC_HISTORY_TABLE)?;
+            for ((minute, dimension, name), bytes) in pending {
+                for granularity in TrafficGranularity::ALL {
+                    if !granularity.records(dimension) {
+                        continue;
+                    }
+                    let key = bucket_key(
+                        granularity,
+                        dimension,
+                        granularity.bucket_start(minute),
+                        &name,
+                    );
+                    let mut value = match table.get(key.as_str())? {
+                        Some(value) => serde_json::from_slice::<TrafficBytes>(value.value())?,
+                        None => TrafficBytes::default(),
+                    };
+                    value.add(bytes);
+                    let value = serde_json::to_vec(&value)?;
+                    table.insert(key.as_str(), value.as_slice())?;
+                }
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// remove the buckets older than the retention
+    fn prune(&self, now: u64, config: &TrafficHistoryConfig) -> Result<usize> {
+        let mut removed = 0;
+        let write_txn = self.storage.get_instance().begin_write()?;
+        {
+            let mut table = write_txn.open_table(TRAFFIC_HISTORY_TABLE)?;
+            for granularity in TrafficGranularity::ALL {
+                let span = granularity
+                    .retention(config)
+                    .saturating_mul(granularity.seconds());
+                let cutoff = granularity.bucket_start(now).saturating_sub(span);
+                for dimension in TrafficDimension::ALL {
+                    let start = bucket_key(granularity, dimension, 0, "");
+                    let end = bucket_key(granularity, dimension, cutoff, "");
+                    let keys = table
+                        .range(start.as_str()..end.as_str())?
+                        .map(|entry| entry.map(|(key, _)| key.value().to_string()))
+                        .collect::<StdResult<Vec<_>, _>>()?;
+                    for key in keys {
+                        table.remove(key.as_str())?;
+                        removed += 1;
+                    }
+                }
+            }
+        }
+        write_txn.commit()?;
+        Ok(removed)
+    }
+
+    /// the buckets of the dimension in `[from, to)`, sorted by the start
+    pub fn query(
+        &self,
+        dimension: TrafficDimension,
+        granularity: TrafficGranularity,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<TrafficBucket>> {
+        let read_txn = self.storage.get_instance().begin_read()?;
+        let table = read_txn.open_table(TRAFFIC_HISTORY_TABLE)?;
+        let start = bucket_key(granularity, dimension, granularity.bucket_start(from), "");
+        let end = bucket_key(granularity, dimension, to, "");
+        let mut buckets = Vec::new();
+        for entry in table.range(start.as_str()..end.as_str())? {
+            let (key, value) = entry?;
+            let Some((start, name)) = parse_bucket_key(key.value()) else {
+                continue;
+            };
+            buckets.push(TrafficBucket {
+                start,
+                name: name.to_string(),
+                bytes: serde_json::from_slice(value.value())?,
+            });
+        }
+        Ok(buckets)
+    }
+
+    /// the names of the dimension using the most traffic in the last `range` seconds
+    pub fn top(
+        &self,
+        dimension: TrafficDimension,
+        now: u64,
+        range: u64,
+        limit: usize,
+        config: &TrafficHistoryConfig,
+    ) -> Result<Vec<TrafficUsage>> {
+        // the finest granularity which still keeps the whole range
+        let granularity = TrafficGranularity::ALL
+            .into_iter()
+            .find(|granularity| {
+                granularity.records(dimension)
+                    && granularity
+                        .retention(config)
+                        .saturating_mul(granularity.seconds())
+                        >= range
+            })
+            .unwrap_or(TrafficGranularity::Day);
+        let to = granularity.bucket_start(now) + granularity.seconds();
+        let mut usage: HashMap<String, TrafficBytes> = HashMap::new();
+        for bucket in self.query(dimension, granularity, now.saturating_sub(range), to)? {
+            usage.entry(bucket.name).or_default().add(bucket.bytes);
+        }
+        let mut usage = usage
+            .into_iter()
+            .map(|(name, bytes)| TrafficUsage { name, bytes })
+            .collect::<Vec<_>>();
+        usage.sort_by(|a, b| {
+            b.bytes
+                .total()
+                .cmp(&a.bytes.total())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        usage.truncate(limit);
+        Ok(usage)
+    }
+
+    async fn run(self, mut rx: broadcast::Receiver<ClashConnectionsConnectorEvent>) {
+        let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) => {
+                        if Config::verge().latest().get_traffic_history().enable {
+                            self.pending
+                                .lock()
+                                .record(chrono::Utc::now().timestamp() as u64, &event);
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!(target: "app", "traffic history skipped {skipped} events");
+                    }
+                    Err(RecvError::Closed) => {
+                        let history = self.clone();
+                        if let Ok(Err(err)) =
+                            tokio::task::spawn_blocking(move || history.flush_pending()).await
+                        {
+                            log::error!(target: "app", "failed to flush traffic history: {err}");
+                        }
+                        break;
+                    }
+                },
+                _ = interval.tick() => {
+                    let config = Config::verge().latest().get_traffic_history();
+                    let history = self.clone();
+                    // redb is blocking
+                    let result = tokio::task::spawn_blocking(move || {
+                        history.flush_pending()?;
+                        history.prune(chrono::Utc::now().timestamp() as u64, &config)
+                    })
+                    .await;
+                    match result {
+                        Ok(Ok(removed)) if removed > 0 => {
+                            log::debug!(target: "app", "pruned {removed} traffic history buckets");
+                        }
+                        Ok(Ok(_)) => {}
+                        Ok(Err(err)) => {
+                            log::error!(target: "app", "failed to flush traffic history: {err}");
+                        }
+                        Err(err) => {
+                            log::error!(target: "app", "traffic history flush panicked: {err}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn setup<R: tauri::Runtime, M: tauri::Manager<R>>(manager: &M) -> anyhow::Result<()> {
+    let storage = manager
+        .try_state::<Storage>()
+        .ok_or_else(|| anyhow::anyhow!("the storage is not initialized"))?
+        .inner()
+        .clone();
+    let history = TrafficHistory::try_new(storage)?;
+    let rx = manager.state::<ClashConnectionsConnector>().subscribe();
+    tauri::async_runtime::spawn(history.clone().run(rx));
+    manager.manage(history);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::clash::ws::{
+        ClashConnectionMetadata, ClashConnectionsDiff, ClashConnectionsInfo, TrackedConnection,
+    };
+    use pretty_assertions::assert_eq;
+
+    fn history() -> (tempfile::TempDir, TrafficHistory) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::try_new(&dir.path().join("storage.db")).unwrap();
+        (dir, TrafficHistory::try_new(storage).unwrap())
+    }
+
+    fn tracked(id: &str, host: &str, proxy: &str, upload: u64, download: u64) -> TrackedConnection {
+        TrackedConnection {
+            connection: ClashConnection {
+                id: id.to_string(),
+                metadata: ClashConnectionMetadata {
+                    host: host.to_string(),
+                    destination_ip: "1.1.1.1".to_string(),
+                    ..Default::default()
+                },
+                upload,
+                download,
+                start: String::new(),
+                chains: vec![proxy.to_string(), "PROXY".to_string()],
+                rule: "Match".to_string(),
+                rule_payload: String::new(),
+            },
+            download_speed: 0,
+            upload_speed: 0,
+        }
+    }
+
+    #[test]
+    fn test_accumulate_connections() {
+        let mut pending = TrafficAccumulator::default();
+        let changed = |opened, updated, closed: Vec<&str>| {
+            ClashConnectionsConnectorEvent::ConnectionsChanged(ClashConnectionsDiff {
+                opened,
+                updated,
+                closed: closed.into_iter().map(str::to_string).collect(),
+            })
+        };
+        let totals = |upload_total, download_total| {
+            ClashConnectionsConnectorEvent::Update(ClashConnectionsInfo {
+                upload_total,
+                download_total,
+                upload_speed: upload_total,
+                download_speed: download_total,
+            })
+        };
+        // the totals before the first frame are not recorded
+        pending.record(121, &totals(100, 1000));
+        pending.record(
+            122,
+            &changed(
+                vec![
+                    tracked("1", "example.com", "hk", 20, 200),
+                    tracked("2", "", "jp", 1, 2),
+                ],
+                vec![],
+                vec![],
+            ),
+        );
+        pending.record(
+            125,
+            &changed(
+                vec![],
+                vec![tracked("1", "example.com", "hk", 30, 300)],
+                vec![],
+            ),
+        );
+        pending.record(126, &totals(111, 1102));
+        // the websocket is reconnected, the connections are closed and opened again
+        pending.record(127, &changed(vec![], vec![], vec!["1", "2"]));
+        pending.record(
+            128,
+            &changed(
+                vec![tracked("1", "example.com", "hk", 35, 350)],
+                vec![],
+                vec![],
+            ),
+        );
+
+        let traffic = pending.take(130);
+        let get = |dimension, name: &str| traffic.get(&(120, dimension, name.to_string())).copied();
+        assert_eq!(
+            get(TrafficDimension::Total, ""),
+            Some(TrafficBytes { up: 11, down: 102 })
+        );
+        assert_eq!(
+            get(TrafficDimension::Host, "example.com"),
+            Some(TrafficBytes { up: 35, down: 350 })
+        );
+        // falls back to the destination ip
+        assert_eq!(
+            get(TrafficDimension::Host, "1.1.1.1"),
+            Some(TrafficBytes { up: 1, down: 2 })
+        );
+        assert_eq!(
+            get(TrafficDimension::Rule, "Match"),
+            Some(TrafficBytes { up: 36, down: 352 })
+        );
+        assert_eq!(
+            get(TrafficDimension::Process, "unknown"),
+            Some(TrafficBytes { up: 36, down: 352 })
+        );
+        assert_eq!(
+            get(TrafficDimension::Proxy, "jp"),
+            Some(TrafficBytes { up: 1, down: 2 })
+        );
+
+        // the closed connections are forgotten after a while
+        pending.take(127 + CLOSED_RETENTION);
+        assert!(pending.connections.contains_key("1"));
+        assert!(!pending.connections.contains_key("2"));
+    }
+
+    #[test]
+    fn test_flush_and_query() {
+        let (_dir, history) = history();
+        let hour = TrafficGranularity::Hour.seconds();
+        let mut pending = TrafficAccumulator::default();
+        let bytes = TrafficBytes { up: 1, down: 10 };
+        pending.add(60, TrafficDimension::Host, "a.com".to_string(), bytes);
+        pending.add(120, TrafficDimension::Host, "a.com".to_string(), bytes);
+        pending.add(
+            hour + 60,
+            TrafficDimension::Host,
+            "b.com".to_string(),
+            bytes,
+        );
+        pending.add(60, TrafficDimension::Proxy, "hk".to_string(), bytes);
+        history.flush(pending.take(0)).unwrap();
+
+        // the hosts are not recorded by minute
+        let minutes = history
+            .query(
+                TrafficDimension::Host,
+                TrafficGranularity::Minute,
+                0,
+                hour * 2,
+            )
+            .unwrap();
+        assert!(minutes.is_empty());
+        let minutes = history
+            .query(
+                TrafficDimension::Proxy,
+                TrafficGranularity::Minute,
+                0,
+                hour * 2,
+            )
+            .unwrap();
+        assert_eq!(minutes.len(), 1);
+
+        let hours = history
+            .query(
+                TrafficDimension::Host,
+                TrafficGranularity::Hour,
+                0,
+                hour * 2,
+            )
+            .unwrap();
+        assert_eq!(
+            hours,
+            vec![
+                TrafficBucket {
+                    start: 0,
+                    name: "a.com".to_string(),
+                    bytes: TrafficBytes { up: 2, down: 20 },
+                },
+                TrafficBucket {
+                    start: hour,
+                    name: "b.com".to_string(),
+                    bytes,
+                },
+            ]
+        );
+        // the end is exclusive
+        let hours = history
+            .query(TrafficDimension::Host, TrafficGranularity::Hour, 0, hour)
+            .unwrap();
+        assert_eq!(hours.len(), 1);
+
+        let config = TrafficHistoryConfig::default();
+        let top = history
+            .top(TrafficDimension::Host, hour + 60, 7 * 24 * hour, 1, &config)
+            .unwrap();
+        assert_eq!(
+            top,
+            vec![TrafficUsage {
+                name: "a.com".to_string(),
+                bytes: TrafficBytes { up: 2, down: 20 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_prune() {
+        let (_dir, history) = history();
+        let day = TrafficGranularity::Day.seconds();
+        let mut pending = TrafficAccumulator::default();
+        let bytes = TrafficBytes { up: 1, down: 1 };
+        pending.add(0, TrafficDimension::Total, String::new(), bytes);
+        pending.add(day * 2, TrafficDimension::Total, String::new(), bytes);
+        history.flush(pending.take(0)).unwrap();
+
+        let config = TrafficHistoryConfig {
+            minute_buckets: 60,
+            hour_buckets: 24,
+            day_buckets: 1,
+            ..Default::default()
+        };
+        // the minute, hour and day buckets of the first day
+        assert_eq!(history.prune(day * 2 + 60, &config).unwrap(), 3);
+        let days = history
+            .query(TrafficDimension::Total, TrafficGranularity::Day, 0, day * 3)
+            .unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].start, day * 2);
+        let minutes = history
+            .query(
+                TrafficDimension::Total,
+                TrafficGranularity::Minute,
+                0,
+                day * 3,
+            )
+            .unwrap();
+        assert_eq!(minutes.len(), 1);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::{
     config::{Config, nyanpasu::ProxiesSelectorMode},
     core::{
-        clash::proxies::{Proxies, ProxiesGuard, ProxiesGuardExt},
+        clash::proxies::{Proxies, ProxiesEvent, ProxiesGuard, ProxiesGuardExt},
         handle::Handle,
     },
 };
 use anyhow::Context;
 use indexmap::IndexMap;
+use std::sync::Arc;
 use tauri::{AppHandle, Manager, Runtime, menu::MenuBuilder};
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{debug, error, warn};
 use tracing_attributes::instrument;
 
@@ -97,59 +99,44 @@ fn to_tray_proxies(mode: &str, raw_proxies: &Proxies) -> TrayProxies {
     tray_proxies
 }
 
-fn diff_proxies(old_proxies: &TrayProxies, new_proxies: &TrayProxies) -> TrayUpdateType {
-    // 1. check if the length of two map is different
-    if old_proxies.len() != new_proxies.len() {
-        return TrayUpdateType::Full;
-    }
-    // 2. check if the group matching
-    let group_matching = new_proxies
-        .keys()
-        .cloned()
-        .collect::<Vec<String>>()
-        .iter()
-        .zip(&old_proxies.keys().cloned().collect::<Vec<String>>())
-        .filter(|&(new, old)| new == old)
-        .count();
-    if group_matching != old_proxies.len() {
-        return TrayUpdateType::Full;
-    }
-    // 3. start checking the group content
+/// the tray update for the events, the selections are applied to `tray_proxies`
+fn tray_update(events: &[ProxiesEvent], tray_proxies: &mut TrayProxies) -> TrayUpdateType {
     let mut actions = Vec::new();
-    for (group, item) in new_proxies.iter() {
-        let Some(old_item) = old_proxies.get(group) else {
-            warn!("Group {} not found in old proxies, skipping", group);
-            continue;
-        };
-
-        // check if the length of all list is different
-        if item.all.len() != old_item.all.len() {
-            return TrayUpdateType::Full;
-        }
-
-        // first diff the all list
-        let all_matching = item
-            .all
-            .iter()
-            .zip(&old_item.all)
-            .filter(|&(new, old)| new == old)
-            .count();
-        if all_matching != old_item.all.len() {
-            return TrayUpdateType::Full;
-        }
-        // then diff the current
-        if item.current != old_item.current {
-            match (&old_item.current, &item.current) {
-                (Some(old_current), Some(new_current)) => {
-                    actions.push((group.clone(), old_current.clone(), new_current.clone()));
-                }
-                _ => {
-                    warn!(
-                        "Cannot update proxy for group {}: current proxy is None",
-                        group
-                    );
+    for event in events {
+        match event {
+            ProxiesEvent::Reset => return TrayUpdateType::Full,
+            // the order of the groups follows the members of `GLOBAL`
+            ProxiesEvent::GroupMembersChanged { group, .. }
+                if group == "GLOBAL" || tray_proxies.contains_key(group) =>
+            {
+                return TrayUpdateType::Full;
+            }
+            ProxiesEvent::ProxyRemoved { name } if tray_proxies.contains_key(name) => {
+                return TrayUpdateType::Full;
+            }
+            ProxiesEvent::SelectionChanged { group, from, to } => {
+                let key = if group == "GLOBAL" {
+                    "global"
+                } else {
+                    group.as_str()
+                };
+                let Some(item) = tray_proxies.get_mut(key) else {
+                    continue;
+                };
+                match (from, to) {
+                    (Some(from), Some(to)) => {
+                        item.current = Some(to.clone());
+                        actions.push((key.to_string(), from.clone(), to.clone()));
+                    }
+                    _ => {
+                        warn!(
+                            "Cannot update proxy for group {}: current proxy is None",
+                            group
+                        );
+                    }
                 }
             }
+            _ => {}
         }
     }
     if actions.is_empty() {
@@ -161,66 +148,77 @@ fn diff_proxies(old_proxies: &TrayProxies, new_proxies: &TrayProxies) -> TrayUpd
 
 #[instrument]
 pub async fn proxies_updated_receiver() {
-    let (mut rx, mut tray_proxies_holder) = {
+    let (mut rx, mut tray_mode, mut tray_proxies_holder) = {
         let guard = ProxiesGuard::global().read();
         let proxies = guard.inner().to_owned();
         let mode = crate::utils::config::get_current_clash_mode();
-        (
-            guard.get_receiver(),
-            to_tray_proxies(mode.as_str(), &proxies),
-        )
+        let tray_proxies = to_tray_proxies(mode.as_str(), &proxies);
+        (guard.get_receiver(), mode, tray_proxies)
     };
 
     loop {
-        match rx.recv().await {
-            Ok(_) => {
-                debug!("proxies updated");
-                if Handle::global().app_handle.lock().is_none() {
-                    warn!("app handle not found");
-                    continue;
-                }
-                Handle::mutate_proxies();
-                {
-                    let is_tray_selector_enabled = Config::verge()
-                        .latest()
-                        .clash_tray_selector
-                        .unwrap_or_default()
-                        != ProxiesSelectorMode::Hidden;
-                    if !is_tray_selector_enabled {
-                        continue;
-                    }
-                }
-                // Do diff check
-                let mode = crate::utils::config::get_current_clash_mode();
-                let current_tray_proxies =
-                    to_tray_proxies(mode.as_str(), ProxiesGuard::global().read().inner());
+        let events = match rx.recv().await {
+            Ok(events) => events,
+            // some updates are missed, reload the whole proxies
+            Err(RecvError::Lagged(count)) => {
+                warn!("proxies updated receiver lagged {} updates", count);
+                Arc::new(vec![ProxiesEvent::Reset])
+            }
+            Err(RecvError::Closed) => {
+                warn!("proxies updated channel is closed, stop receiving");
+                break;
+            }
+        };
+        debug!("proxies updated: {:?}", events);
+        if Handle::global().app_handle.lock().is_none() {
+            warn!("app handle not found");
+            continue;
+        }
+        // the frontend still reloads the whole proxies until it consumes `proxies-events`
+        Handle::mutate_proxies();
+        if let Err(e) = Handle::emit("proxies-events", events.as_slice()) {
+            warn!("emit proxies events failed: {:?}", e);
+        }
+        {
+            let is_tray_selector_enabled = Config::verge()
+                .latest()
+                .clash_tray_selector
+                .unwrap_or_default()
+                != ProxiesSelectorMode::Hidden;
+            if !is_tray_selector_enabled {
+                continue;
+            }
+        }
+        // the tray is rebuilt if the mode is changed since the last update
+        let mode = crate::utils::config::get_current_clash_mode();
+        let update = if mode != tray_mode {
+            TrayUpdateType::Full
+        } else {
+            tray_update(&events, &mut tray_proxies_holder)
+        };
 
-                match diff_proxies(&tray_proxies_holder, &current_tray_proxies) {
-                    TrayUpdateType::Full => {
-                        debug!("should do full update");
-
-                        tray_proxies_holder = current_tray_proxies;
-                        match Handle::emit("update_systray", ()) {
-                            Ok(_) => {
-                                debug!("update systray success");
-                            }
-                            Err(e) => {
-                                warn!("update systray failed: {:?}", e);
-                            }
-                        }
+        match update {
+            TrayUpdateType::Full => {
+                debug!("should do full update");
+
+                tray_proxies_holder =
+                    to_tray_proxies(mode.as_str(), ProxiesGuard::global().read().inner());
+                tray_mode = mode;
+                match Handle::emit("update_systray", ()) {
+                    Ok(_) => {
+                        debug!("update systray success");
                     }
-                    TrayUpdateType::Part(action_list) => {
-                        debug!("should do partial update, op list: {:?}", action_list);
-                        tray_proxies_holder = current_tray_proxies;
-                        platform_impl::update_selected_proxies(&action_list);
-                        debug!("update selected proxies success");
+                    Err(e) => {
+                        warn!("update systray failed: {:?}", e);
                     }
-                    _ => {}
                 }
             }
-            Err(e) => {
-                warn!("proxies updated receiver failed: {:?}", e);
+            TrayUpdateType::Part(action_list) => {
+                debug!("should do partial update, op list: {:?}", action_list);
+                platform_impl::update_selected_proxies(&action_list);
+                debug!("update selected proxies success");
             }
+            _ => {}
         }
     }
 }
@@ -16,7 +16,7 @@ use serde_yaml::Mapping;
 use std::fs;
 use strum::EnumString;
 
-use super::Logs;
+use super::{Logs, variables::Variables};
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, specta::Type)]
 /// 后处理输出
@@ -34,6 +34,8 @@ pub struct PostProcessingOutput {
 pub struct ChainItem {
     pub uid: String,
     pub data: ChainTypeWrapper,
+    /// the variables of the profile item
+    pub variables: Variables,
 }
 
 #[derive(Debug, Clone)]
@@ -90,7 +92,11 @@ impl TryFrom<&Profile> for ChainItem {
     fn try_from(item: &Profile) -> Result<Self, Self::Error> {
         let uid = item.uid().to_string();
         let data = ChainTypeWrapper::try_from(item)?;
-        Ok(Self { uid, data })
+        Ok(Self {
+            uid,
+            data,
+            variables: item.variables().clone(),
+        })
     }
 }
 
@@ -100,7 +106,11 @@ impl From<&Profile> for Option<ChainItem> {
         let data = ChainTypeWrapper::try_from(item);
         match data {
             Err(_) => None,
-            Ok(data) => Some(ChainItem { uid, data }),
+            Ok(data) => Some(ChainItem {
+                uid,
+                data,
+                variables: item.variables().clone(),
+            }),
         }
     }
 }
@@ -173,6 +183,7 @@ impl ChainItem {
         Self {
             uid: uid.into(),
             data: data.into(),
+            variables: Variables::new(),
         }
     }
 }
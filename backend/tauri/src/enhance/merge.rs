@@ -1,5 +1,5 @@
 use super::{Logs, LogsExt, runner::ProcessOutput};
-use mlua::LuaSerdeExt;
+use mlua::{Lua, LuaSerdeExt};
 use serde::de::DeserializeOwned;
 use serde_yaml::{Mapping, Value};
 use tracing_attributes::instrument;
@@ -7,9 +7,7 @@ use tracing_attributes::instrument;
 // Override recursive, and if the value is sequence, it should be append to the end.
 fn override_recursive(config: &mut Mapping, key: &Value, data: Value) {
     if let Some(value) = config.get_mut(key) {
-        if value.is_mapping() {
-            let value = value.as_mapping_mut().unwrap();
-            let data = data.as_mapping().unwrap();
+        if let (Some(value), Some(data)) = (value.as_mapping_mut(), data.as_mapping()) {
             for (k, v) in data.iter() {
                 override_recursive(value, k, v.clone());
             }
@@ -59,14 +57,12 @@ fn merge_sequence(target: &mut Value, to_merge: &Value, append: bool) {
     }
 }
 
-fn run_expr<T: DeserializeOwned>(logs: &mut Logs, item: &Value, expr: &str) -> Option<T> {
-    let lua_runtime = match super::script::create_lua_context() {
-        Ok(lua) => lua,
-        Err(e) => {
-            logs.error(e.to_string());
-            return None;
-        }
-    };
+fn run_expr<T: DeserializeOwned>(
+    logs: &mut Logs,
+    lua_runtime: &Lua,
+    item: &Value,
+    expr: &str,
+) -> Option<T> {
     let item = match lua_runtime.to_value(item) {
         Ok(v) => v,
         Err(e) => {
@@ -96,7 +92,7 @@ fn run_expr<T: DeserializeOwned>(logs: &mut Logs, item: &Value, expr: &str) -> O
     }
 }
 
-fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Value) {
+fn do_filter(logs: &mut Logs, lua: &Lua, config: &mut Value, field_str: &str, filter: &Value) {
     let field = match find_field(config, field_str) {
         Some(field) if !field.is_sequence() => {
             logs.warn(format!("field is not sequence: {field_str:#?}"));
@@ -111,12 +107,12 @@ fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Valu
     match filter {
         Value::Sequence(filters) => {
             for filter in filters {
-                do_filter(logs, config, field_str, filter);
+                do_filter(logs, lua, config, field_str, filter);
             }
         }
         Value::String(filter) => {
             let list = field.as_sequence_mut().unwrap();
-            list.retain(|item| run_expr(logs, item, filter).unwrap_or(false));
+            list.retain(|item| run_expr(logs, lua, item, filter).unwrap_or(false));
         }
         Value::Mapping(filter)
             if filter.get("when").is_some_and(|v| v.is_string())
@@ -126,9 +122,9 @@ fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Valu
             let expr = filter.get("expr").unwrap().as_str().unwrap();
             let list = field.as_sequence_mut().unwrap();
             list.iter_mut().for_each(|item| {
-                let r#match = run_expr(logs, item, when);
+                let r#match = run_expr(logs, lua, item, when);
                 if r#match.unwrap_or(false) {
-                    let res: Option<Value> = run_expr(logs, item, expr);
+                    let res: Option<Value> = run_expr(logs, lua, item, expr);
                     if let Some(res) = res {
                         *item = res;
                     }
@@ -143,7 +139,7 @@ fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Valu
             let r#override = filter.get("override").unwrap();
             let list = field.as_sequence_mut().unwrap();
             list.iter_mut().for_each(|item| {
-                let r#match = run_expr(logs, item, when);
+                let r#match = run_expr(logs, lua, item, when);
                 if r#match.unwrap_or(false) {
                     *item = r#override.clone();
                 }
@@ -157,7 +153,7 @@ fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Valu
             let merge = filter.get("merge").unwrap().as_mapping().unwrap();
             let list = field.as_sequence_mut().unwrap();
             list.iter_mut().for_each(|item| {
-                let r#match = run_expr(logs, item, when);
+                let r#match = run_expr(logs, lua, item, when);
                 if r#match.unwrap_or(false) {
                     for (key, value) in merge.iter() {
                         let item = item.as_mapping_mut().unwrap();
@@ -179,7 +175,7 @@ fn do_filter(logs: &mut Logs, config: &mut Value, field_str: &str, filter: &Valu
             let remove = filter.get("remove").unwrap().as_sequence().unwrap();
             let list = field.as_sequence_mut().unwrap();
             list.iter_mut().for_each(|item| {
-                let r#match = run_expr(logs, item, when);
+                let r#match = run_expr(logs, lua, item, when);
                 if r#match.unwrap_or(false) {
                     remove.iter().for_each(|key| {
                         if key.is_string() && item.is_mapping() {
@@ -244,6 +240,8 @@ pub fn use_merge(merge: &Mapping, mut config: Mapping) -> ProcessOutput {
     tracing::trace!("merge: {:#?}", merge);
     let mut logs = Logs::new();
     let mut map = Value::from(config);
+    // the lua context is created on the first filter, and shared by the items of the pass
+    let mut lua = None;
     for (key, value) in merge.iter() {
         let key_str = key.as_str().unwrap_or_default().to_lowercase();
         match key_str {
@@ -304,7 +302,16 @@ pub fn use_merge(merge: &Mapping, mut config: Mapping) -> ProcessOutput {
             }
             key_str if key_str.starts_with("filter__") => {
                 let key_str = key_str.replace("filter__", "");
-                do_filter(&mut logs, &mut map, &key_str, value);
+                if lua.is_none() {
+                    match super::script::create_lua_context() {
+                        Ok(context) => lua = Some(context),
+                        Err(e) => {
+                            logs.error(e.to_string());
+                            continue;
+                        }
+                    }
+                }
+                do_filter(&mut logs, lua.as_ref().unwrap(), &mut map, &key_str, value);
                 continue;
             }
             _ => {
@@ -1,20 +1,29 @@
 mod chain;
 mod field;
 mod merge;
+mod provider_mirror;
 mod script;
 mod tun;
 mod utils;
+mod variables;
 
 pub use self::chain::ScriptType;
 use self::{chain::*, field::*, merge::*, script::*, tun::*};
-use crate::config::{Config, ProfileMetaGetter, nyanpasu::ClashCore};
+use crate::{
+    config::{Config, ProfileMetaGetter, RemoteProfileOptions, nyanpasu::ClashCore},
+    core::provider_mirror::ProviderMirror,
+};
 pub use chain::PostProcessingOutput;
 use futures::future::join_all;
 use indexmap::IndexMap;
+pub use merge::use_merge;
+pub(crate) use provider_mirror::mirrored_key;
+use provider_mirror::{MirrorUsage, use_provider_mirror};
 use serde_yaml::{Mapping, Value};
 use std::collections::HashSet;
 pub use utils::{Logs, LogsExt};
 use utils::{merge_profiles, process_chain};
+use variables::VariableScope;
 
 /// Enhance mode
 /// 返回最终配置、该配置包含的键、和script执行的结果
@@ -22,7 +31,14 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     // config.yaml 的配置
     let clash_config = { Config::clash().latest().0.clone() };
 
-    let (clash_core, enable_tun, enable_builtin, enable_filter) = {
+    let (
+        clash_core,
+        enable_tun,
+        enable_builtin,
+        enable_filter,
+        variable_overrides,
+        provider_mirror,
+    ) = {
         let verge = Config::verge();
         let verge = verge.latest();
         (
@@ -30,14 +46,32 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
             verge.enable_tun_mode.unwrap_or(false),
             verge.enable_builtin_enhanced.unwrap_or(true),
             verge.enable_clash_fields.unwrap_or(true),
+            verge.profile_variables.clone().unwrap_or_default(),
+            verge.get_provider_mirror(),
         )
     };
 
     // 从profiles里拿东西
-    let (profiles, profile_chain, global_chain, valid) = {
+    let (
+        profiles,
+        profile_chain,
+        global_chain,
+        valid,
+        global_scope,
+        profile_scopes,
+        profile_options,
+    ) = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
 
+        let global_scope = VariableScope::new(profiles.variables.clone(), variable_overrides);
+        let profile_scopes = profiles
+            .get_current()
+            .iter()
+            .filter_map(|uid| profiles.get_item(uid).ok())
+            .map(|item| (item.uid().to_string(), global_scope.with(item.variables())))
+            .collect::<IndexMap<_, _>>();
+
         let profile_chain_mapping = profiles
             .get_current()
             .iter()
@@ -67,11 +101,28 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
             .map(|(k, v)| (k.to_string(), v))
             .collect::<IndexMap<_, _>>();
 
+        // the providers are downloaded with the same options as the subscription
+        let profile_options = profiles
+            .get_current()
+            .iter()
+            .filter_map(|uid| profiles.get_item(uid).ok())
+            .filter_map(|item| item.as_remote())
+            .map(|item| (item.uid().to_string(), item.option.clone()))
+            .collect::<IndexMap<_, _>>();
+
         let global_chain = utils::convert_uids_to_scripts(&profiles, &profiles.chain);
 
         let valid = profiles.valid.clone();
 
-        (current_mappings, profile_chain_mapping, global_chain, valid)
+        (
+            current_mappings,
+            profile_chain_mapping,
+            global_chain,
+            valid,
+            global_scope,
+            profile_scopes,
+            profile_options,
+        )
     };
 
     let mut postprocessing_output = PostProcessingOutput::default();
@@ -81,7 +132,8 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     // 执行 scoped chain
     let profiles_outputs = join_all(profiles.into_iter().map(|(uid, mapping)| async {
         let chain = profile_chain.get(&uid).map_or(&[] as &[_], |v| v);
-        let output = process_chain(mapping, chain).await;
+        let scope = profile_scopes.get(&uid).unwrap_or(&global_scope);
+        let output = process_chain(mapping, chain, scope).await;
         (uid, output)
     }))
     .await;
@@ -92,15 +144,60 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
         profiles.insert(uid.to_string(), config);
     }
 
+    let mut mirror_logs = Logs::new();
+    let mut mirror_usage = MirrorUsage::new();
+    let mirror = if provider_mirror.enable {
+        ProviderMirror::global()
+            .inspect_err(|e| log::error!(target: "app", "failed to init provider mirror: {e:?}"))
+            .ok()
+    } else {
+        None
+    };
+    if let Some(mirror) = mirror {
+        for (uid, config) in profiles.iter_mut() {
+            let option = profile_options.get(uid).cloned().unwrap_or_default();
+            let (mirrored, logs) = use_provider_mirror(
+                std::mem::take(config),
+                mirror,
+                &provider_mirror,
+                Some(uid.as_str()),
+                &option,
+                &mut mirror_usage,
+            )
+            .await;
+            *config = mirrored;
+            mirror_logs.extend(logs);
+        }
+    }
+
     // 合并多个配置
     // TODO: 此步骤需要提供针对每个配置的 Meta 信息
     // TODO: 需要支持自定义合并逻辑
     let config = merge_profiles(profiles);
 
     // 执行全局 chain
-    let (mut config, global_chain_output) = process_chain(config, &global_chain).await;
+    let (mut config, global_chain_output) =
+        process_chain(config, &global_chain, &global_scope).await;
     postprocessing_output.global = global_chain_output;
 
+    // the providers added by the global chain
+    if let Some(mirror) = mirror {
+        let (mirrored, logs) = use_provider_mirror(
+            config,
+            mirror,
+            &provider_mirror,
+            None,
+            &RemoteProfileOptions::default(),
+            &mut mirror_usage,
+        )
+        .await;
+        config = mirrored;
+        mirror_logs.extend(logs);
+        if let Err(e) = mirror.retain(&mirror_usage) {
+            log::error!(target: "app", "failed to clean up provider mirrors: {e:?}");
+        }
+    }
+
     // 记录当前配置包含的键
     let mut exists_keys = use_keys(&config);
     config = use_whitelist_fields_filter(config, &valid, enable_filter);
@@ -128,7 +225,7 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
 
             if let ChainTypeWrapper::Script(script) = item.data {
                 let (res, _) = script_runner
-                    .process_script(&script, config.to_owned())
+                    .process_script(&script, config.to_owned(), &Default::default())
                     .await;
                 match res {
                     Ok(res_config) => {
@@ -148,8 +245,7 @@ pub async fn enhance() -> (Mapping, Vec<String>, PostProcessingOutput) {
     config = use_cache(config);
     config = use_sort(config, enable_filter);
 
-    let logs = Vec::new(); // Simplified - no advice in extreme cleanup version
-    postprocessing_output.advice = logs;
+    postprocessing_output.advice = mirror_logs;
 
     let mut exists_set = HashSet::new();
     exists_set.extend(exists_keys.into_iter().filter(|s| clash_fields.contains(s)));
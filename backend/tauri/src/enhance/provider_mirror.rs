@@ -0,0 +1,291 @@
+//! Rewrite the http providers to their local mirrors
+use super::{Logs, LogsExt};
+use crate::{
+    config::{
+        RemoteProfileOptions, SensitiveString,
+        nyanpasu::{ProviderMirrorConfig, ProviderMirrorMode},
+    },
+    core::provider_mirror::{MirrorEntry, ProviderKind, ProviderMirror},
+};
+use indexmap::{IndexMap, IndexSet};
+use serde_yaml::{Mapping, Value};
+use url::Url;
+
+/// key of the mirror -> names of the providers
+pub type MirrorUsage = IndexMap<String, IndexSet<String>>;
+
+/// the `header` of a provider is sent along with the headers of the profile
+fn provider_option(provider: &Mapping, option: &RemoteProfileOptions) -> RemoteProfileOptions {
+    let mut option = option.clone();
+    if let Some(headers) = provider.get("header").and_then(Value::as_mapping) {
+        for (name, values) in headers {
+            let value = match values {
+                Value::Sequence(values) => values.first().and_then(Value::as_str),
+                value => value.as_str(),
+            };
+            if let (Some(name), Some(value)) = (name.as_str(), value) {
+                option
+                    .headers
+                    .entry(name.to_string())
+                    .or_insert_with(|| SensitiveString(value.to_string()));
+            }
+        }
+    }
+    option
+}
+
+fn mirror_url(key: &str) -> String {
+    format!(
+        "http://127.0.0.1:{}/providers/{key}",
+        *crate::server::SERVER_PORT
+    )
+}
+
+/// the key of a provider which is already pointed to the internal server
+pub(crate) fn mirrored_key(url: &Url) -> Option<String> {
+    url.as_str()
+        .strip_prefix(&mirror_url(""))
+        .map(str::to_string)
+}
+
+fn rewrite_provider(
+    provider: &mut Mapping,
+    mirror: &ProviderMirror,
+    mode: ProviderMirrorMode,
+    key: &str,
+    interval: u64,
+) {
+    // the mirror is reached directly by the core
+    provider.remove("proxy");
+    provider.remove("header");
+    match mode {
+        ProviderMirrorMode::Server => {
+            provider.insert("url".into(), mirror_url(key).into());
+        }
+        ProviderMirrorMode::File => {
+            provider.remove("url");
+            provider.remove("size-limit");
+            provider.insert("type".into(), "file".into());
+            let path = mirror.path(key).to_string_lossy().to_string();
+            provider.insert("path".into(), path.into());
+        }
+    }
+    // so that the refreshed payload of the mirror is picked up by the core
+    provider.insert("interval".into(), interval.into());
+}
+
+/// download the http providers through the subscription client with the `option` of the profile,
+/// and point them to the local mirrors. The providers which could not be mirrored are kept as is.
+pub async fn use_provider_mirror(
+    mut config: Mapping,
+    mirror: &ProviderMirror,
+    settings: &ProviderMirrorConfig,
+    profile: Option<&str>,
+    option: &RemoteProfileOptions,
+    usage: &mut MirrorUsage,
+) -> (Mapping, Logs) {
+    let mut logs = Logs::new();
+    for kind in [ProviderKind::Proxy, ProviderKind::Rule] {
+        let Some(providers) = config.get_mut(kind.field()).and_then(Value::as_mapping_mut) else {
+            continue;
+        };
+        for (name, provider) in providers.iter_mut() {
+            let (Some(name), Some(provider)) = (name.as_str(), provider.as_mapping_mut()) else {
+                continue;
+            };
+            if provider.get("type").and_then(Value::as_str) != Some("http") {
+                continue;
+            }
+            let Some(url) = provider
+                .get("url")
+                .and_then(Value::as_str)
+                .and_then(|url| Url::parse(url).ok())
+                .filter(|url| matches!(url.scheme(), "http" | "https"))
+            else {
+                continue;
+            };
+            if let Some(key) = mirrored_key(&url) {
+                usage.entry(key).or_default().insert(name.to_string());
+                continue;
+            }
+            let interval = provider
+                .get("interval")
+                .and_then(Value::as_u64)
+                .filter(|interval| *interval > 0)
+                .unwrap_or(settings.default_interval * 60);
+            let entry = MirrorEntry::new(
+                url,
+                kind,
+                profile.map(str::to_string),
+                provider_option(provider, option),
+                interval,
+            );
+            match mirror.mirror(entry).await {
+                Ok(key) => {
+                    rewrite_provider(provider, mirror, settings.mode, &key, interval);
+                    usage.entry(key).or_default().insert(name.to_string());
+                    logs.info(format!(
+                        "{kind} provider `{name}` is served by the local mirror"
+                    ));
+                }
+                Err(err) => {
+                    logs.warn(format!(
+                        "failed to mirror the {kind} provider `{name}`: {err:#}"
+                    ));
+                }
+            }
+        }
+    }
+    (config, logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhance::utils::LogSpan;
+    use axum::{Router, routing::get};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    const PAYLOAD: &str = "payload:\n  - DOMAIN-SUFFIX,example.com\n";
+
+    async fn serve_provider() -> (u16, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/rules.yaml",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { PAYLOAD }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (port, requests)
+    }
+
+    fn config(port: u16) -> Mapping {
+        serde_yaml::from_str(&format!(
+            r#"
+rule-providers:
+  reject:
+    type: http
+    behavior: domain
+    url: http://127.0.0.1:{port}/rules.yaml
+    proxy: Proxy
+  local:
+    type: file
+    behavior: domain
+    path: ./local.yaml
+proxy-providers:
+  missing:
+    type: http
+    url: http://127.0.0.1:{port}/proxies.yaml
+    interval: 3600
+"#
+        ))
+        .unwrap()
+    }
+
+    #[test_log::test(tokio::test(flavor = "multi_thread"))]
+    async fn test_use_provider_mirror() {
+        let (port, requests) = serve_provider().await;
+        let dir = tempfile::tempdir().unwrap();
+        let mirror = ProviderMirror::new(dir.path().to_path_buf()).unwrap();
+        let settings = ProviderMirrorConfig {
+            enable: true,
+            mode: ProviderMirrorMode::File,
+            default_interval: 60,
+        };
+        let mut option = RemoteProfileOptions::default();
+        option.headers.insert(
+            "X-Token".to_string(),
+            SensitiveString("secret-token".to_string()),
+        );
+
+        let mut usage = MirrorUsage::new();
+        let (mapping, logs) =
+            use_provider_mirror(config(port), &mirror, &settings, None, &option, &mut usage).await;
+        let key = ProviderMirror::key(
+            &format!("http://127.0.0.1:{port}/rules.yaml")
+                .parse()
+                .unwrap(),
+        );
+        let reject = &mapping["rule-providers"]["reject"];
+        assert_eq!(reject["type"], Value::from("file"));
+        assert_eq!(
+            reject["path"],
+            Value::from(mirror.path(&key).to_string_lossy().to_string())
+        );
+        assert_eq!(reject["interval"], Value::from(3600));
+        assert!(reject.get("url").is_none());
+        assert!(reject.get("proxy").is_none());
+        assert_eq!(
+            mapping["rule-providers"]["local"]["path"],
+            Value::from("./local.yaml")
+        );
+        assert_eq!(std::fs::read_to_string(mirror.path(&key)).unwrap(), PAYLOAD);
+        // the provider which could not be downloaded is left to the core
+        assert_eq!(
+            mapping["proxy-providers"]["missing"]["type"],
+            Value::from("http")
+        );
+        assert_eq!(
+            logs.iter()
+                .filter(|(span, _)| *span == LogSpan::Warn)
+                .count(),
+            1
+        );
+        assert_eq!(usage.len(), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        // the credentials of the profile are not persisted in the index
+        let index = std::fs::read_to_string(dir.path().join("index.yaml")).unwrap();
+        assert!(!index.contains("secret-token"));
+        let reloaded = ProviderMirror::new(dir.path().to_path_buf()).unwrap();
+        assert!(reloaded.get(&key).unwrap().option.is_none());
+
+        // the cached payload is reused
+        let settings = ProviderMirrorConfig {
+            mode: ProviderMirrorMode::Server,
+            ..settings
+        };
+        let (mapping, _) =
+            use_provider_mirror(config(port), &mirror, &settings, None, &option, &mut usage).await;
+        let reject = &mapping["rule-providers"]["reject"];
+        assert_eq!(reject["type"], Value::from("http"));
+        assert!(
+            reject["url"]
+                .as_str()
+                .unwrap()
+                .ends_with(&format!("/providers/{key}"))
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(mirror.cached_path(&key).is_some());
+
+        // the mirrored providers are kept as is, and still in use
+        let mut usage = MirrorUsage::new();
+        let (remirrored, _) = use_provider_mirror(
+            mapping.clone(),
+            &mirror,
+            &settings,
+            None,
+            &option,
+            &mut usage,
+        )
+        .await;
+        assert_eq!(
+            remirrored["rule-providers"]["reject"],
+            mapping["rule-providers"]["reject"]
+        );
+        assert!(usage.contains_key(&key));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // the providers which are not in use anymore are removed
+        mirror.retain(&MirrorUsage::new()).unwrap();
+        assert!(mirror.cached_path(&key).is_none());
+        assert!(!mirror.path(&key).exists());
+    }
+}
@@ -1,5 +1,8 @@
 use super::runner::{ProcessOutput, Runner, wrap_result};
-use crate::enhance::utils::{LogSpan, Logs, LogsExt};
+use crate::enhance::{
+    utils::{LogSpan, Logs, LogsExt},
+    variables::Variables,
+};
 use anyhow::Context as _;
 use async_trait::async_trait;
 use boa_engine::{
@@ -135,6 +138,16 @@ impl BoaRunner {
         Ok(())
     }
 
+    /// expose the profile variables as the `variables` global
+    pub fn setup_variables(&self, variables: &Variables) -> Result<()> {
+        let ctx = &mut self.ctx.borrow_mut();
+        let variables = serde_json::to_value(variables)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let variables = JsValue::from_json(&variables, ctx)?;
+        ctx.register_global_property(js_string!("variables"), variables, Attribute::all())?;
+        Ok(())
+    }
+
     pub fn get_ctx(&self) -> Rc<RefCell<Context>> {
         self.ctx.clone()
     }
@@ -201,8 +214,14 @@ impl Runner for JSRunner {
         self.process_honey(mapping, &content).await
     }
 
-    async fn process_honey(&self, mapping: Mapping, script: &str) -> ProcessOutput {
+    async fn process_honey_with_variables(
+        &self,
+        mapping: Mapping,
+        script: &str,
+        variables: &Variables,
+    ) -> ProcessOutput {
         let script = wrap_result!(wrap_script_if_not_esm(script));
+        let variables = variables.clone();
         let hash = crate::utils::help::get_uid("script");
         let path = CUSTOM_SCRIPTS_DIR.join(format!("{hash}.mjs"));
         wrap_result!(
@@ -216,6 +235,7 @@ impl Runner for JSRunner {
                 let mut logger = BoaConsoleLogger(Logs::new());
                 let boa_runner = wrap_result!(BoaRunner::try_new(), logger.take());
                 wrap_result!(boa_runner.setup_console(logger), take_console_logs());
+                wrap_result!(boa_runner.setup_variables(&variables), take_console_logs());
                 let config = wrap_result!(
                     serde_json::to_string(&mapping)
                         .map_err(|e| { std::io::Error::new(std::io::ErrorKind::InvalidData, e) }),
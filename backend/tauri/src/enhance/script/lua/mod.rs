@@ -5,7 +5,7 @@ use mlua::prelude::*;
 use parking_lot::Mutex;
 use serde_yaml::{Mapping, Value};
 
-use crate::enhance::{Logs, LogsExt, runner::wrap_result, utils::take_logs};
+use crate::enhance::{Logs, LogsExt, runner::wrap_result, utils::take_logs, variables::Variables};
 
 use super::runner::{ProcessOutput, Runner};
 
@@ -109,7 +109,12 @@ impl Runner for LuaRunner {
         self.process_honey(mapping, &file).await
     }
     // TODO: Keep the order of the dictionary structure in the configuration when processing lua. Because mihomo needs ordered dictionaries for dns policy.
-    async fn process_honey(&self, mapping: Mapping, script: &str) -> ProcessOutput {
+    async fn process_honey_with_variables(
+        &self,
+        mapping: Mapping,
+        script: &str,
+        variables: &Variables,
+    ) -> ProcessOutput {
         let lua = wrap_result!(create_lua_context());
         let logger = Arc::new(Mutex::new(Some(Logs::new())));
         wrap_result!(create_console(&lua, logger.clone()), take_logs(logger));
@@ -124,6 +129,17 @@ impl Runner for LuaRunner {
                 .context("Failed to set config"),
             take_logs(logger)
         );
+        let variables = wrap_result!(
+            lua.to_value(variables)
+                .context("Failed to convert variables to value"),
+            take_logs(logger)
+        );
+        wrap_result!(
+            lua.globals()
+                .set("variables", variables)
+                .context("Failed to set variables"),
+            take_logs(logger)
+        );
         let output = wrap_result!(
             lua.load(script)
                 .eval::<mlua::Value>()
@@ -197,6 +213,26 @@ mod tests {
         assert_eq!(expected, result.unwrap());
     }
 
+    #[test]
+    fn test_process_honey_with_variables() {
+        use super::*;
+        use crate::enhance::runner::Runner;
+
+        let runner = LuaRunner;
+        let variables = serde_yaml::from_str("port: 7890\ngroup: Proxy").unwrap();
+        let script = r#"
+            config["mixed-port"] = variables.port;
+            config["rules"] = {"MATCH," .. variables.group};
+            return config;
+        "#;
+        let (result, _) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(runner.process_honey_with_variables(Mapping::new(), script, &variables));
+        let expected =
+            serde_yaml::from_str::<Mapping>("mixed-port: 7890\nrules:\n- MATCH,Proxy").unwrap();
+        assert_eq!(expected, result.unwrap());
+    }
+
     #[test]
     fn test_correct_original_mapping_order() {
         use super::*;
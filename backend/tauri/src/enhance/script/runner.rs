@@ -4,7 +4,7 @@ use serde_yaml::Mapping;
 use std::collections::HashMap;
 
 use super::{js, lua};
-use crate::enhance::{Logs, ScriptType, ScriptWrapper};
+use crate::enhance::{Logs, ScriptType, ScriptWrapper, variables::Variables};
 
 /// The output of the process function is a tuple of the mapping and the logs.
 /// Although the process fails, the logs should be returned.
@@ -41,7 +41,23 @@ pub trait Runner: Send + Sync {
     /// Honey replacement - use in memory code str to load module and exec it!
     /// It might not be implemented - due to some embeded engine is not support.
     async fn process_honey(&self, mapping: Mapping, script: &str) -> ProcessOutput {
-        tracing::debug!("mapping: {:?}\nscript:{}", mapping, script);
+        self.process_honey_with_variables(mapping, script, &Variables::new())
+            .await
+    }
+
+    /// Same as `process_honey`, and the profile variables are exposed as the `variables` global.
+    async fn process_honey_with_variables(
+        &self,
+        mapping: Mapping,
+        script: &str,
+        variables: &Variables,
+    ) -> ProcessOutput {
+        tracing::debug!(
+            "mapping: {:?}\nscript:{}\nvariables: {:?}",
+            mapping,
+            script,
+            variables
+        );
         unimplemented!()
     }
 }
@@ -72,9 +88,12 @@ impl RunnerManager {
         &mut self,
         script: &ScriptWrapper,
         config: Mapping,
+        variables: &Variables,
     ) -> ProcessOutput {
         let runner = wrap_result!(self.get_or_init_runner(&script.0));
         tracing::debug!("script: {:?}", script);
-        runner.process_honey(config, script.1.as_str()).await
+        runner
+            .process_honey_with_variables(config, script.1.as_str(), variables)
+            .await
     }
 }
@@ -4,7 +4,10 @@ use serde_yaml::Mapping;
 
 use crate::config::profile::{item_type::ProfileUid, profiles::Profiles};
 
-use super::{ChainItem, ChainTypeWrapper, RunnerManager, use_merge};
+use super::{
+    ChainItem, ChainTypeWrapper, RunnerManager, use_merge,
+    variables::{VariableScope, use_variables},
+};
 use parking_lot::Mutex;
 use std::{borrow::Borrow, sync::Arc};
 
@@ -89,26 +92,30 @@ pub fn merge_profiles<T: Borrow<String>>(mappings: IndexMap<T, Mapping>) -> Mapp
 }
 
 /// 处理链
+/// merge 在执行前替换 `${name}` 变量，script 通过全局的 `variables` 读取变量
 pub async fn process_chain(
     mut config: Mapping,
     nodes: &[ChainItem],
+    scope: &VariableScope,
 ) -> (Mapping, IndexMap<ProfileUid, Logs>) {
     let mut result_map = IndexMap::new();
 
     let mut script_runner = RunnerManager::new();
     for item in nodes.iter() {
+        let variables = scope.resolve(&item.variables);
         match &item.data {
             ChainTypeWrapper::Merge(merge) => {
-                let mut logs = vec![];
-                let (res, process_logs) = use_merge(merge, config.clone());
+                let (merge, mut logs) = use_variables(merge.clone(), &variables);
+                let (res, process_logs) = use_merge(&merge, config.clone());
                 config = res.unwrap();
                 logs.extend(process_logs);
                 result_map.insert(item.uid.to_string(), logs);
             }
             ChainTypeWrapper::Script(script) => {
                 let mut logs = vec![];
-                let (res, process_logs) =
-                    script_runner.process_script(script, config.clone()).await;
+                let (res, process_logs) = script_runner
+                    .process_script(script, config.clone(), &variables)
+                    .await;
                 logs.extend(process_logs);
                 // TODO: 修改日记 level 格式？
                 match res {
@@ -148,6 +155,7 @@ mod tests {
             data: ChainTypeWrapper::new_js(
                 "function main(cfg) { cfg.value = 'a'; return cfg; }".to_string(),
             ),
+            variables: Default::default(),
         };
 
         let item_b = ChainItem {
@@ -155,12 +163,14 @@ mod tests {
             data: ChainTypeWrapper::new_js(
                 "function main(cfg) { cfg.value = cfg.value + '_b'; return cfg; }".to_string(),
             ),
+            variables: Default::default(),
         };
 
         let chain = vec![item_a, item_b];
 
         // 执行处理链
-        let (final_config, logs) = process_chain(initial_config, &chain).await;
+        let (final_config, logs) =
+            process_chain(initial_config, &chain, &VariableScope::default()).await;
 
         // 验证最终结果
         assert_eq!(
@@ -173,4 +183,39 @@ mod tests {
         assert!(logs.contains_key("a"), "应该包含 A 的处理日志");
         assert!(logs.contains_key("b"), "应该包含 B 的处理日志");
     }
+
+    #[tokio::test]
+    async fn test_process_chain_variables() {
+        let scope = VariableScope::new(
+            serde_yaml::from_str("port: 7890\ngroup: Proxy").unwrap(),
+            serde_yaml::from_str("port: 7891").unwrap(),
+        );
+        let merge = ChainItem {
+            uid: "merge".to_string(),
+            data: ChainTypeWrapper::new_merge(
+                serde_yaml::from_str("mixed-port: ${port}\nmode: ${mode}").unwrap(),
+            ),
+            variables: serde_yaml::from_str("group: Merge").unwrap(),
+        };
+        let script = ChainItem::to_script(
+            "script",
+            ChainTypeWrapper::new_js(
+                "function main(cfg) { cfg.group = variables.group; return cfg; }".to_string(),
+            ),
+        );
+
+        let (config, logs) = process_chain(Mapping::new(), &[merge, script], &scope).await;
+
+        // 本机覆盖的变量优先
+        assert_eq!(config.get("mixed-port"), Some(&Value::from(7891)));
+        assert_eq!(config.get("mode"), Some(&Value::from("${mode}")));
+        // 脚本只能读到自身作用域的变量
+        assert_eq!(config.get("group"), Some(&Value::from("Proxy")));
+        assert!(
+            logs["merge"]
+                .iter()
+                .any(|(_, msg)| msg.contains("`${mode}`")),
+            "未解析的变量应该记录在日志中"
+        );
+    }
 }
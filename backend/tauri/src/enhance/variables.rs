@@ -0,0 +1,254 @@
+//! `${name}` substitution of the profile variables
+use super::{Logs, LogsExt};
+use indexmap::{IndexMap, IndexSet};
+use serde_yaml::{Mapping, Value};
+
+pub type Variables = IndexMap<String, Value>;
+
+/// The variables visible to a chain
+#[derive(Debug, Default, Clone)]
+pub struct VariableScope {
+    /// the variables of `profiles.yaml` and the profile which the chain belongs to
+    pub base: Variables,
+    /// the per-machine overrides, which take precedence over all the others
+    pub overrides: Variables,
+}
+
+impl VariableScope {
+    pub fn new(base: Variables, overrides: Variables) -> Self {
+        Self { base, overrides }
+    }
+
+    /// a child scope with the variables of a profile
+    pub fn with(&self, variables: &Variables) -> Self {
+        let mut base = self.base.clone();
+        base.extend(variables.clone());
+        Self {
+            base,
+            overrides: self.overrides.clone(),
+        }
+    }
+
+    /// the variables of a chain item, from the lowest precedence to the highest:
+    /// base < item < overrides
+    pub fn resolve(&self, variables: &Variables) -> Variables {
+        let mut resolved = self.base.clone();
+        resolved.extend(variables.clone());
+        resolved.extend(self.overrides.clone());
+        resolved
+    }
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+struct Resolver<'a> {
+    variables: &'a Variables,
+    unresolved: IndexSet<String>,
+    not_scalar: IndexSet<String>,
+}
+
+impl Resolver<'_> {
+    fn value(&mut self, value: Value) -> Value {
+        match value {
+            Value::String(s) => self.string(s),
+            Value::Sequence(seq) => {
+                Value::Sequence(seq.into_iter().map(|v| self.value(v)).collect())
+            }
+            Value::Mapping(map) => Value::Mapping(self.mapping(map)),
+            Value::Tagged(mut tagged) => {
+                tagged.value = self.value(tagged.value);
+                Value::Tagged(tagged)
+            }
+            value => value,
+        }
+    }
+
+    fn mapping(&mut self, mapping: Mapping) -> Mapping {
+        mapping
+            .into_iter()
+            .map(|(k, v)| (self.value(k), self.value(v)))
+            .collect()
+    }
+
+    /// a string which is exactly one reference keeps the type of the variable,
+    /// otherwise the variables are formatted into the string
+    fn string(&mut self, s: String) -> Value {
+        if let Some(name) = s.strip_prefix("${").and_then(|s| s.strip_suffix('}'))
+            && is_variable_name(name)
+        {
+            return match self.variables.get(name) {
+                Some(value) => value.clone(),
+                None => {
+                    self.unresolved.insert(name.to_string());
+                    Value::String(s)
+                }
+            };
+        }
+        if !s.contains("${") {
+            return Value::String(s);
+        }
+
+        let mut output = String::with_capacity(s.len());
+        let mut rest = s.as_str();
+        while let Some(start) = rest.find("${") {
+            // `$${` is an escaped `${`
+            if rest[..start].ends_with('$') {
+                output.push_str(&rest[..start - 1]);
+                output.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find('}') {
+                Some(end) if is_variable_name(&after[..end]) => {
+                    let name = &after[..end];
+                    match self.variables.get(name) {
+                        Some(value) => match scalar_to_string(value) {
+                            Some(value) => output.push_str(&value),
+                            None => {
+                                self.not_scalar.insert(name.to_string());
+                                output.push_str(&rest[start..start + end + 3]);
+                            }
+                        },
+                        None => {
+                            self.unresolved.insert(name.to_string());
+                            output.push_str(&rest[start..start + end + 3]);
+                        }
+                    }
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    output.push_str("${");
+                    rest = after;
+                }
+            }
+        }
+        output.push_str(rest);
+        Value::String(output)
+    }
+}
+
+/// substitute the `${name}` references in the keys and values of the mapping,
+/// the references which could not be resolved are kept as is and reported in the logs
+pub fn use_variables(mapping: Mapping, variables: &Variables) -> (Mapping, Logs) {
+    let mut resolver = Resolver {
+        variables,
+        unresolved: IndexSet::new(),
+        not_scalar: IndexSet::new(),
+    };
+    let mapping = resolver.mapping(mapping);
+    let mut logs = Logs::new();
+    for name in resolver.unresolved {
+        logs.warn(format!("unresolved variable reference `${{{name}}}`"));
+    }
+    for name in resolver.not_scalar {
+        logs.warn(format!(
+            "variable `{name}` is not a scalar, it could not be embedded in a string"
+        ));
+    }
+    (mapping, logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enhance::utils::LogSpan;
+
+    fn variables() -> Variables {
+        serde_yaml::from_str(
+            r#"
+port: 7890
+dns: 1.1.1.1
+group: Proxy
+servers:
+  - 1.1.1.1
+  - 8.8.8.8
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_use_variables() {
+        let merge: Mapping = serde_yaml::from_str(
+            r#"
+mixed-port: ${port}
+dns:
+  nameserver: ${servers}
+  default-nameserver:
+    - ${dns}
+  fallback: ${servers} and more
+rules:
+  - MATCH,${group}
+${group}-key: tcp://${dns}:53
+escaped: $${port}
+unknown: ${missing}
+broken: ${not closed
+"#,
+        )
+        .unwrap();
+        let (mapping, logs) = use_variables(merge, &variables());
+        let expected: Mapping = serde_yaml::from_str(
+            r#"
+mixed-port: 7890
+dns:
+  nameserver:
+    - 1.1.1.1
+    - 8.8.8.8
+  default-nameserver:
+    - 1.1.1.1
+  fallback: ${servers} and more
+rules:
+  - MATCH,Proxy
+Proxy-key: tcp://1.1.1.1:53
+escaped: ${port}
+unknown: ${missing}
+broken: ${not closed
+"#,
+        )
+        .unwrap();
+        assert_eq!(mapping, expected);
+        assert_eq!(
+            logs,
+            vec![
+                (
+                    LogSpan::Warn,
+                    "unresolved variable reference `${missing}`".to_string()
+                ),
+                (
+                    LogSpan::Warn,
+                    "variable `servers` is not a scalar, it could not be embedded in a string"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variable_scope_precedence() {
+        let scope = VariableScope::new(
+            serde_yaml::from_str("a: global\nb: global\nc: global").unwrap(),
+            serde_yaml::from_str("c: machine").unwrap(),
+        );
+        let scope = scope.with(&serde_yaml::from_str("b: profile").unwrap());
+        let resolved = scope.resolve(&serde_yaml::from_str("a: item\nc: item").unwrap());
+        assert_eq!(resolved["a"], Value::from("item"));
+        assert_eq!(resolved["b"], Value::from("profile"));
+        assert_eq!(resolved["c"], Value::from("machine"));
+    }
+}
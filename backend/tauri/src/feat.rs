@@ -4,17 +4,19 @@
 //! - timer 定时器
 //! - cmds 页面调用
 //!
-use std::borrow::Borrow;
+use std::{borrow::Borrow, path::Path};
 
 use crate::{
     config::{
         nyanpasu::NetworkStatisticWidgetConfig,
         profile::{
             builder::ProfileBuilder,
+            bundle::{BundleImportReport, BundleManifest, ProfilesBundle},
             item::{
                 LocalProfileBuilder, MergeProfileBuilder, ProfileSharedBuilder,
                 ScriptProfileBuilder,
             },
+            item_type::{ProfileItemType, ProfileUid},
         },
         *,
     },
@@ -25,6 +27,7 @@ use crate::{
 use anyhow::{Result, bail};
 use handle::Message;
 use nyanpasu_ipc::api::status::CoreState;
+use rust_i18n::t;
 use serde_yaml::{Mapping, Value};
 use tauri::{AppHandle, Manager};
 use tauri_plugin_clipboard_manager::ClipboardExt;
@@ -288,6 +291,13 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             }
         }
 
+        // the config is already regenerated if the tun mode is toggled
+        if (patch.profile_variables.is_some() || patch.provider_mirror.is_some())
+            && tun_mode.is_none()
+        {
+            update_core_config().await?;
+        }
+
         if auto_launch.is_some() {
             sysopt::Sysopt::global().update_launch()?;
         }
@@ -296,6 +306,10 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
             sysopt::Sysopt::global().guard_proxy();
         }
 
+        if patch.network_rules.is_some() {
+            network_rules::NetworkRules::global().check_now();
+        }
+
         if let Some(hotkeys) = patch.hotkeys {
             hotkey::Hotkey::global().update(hotkeys)?;
         }
@@ -356,10 +370,29 @@ pub async fn update_profile<T: Borrow<String>>(
 ) -> Result<()> {
     let uid = uid.borrow();
     let profile_item = Config::profiles().latest().get_item(uid)?.clone();
+    // an explicit update of a url backed merge or script re-pins it to the upstream content
+    if profile_item.source().is_some() {
+        sync_profile_source(uid, true).await?;
+        return Ok(());
+    }
     let is_remote = profile_item.is_remote();
 
     let should_update = if is_remote {
         let mut item = profile_item.as_remote().unwrap().clone();
+        let mut options = item.option.clone();
+        if let Some(opts) = opts.clone() {
+            options.apply(opts);
+        }
+        if options.local_edits == Some(LocalEditsPolicy::MergeItem)
+            && convert_local_edits(uid).await?.is_some()
+        {
+            item = Config::profiles()
+                .latest()
+                .get_item(uid)?
+                .as_remote()
+                .unwrap()
+                .clone();
+        }
 
         item.subscribe(opts).await?;
         let committer = Config::profiles().auto_commit();
@@ -407,6 +440,245 @@ pub async fn update_profile<T: Borrow<String>>(
     Ok(())
 }
 
+/// the local edits of a remote profile against its last fetched upstream content
+pub async fn get_local_edits(uid: &str) -> Result<Option<LocalEdits>> {
+    let item = Config::profiles().latest().get_item(uid)?.clone();
+    let Some(remote) = item.as_remote() else {
+        bail!("profile `{uid}` is not a remote profile");
+    };
+    Ok(remote
+        .local_edits()
+        .await?
+        .filter(|edits| !edits.is_empty()))
+}
+
+/// move the local edits of a remote profile to a generated merge profile at the head of its chain,
+/// and reset the profile file to the upstream content, so the edits survive the updates.
+/// Return the uid of the merge profile, `None` if there are no local edits
+pub async fn convert_local_edits(uid: &str) -> Result<Option<ProfileUid>> {
+    let Some(edits) = get_local_edits(uid).await? else {
+        return Ok(None);
+    };
+    if !edits.removed.is_empty() {
+        log::warn!(
+            target: "app",
+            "the removals of {:?} in profile `{uid}` could not be moved to a merge profile",
+            edits.removed_fields()
+        );
+    }
+    let mut remote = Config::profiles()
+        .latest()
+        .get_item(uid)?
+        .as_remote()
+        .unwrap()
+        .clone();
+
+    let mut shared = ProfileShared::get_default_builder(&ProfileItemType::Merge);
+    shared.name(format!("{} (local edits)", remote.shared.name));
+    let mut builder = MergeProfileBuilder::default();
+    builder.shared(shared);
+    let merge = builder.build()?;
+    merge
+        .write_file(serde_yaml::to_string(&edits.edits)?)
+        .await?;
+    let merge_uid = merge.shared.uid.clone();
+
+    remote.reset_to_upstream().await?;
+    remote.chain.insert(0, merge_uid.clone());
+    {
+        let committer = Config::profiles().auto_commit();
+        let mut profiles = committer.draft();
+        profiles.append_item(merge.into())?;
+        profiles.replace_item(uid.to_string(), remote.into())?;
+    }
+    log::info!(target: "app", "moved the local edits of profile `{uid}` to `{merge_uid}`");
+    Ok(Some(merge_uid))
+}
+
+/// 拉取 merge / script 的远程来源
+/// 仅在 `pin` 为 true 时写入并固定新的内容，否则只记录上游的新版本
+pub async fn sync_profile_source(uid: &str, pin: bool) -> Result<SourceSyncOutcome> {
+    let mut item = Config::profiles().latest().get_item(uid)?.clone();
+    let outcome = item.sync_source(pin).await?;
+    let should_update = {
+        let committer = Config::profiles().auto_commit();
+        let mut profiles = committer.draft();
+        profiles.replace_item(uid.to_string(), item)?;
+        matches!(outcome, SourceSyncOutcome::Updated(_)) && profiles.is_in_use(uid)
+    };
+    if should_update {
+        update_core_config().await?;
+    }
+    Ok(outcome)
+}
+
+/// 更新分组中的所有订阅
+/// 单个订阅失败不会中断其余订阅的更新
+pub async fn update_profile_group(
+    group: &str,
+    opts: Option<RemoteProfileOptionsBuilder>,
+) -> Result<()> {
+    let uids = Config::profiles()
+        .latest()
+        .get_group_items(group)
+        .into_iter()
+        .filter(|item| item.is_remote())
+        .map(|item| item.uid().to_string())
+        .collect::<Vec<_>>();
+    let mut failures = Vec::new();
+    for uid in uids {
+        if let Err(err) = update_profile(&uid, opts.clone()).await {
+            log::error!(target: "app", "failed to update the profile \"{uid}\": {err:?}");
+            failures.push(format!("{uid}: {err}"));
+        }
+    }
+    if !failures.is_empty() {
+        bail!(
+            "failed to update the profiles in group \"{group}\":\n{}",
+            failures.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// 修改profiles的配置
+/// 激活失败时回滚，成功后按配置中断连接
+pub async fn patch_profiles(profiles: ProfilesBuilder) -> Result<()> {
+    Config::profiles().draft().apply(profiles);
+
+    match CoreManager::global().update_config().await {
+        Ok(_) => {
+            handle::Handle::refresh_clash();
+            Config::profiles().apply();
+            Config::profiles().data().save_file()?;
+
+            // Interrupt connections based on configuration
+            let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_profile_change().await;
+
+            Ok(())
+        }
+        Err(err) => {
+            Config::profiles().discard();
+            log::error!(target: "app", "{err:?}");
+            Err(err)
+        }
+    }
+}
+
+/// replace the expired profile in `current` with the fallback one
+pub async fn switch_to_fallback_profile(expired: &str, fallback: &str) -> Result<()> {
+    let current = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles.get_item(fallback)?;
+        let mut current = profiles
+            .get_current()
+            .iter()
+            .filter(|uid| uid.as_str() != expired)
+            .cloned()
+            .collect::<Vec<_>>();
+        if !current.iter().any(|uid| uid == fallback) {
+            current.insert(0, fallback.to_string());
+        }
+        current
+    };
+    log::info!(target: "app", "switch profile `{expired}` to fallback `{fallback}`");
+    let mut builder = ProfilesBuilder::default();
+    builder.current(current);
+    patch_profiles(builder).await?;
+    handle::Handle::refresh_profiles();
+    Ok(())
+}
+
+/// replace `current` with the existing ones of `uids`, through the same path as `patch_profiles_config`,
+/// returns whether the profiles are switched
+pub async fn activate_profiles(uids: &[ProfileUid]) -> Result<bool> {
+    let current = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let current = uids
+            .iter()
+            .filter(|uid| profiles.get_item(uid).is_ok())
+            .cloned()
+            .collect::<Vec<_>>();
+        if current.is_empty() {
+            bail!("none of the profiles {uids:?} exists");
+        }
+        if current == profiles.current {
+            return Ok(false);
+        }
+        current
+    };
+    let mut builder = ProfilesBuilder::default();
+    builder.current(current);
+    patch_profiles(builder).await?;
+    handle::Handle::refresh_profiles();
+    Ok(true)
+}
+
+/// activate the profiles of a schedule rule
+pub async fn apply_profile_schedule(schedule: &ProfileSchedule) -> Result<()> {
+    if !activate_profiles(&schedule.current).await? {
+        return Ok(());
+    }
+    log::info!(target: "app", "activated the profiles of the schedule `{}`: {:?}", schedule.name, schedule.current);
+    handle::Handle::notify_system(
+        &t!("notification.profile_schedule.title"),
+        &t!(
+            "notification.profile_schedule.activated",
+            name = schedule.name
+        ),
+    );
+    Ok(())
+}
+
+/// 导出配置包
+pub fn export_profiles_bundle(path: &Path, with_verge: bool) -> Result<BundleManifest> {
+    let bundle = {
+        let profiles = Config::profiles();
+        let verge = Config::verge();
+        let verge = verge.latest();
+        ProfilesBundle::new(&profiles.latest(), with_verge.then_some(&*verge))?
+    };
+    bundle.write_to(path)?;
+    Ok(bundle.manifest)
+}
+
+/// 导出分组的配置包
+/// 仅包含分组内的配置及其链式配置，不包含应用设置
+pub fn export_profile_group_bundle(group: &str, path: &Path) -> Result<BundleManifest> {
+    let bundle = {
+        let profiles = Config::profiles().latest().group_subset(group)?;
+        ProfilesBundle::new(&profiles, None)?
+    };
+    bundle.write_to(path)?;
+    Ok(bundle.manifest)
+}
+
+/// 导入配置包
+/// UID 冲突的配置会被重新分配 UID
+pub async fn import_profiles_bundle(path: &Path, with_verge: bool) -> Result<BundleImportReport> {
+    let bundle = ProfilesBundle::read_from(path)?;
+    let verge = bundle.verge.clone().filter(|_| with_verge);
+    let (report, activated) = {
+        let committer = Config::profiles().auto_commit();
+        let mut profiles = committer.draft();
+        let was_empty = profiles.get_current().is_empty();
+        let report = profiles.import_bundle(bundle)?;
+        (report, was_empty && !profiles.get_current().is_empty())
+    };
+    log::info!(target: "app", "imported profiles bundle: {report:?}");
+    if let Some(verge) = verge {
+        patch_verge(verge).await?;
+        handle::Handle::refresh_verge();
+    }
+    handle::Handle::refresh_profiles();
+    if activated {
+        update_core_config().await?;
+    }
+    Ok(report)
+}
+
 /// 更新配置
 async fn update_core_config() -> Result<()> {
     match CoreManager::global().update_config().await {
@@ -455,6 +727,25 @@ pub fn copy_clash_env(app_handle: &AppHandle, option: &str) {
     }
 }
 
+/// select the proxy of the group, and interrupt the connections by the configuration
+pub async fn select_proxy(group: &str, name: &str) -> Result<()> {
+    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
+    let previous = ProxiesGuard::global()
+        .read()
+        .inner()
+        .records
+        .get(group)
+        .and_then(|group| group.now.clone());
+    ProxiesGuard::global().select_proxy(group, name).await?;
+
+    let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_proxy_change(
+        group,
+        previous.as_deref(),
+    )
+    .await;
+    Ok(())
+}
+
 pub fn update_proxies_buff(rx: Option<tokio::sync::oneshot::Receiver<()>>) {
     use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
 
@@ -1,8 +1,24 @@
 use crate::{
-    config::{profile::ProfileBuilder, *},
+    config::{
+        profile::{
+            ProfileBuilder,
+            bundle::{BundleImportReport, BundleManifest},
+        },
+        *,
+    },
     core::{
-        logger::Logger, storage::Storage, tasks::jobs::ProfilesJobGuard,
-        updater::ManifestVersionLatest, *,
+        clash::{
+            api::{ClashApi, ClashController},
+            latency::{LatencyHistory, LatencyRecord, LatencyTestOptions, ProxyLatency},
+        },
+        logger::Logger,
+        storage::Storage,
+        tasks::jobs::{ProfileSchedulesJobGuard, ProfilesJobGuard},
+        traffic_history::{
+            TrafficBucket, TrafficDimension, TrafficGranularity, TrafficHistory, TrafficUsage,
+        },
+        updater::ManifestVersionLatest,
+        *,
     },
     enhance::PostProcessingOutput,
     feat,
@@ -22,7 +38,7 @@ use serde_yaml::Mapping;
 use std::{borrow::Cow, collections::VecDeque, path::PathBuf, result::Result as StdResult};
 use storage::{StorageOperationError, WebStorage};
 use sysproxy::Sysproxy;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 // Simplified tray icon handling in extreme cleanup
 
 use tauri_plugin_dialog::{DialogExt, FileDialogBuilder};
@@ -142,7 +158,7 @@ pub async fn import_profile(url: String, option: Option<RemoteProfileOptionsBuil
     if let Some(profile_id) = profile_id {
         let mut builder = ProfilesBuilder::default();
         builder.current(vec![profile_id]);
-        patch_profiles_config(builder).await?;
+        (feat::patch_profiles(builder).await)?;
     }
     Ok(())
 }
@@ -155,7 +171,7 @@ pub async fn create_profile(item: ProfileBuilder, file_data: Option<String>) ->
 
     let is_remote = matches!(&item, ProfileBuilder::Remote(_));
 
-    let profile: Profile = match item {
+    let mut profile: Profile = match item {
         ProfileBuilder::Local(builder) => builder
             .build()
             .context("failed to build local profile")?
@@ -177,11 +193,17 @@ pub async fn create_profile(item: ProfileBuilder, file_data: Option<String>) ->
 
     tracing::info!("created new profile: {:#?}", profile);
 
-    // Save file data for non-remote profiles
-    if let Some(file_data) = file_data
+    // Fetch and pin the content of url backed merge and script profiles
+    if profile.source().is_some() {
+        profile
+            .sync_source(true)
+            .await
+            .context("failed to fetch the profile source")?;
+    } else if let Some(file_data) = file_data
         && !file_data.is_empty()
         && !is_remote
     {
+        // Save file data for non-remote profiles
         profile.save_file(file_data)?;
     }
 
@@ -205,7 +227,7 @@ pub async fn create_profile(item: ProfileBuilder, file_data: Option<String>) ->
     if let Some(profile_id) = profile_id {
         let mut builder = ProfilesBuilder::default();
         builder.current(vec![profile_id]);
-        patch_profiles_config(builder).await?;
+        (feat::patch_profiles(builder).await)?;
     }
 
     Ok(())
@@ -234,6 +256,26 @@ pub async fn update_profile(uid: String, option: Option<RemoteProfileOptionsBuil
     Ok(())
 }
 
+/// the local edits of a remote profile against its last fetched upstream content,
+/// in the merge profile syntax
+#[tauri::command]
+#[specta::specta]
+pub async fn get_profile_local_edits(uid: String) -> Result<Option<String>> {
+    let edits = (feat::get_local_edits(&uid).await)?;
+    Ok(match edits {
+        Some(edits) => Some((serde_yaml::to_string(&edits.edits))?),
+        None => None,
+    })
+}
+
+/// move the local edits of a remote profile to a merge profile in its chain,
+/// return the uid of the merge profile
+#[tauri::command]
+#[specta::specta]
+pub async fn convert_profile_local_edits(uid: String) -> Result<Option<String>> {
+    Ok((feat::convert_local_edits(&uid).await)?)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn delete_profile(uid: String) -> Result {
@@ -256,29 +298,148 @@ pub async fn delete_profile(uid: String) -> Result {
     Ok(())
 }
 
-/// 修改profiles的
+/// update all the remote profiles in the group
 #[tauri::command]
 #[specta::specta]
-pub async fn patch_profiles_config(profiles: ProfilesBuilder) -> Result {
-    Config::profiles().draft().apply(profiles);
+pub async fn update_profile_group(
+    group: String,
+    option: Option<RemoteProfileOptionsBuilder>,
+) -> Result {
+    (feat::update_profile_group(&group, option).await)?;
+    Ok(())
+}
 
-    match CoreManager::global().update_config().await {
-        Ok(_) => {
-            handle::Handle::refresh_clash();
-            Config::profiles().apply();
-            (Config::profiles().data().save_file())?;
+/// delete the profiles with the tag, return the deleted uids
+#[tauri::command]
+#[specta::specta]
+pub async fn delete_profiles_by_tag(tag: String) -> Result<Vec<String>> {
+    let (deleted, should_update) = tokio::task::spawn_blocking(move || {
+        #[allow(clippy::let_and_return)] // a bug in clippy
+        nyanpasu_utils::runtime::block_on_current_thread(async move {
+            let committer = Config::profiles().auto_commit();
+            let x = committer.draft().delete_tagged_items(&tag).await;
+            x
+        })
+    })
+    .await
+    .context("failed to join the task")?
+    .context("failed to delete the profiles")?;
 
-            // Interrupt connections based on configuration
-            let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_profile_change().await;
+    if should_update {
+        (CoreManager::global().update_config().await)?;
+        handle::Handle::refresh_clash();
+    }
+    Ok(deleted)
+}
 
-            Ok(())
-        }
-        Err(err) => {
-            Config::profiles().discard();
-            log::error!(target: "app", "{err:?}");
-            Err(IpcError::from(err))
-        }
+/// export the profiles of the group and their chains into a zip bundle
+#[tauri::command]
+#[specta::specta]
+pub fn export_profile_group(group: String, path: PathBuf) -> Result<BundleManifest> {
+    Ok(feat::export_profile_group_bundle(&group, &path)?)
+}
+
+/// set the chain of all the remote and local profiles in the group
+#[tauri::command]
+#[specta::specta]
+pub async fn set_profile_group_chain(group: String, chain: Vec<String>) -> Result {
+    let updated = {
+        let committer = Config::profiles().auto_commit();
+        (committer.draft().set_group_chain(&group, chain))?
+    };
+    let need_update = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        updated
+            .iter()
+            .any(|uid| profiles.get_current().contains(uid))
+    };
+    if need_update {
+        (CoreManager::global().update_config().await)?;
+        handle::Handle::refresh_clash();
     }
+    Ok(())
+}
+
+/// export the profiles, their files and the portable settings into a zip bundle
+#[tauri::command]
+#[specta::specta]
+pub fn export_profiles_bundle(path: PathBuf, with_verge: bool) -> Result<BundleManifest> {
+    Ok(feat::export_profiles_bundle(&path, with_verge)?)
+}
+
+/// import a zip bundle, the conflicting profiles are imported with new uids
+#[tauri::command]
+#[specta::specta]
+pub async fn import_profiles_bundle(
+    app_handle: AppHandle,
+    path: PathBuf,
+    with_verge: bool,
+) -> Result<BundleImportReport> {
+    let report = (feat::import_profiles_bundle(&path, with_verge).await)?;
+    app_handle
+        .state::<ProfileSchedulesJobGuard>()
+        .write()
+        .refresh();
+    Ok(report)
+}
+
+/// 修改profiles的
+#[tauri::command]
+#[specta::specta]
+pub async fn patch_profiles_config(app_handle: AppHandle, profiles: ProfilesBuilder) -> Result {
+    (feat::patch_profiles(profiles).await)?;
+    app_handle
+        .state::<ProfileSchedulesJobGuard>()
+        .write()
+        .refresh();
+    Ok(())
+}
+
+/// replace the rules to activate the profiles on schedule
+#[tauri::command]
+#[specta::specta]
+pub async fn set_profile_schedules(
+    app_handle: AppHandle,
+    schedules: Vec<ProfileSchedule>,
+) -> Result {
+    {
+        let committer = Config::profiles().auto_commit();
+        (committer.draft().set_schedules(schedules))?;
+    }
+    app_handle
+        .state::<ProfileSchedulesJobGuard>()
+        .write()
+        .refresh();
+    Ok(())
+}
+
+/// the latest decisions of the network rules, the newest first
+#[tauri::command]
+#[specta::specta]
+pub fn get_network_rule_decisions() -> Result<Vec<network_rules::NetworkDecision>> {
+    Ok(network_rules::NetworkRules::global().decisions())
+}
+
+/// evaluate the network rules now, even if the network is not changed
+#[tauri::command]
+#[specta::specta]
+pub fn check_network_rules() -> Result {
+    network_rules::NetworkRules::global().check_now();
+    Ok(())
+}
+
+/// activate the fallback profile instead of an expired subscription
+#[tauri::command]
+#[specta::specta]
+pub async fn switch_to_fallback_profile(uid: String) -> Result {
+    let fallback = Config::verge()
+        .latest()
+        .get_subscription_alert()
+        .fallback_profile
+        .ok_or(anyhow!("fallback profile is not configured"))?;
+    (feat::switch_to_fallback_profile(&uid, &fallback).await)?;
+    Ok(())
 }
 
 /// update profile by uid
@@ -890,6 +1051,140 @@ pub async fn clash_api_get_proxy_delay(
     }
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_get_version() -> Result<clash::api::VersionRes> {
+    Ok((ClashController::current()?.get_version().await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_get_rules() -> Result<clash::api::RulesRes> {
+    Ok((ClashController::current()?.get_rules().await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_get_rule_providers() -> Result<clash::api::ProvidersRulesRes> {
+    Ok((ClashController::current()?.get_providers_rules().await)?)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_update_rule_provider(name: String) -> Result<()> {
+    (ClashController::current()?
+        .update_providers_rules_group(&name)
+        .await)?;
+    Ok(())
+}
+
+/// resolve the domain by the DNS of the core, `record_type` is `A` by default
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_query_dns(
+    name: String,
+    record_type: Option<String>,
+) -> Result<clash::api::DnsQueryRes> {
+    Ok((ClashController::current()?
+        .query_dns(&name, record_type.as_deref())
+        .await)?)
+}
+
+/// reload the runtime config forcibly, the ports and the listeners are reloaded as well
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_force_reload_configs() -> Result<()> {
+    let path = (Config::generate_file(ConfigType::Run))?;
+    let path = (dirs::path_to_str(&path))?;
+    (ClashController::current()?.put_configs(path, true).await)?;
+    clash::selections::restore_in_background();
+    Ok(())
+}
+
+/// restart the core process by the core itself
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_restart() -> Result<()> {
+    (ClashController::current()?.restart().await)?;
+    clash::selections::restore_in_background();
+    Ok(())
+}
+
+/// upgrade the core by the core itself
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_upgrade() -> Result<()> {
+    (ClashController::current()?.upgrade().await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_flush_fakeip_cache() -> Result<()> {
+    (ClashController::current()?.flush_fakeip_cache().await)?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn clash_api_debug_gc() -> Result<()> {
+    (ClashController::current()?.debug_gc().await)?;
+    Ok(())
+}
+
+/// explain which rule the destination matches, and the proxies it goes through
+#[tauri::command]
+#[specta::specta]
+pub async fn explain_rule_match(
+    request: clash::rule_explain::RuleMatchRequest,
+) -> Result<clash::rule_explain::RuleExplanation> {
+    Ok((clash::rule_explain::explain(request).await)?)
+}
+
+/// test the latency of the proxies of the group, or all the proxies if `group` is `None`,
+/// the progress is emitted as `latency-test-event`
+#[tauri::command]
+#[specta::specta]
+pub async fn test_proxies_latency(
+    app_handle: AppHandle,
+    group: Option<String>,
+    options: Option<LatencyTestOptions>,
+) -> Result<Vec<ProxyLatency>> {
+    let controller = (ClashController::current())?;
+    let options = options.unwrap_or_default();
+    let results =
+        (clash::latency::test_latency(&controller, group.as_deref(), &options, |event| {
+            if let Err(err) = app_handle.emit("latency-test-event", event) {
+                log::error!(target: "app", "failed to emit latency-test-event: {err}");
+            }
+        })
+        .await)?;
+    if let Some(history) = app_handle.try_state::<LatencyHistory>() {
+        (history.record(chrono::Utc::now().timestamp(), &results))?;
+    }
+    Ok(results)
+}
+
+/// the median, jitter and loss of the latest latency tests of the proxy, the oldest first
+#[tauri::command]
+#[specta::specta]
+pub fn get_proxy_latency_history(
+    app_handle: AppHandle,
+    proxy: String,
+) -> Result<Vec<LatencyRecord>> {
+    let history = app_handle
+        .try_state::<LatencyHistory>()
+        .context("the latency history is unavailable")?;
+    Ok((history.get(&proxy))?)
+}
+
+/// the latest switches of the auto selection policies, the newest first
+#[tauri::command]
+#[specta::specta]
+pub fn get_auto_select_switches() -> Result<Vec<auto_select::AutoSelectSwitch>> {
+    Ok(auto_select::AutoSelect::global().switches())
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_proxies() -> Result<crate::core::clash::proxies::Proxies> {
@@ -920,13 +1215,7 @@ pub async fn mutate_proxies() -> Result<crate::core::clash::proxies::Proxies> {
 #[tauri::command]
 #[specta::specta]
 pub async fn select_proxy(group: String, name: String) -> Result<()> {
-    use crate::core::clash::proxies::{ProxiesGuard, ProxiesGuardExt};
-    (ProxiesGuard::global().select_proxy(&group, &name).await)?;
-
-    // Interrupt connections based on configuration
-    let _ = crate::core::connection_interruption::ConnectionInterruptionService::on_proxy_change()
-        .await;
-
+    (feat::select_proxy(&group, &name).await)?;
     Ok(())
 }
 
@@ -1141,6 +1430,47 @@ pub async fn get_clash_ws_connections_state(
     Ok(ws_connector.state())
 }
 
+/// the alive connections of the latest `/connections` frame,
+/// the changes afterwards are emitted as `clash-connections-event`
+#[tauri::command]
+#[specta::specta]
+pub async fn get_clash_ws_connections(
+    app_handle: AppHandle,
+) -> Result<Vec<crate::core::clash::ws::TrackedConnection>> {
+    let ws_connector = app_handle.state::<crate::core::clash::ws::ClashConnectionsConnector>();
+    Ok(ws_connector.connections())
+}
+
+/// the traffic buckets of the dimension in `[from, to)`, the timestamps are in seconds
+#[tauri::command]
+#[specta::specta]
+pub fn get_traffic_history(
+    app_handle: AppHandle,
+    dimension: TrafficDimension,
+    granularity: TrafficGranularity,
+    from: u64,
+    to: u64,
+) -> Result<Vec<TrafficBucket>> {
+    let history = app_handle.state::<TrafficHistory>();
+    Ok((history.query(dimension, granularity, from, to))?)
+}
+
+/// the names of the dimension using the most traffic in the last `range` seconds,
+/// e.g. the top hosts of the last 7 days
+#[tauri::command]
+#[specta::specta]
+pub fn get_traffic_top(
+    app_handle: AppHandle,
+    dimension: TrafficDimension,
+    range: u64,
+    limit: usize,
+) -> Result<Vec<TrafficUsage>> {
+    let history = app_handle.state::<TrafficHistory>();
+    let config = Config::verge().latest().get_traffic_history();
+    let now = chrono::Utc::now().timestamp() as u64;
+    Ok((history.top(dimension, now, range, limit, &config))?)
+}
+
 // Updater block
 // NOTE: 自动更新功能现在由 tauri-plugin-updater 直接处理
 // 旧的 UpdateWrapper 和 check_update 已移除，前端应使用 tauri-plugin-updater 的 API
@@ -225,6 +225,20 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_runtime_exists,
         ipc::get_postprocessing_output,
         ipc::clash_api_get_proxy_delay,
+        ipc::clash_api_get_version,
+        ipc::clash_api_get_rules,
+        ipc::clash_api_get_rule_providers,
+        ipc::clash_api_update_rule_provider,
+        ipc::clash_api_query_dns,
+        ipc::clash_api_force_reload_configs,
+        ipc::clash_api_restart,
+        ipc::clash_api_upgrade,
+        ipc::clash_api_flush_fakeip_cache,
+        ipc::clash_api_debug_gc,
+        ipc::explain_rule_match,
+        ipc::test_proxies_latency,
+        ipc::get_proxy_latency_history,
+        ipc::get_auto_select_switches,
         ipc::uwp::invoke_uwp_tool,
         // updater
         ipc::fetch_latest_core_versions,
@@ -251,6 +265,10 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_profiles,
         ipc::enhance_profiles,
         ipc::patch_profiles_config,
+        ipc::set_profile_schedules,
+        ipc::switch_to_fallback_profile,
+        ipc::get_network_rule_decisions,
+        ipc::check_network_rules,
         ipc::view_profile,
         ipc::patch_profile,
         ipc::create_profile,
@@ -258,7 +276,15 @@ pub fn run() -> std::io::Result<()> {
         ipc::reorder_profile,
         ipc::reorder_profiles_by_list,
         ipc::update_profile,
+        ipc::get_profile_local_edits,
+        ipc::convert_profile_local_edits,
         ipc::delete_profile,
+        ipc::update_profile_group,
+        ipc::delete_profiles_by_tag,
+        ipc::export_profile_group,
+        ipc::set_profile_group_chain,
+        ipc::export_profiles_bundle,
+        ipc::import_profiles_bundle,
         ipc::read_profile_file,
         ipc::save_profile_file,
         ipc::save_window_size_state,
@@ -308,6 +334,9 @@ pub fn run() -> std::io::Result<()> {
         ipc::get_core_dir,
         // clash layer
         ipc::get_clash_ws_connections_state,
+        ipc::get_clash_ws_connections,
+        ipc::get_traffic_history,
+        ipc::get_traffic_top,
         // updater layer
     ]);
 
@@ -161,11 +161,39 @@ async fn tray_icon(query: Query<TrayIconReq>) -> Response<Body> {
     response
 }
 
+/// the payloads of the provider mirrors, see [`crate::core::provider_mirror`]
+async fn provider_mirror(axum::extract::Path(key): axum::extract::Path<String>) -> Response<Body> {
+    let path = crate::core::provider_mirror::ProviderMirror::global()
+        .ok()
+        .and_then(|mirror| mirror.cached_path(&key));
+    let payload = match path {
+        Some(path) => tokio::fs::read(path).await,
+        None => Err(std::io::ErrorKind::NotFound.into()),
+    };
+    match payload {
+        Ok(payload) => {
+            let mut response = Response::new(Body::from(payload));
+            response.headers_mut().insert(
+                "content-type",
+                HeaderValue::from_static("application/octet-stream"),
+            );
+            response
+        }
+        Err(e) => {
+            tracing::error!("failed to read provider mirror `{key}`: {e}");
+            let mut response = Response::new(Body::from(e.to_string()));
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            response
+        }
+    }
+}
+
 #[instrument]
 pub async fn run(port: u16) -> std::io::Result<()> {
     let app = Router::new()
         .route("/cache/icon", get(cache_icon))
-        .route("/tray/icon", get(tray_icon));
+        .route("/tray/icon", get(tray_icon))
+        .route("/providers/{key}", get(provider_mirror));
     let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{port}")).await?;
     tracing::debug!(
         "internal http server listening on {}",
@@ -178,6 +178,11 @@ pub fn get_clash_external_port(
 #[instrument(skip(app_handle))]
 pub fn cleanup_processes(app_handle: &AppHandle) {
     let _ = super::resolve::save_window_state(app_handle, true);
+    if let Some(history) = app_handle.try_state::<crate::core::traffic_history::TrafficHistory>()
+        && let Err(e) = history.flush_pending()
+    {
+        log::error!("failed to flush traffic history: {e}");
+    }
     super::resolve::resolve_reset();
     let widget_manager = app_handle.state::<crate::widget::WidgetManager>();
     let _ = nyanpasu_utils::runtime::block_on(async {
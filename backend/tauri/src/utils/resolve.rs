@@ -154,6 +154,15 @@ pub fn resolve_setup(app: &mut App) {
     log::trace!("init clash connection connector");
     log_err!(crate::core::clash::setup(app));
 
+    log::trace!("init traffic history");
+    log_err!(crate::core::traffic_history::setup(app));
+
+    log::trace!("init profiles watcher");
+    log_err!(crate::core::profiles_watcher::setup(app));
+
+    log::trace!("init network rules");
+    log_err!(crate::core::network_rules::setup(app));
+
     log::trace!("init widget manager");
     log_err!(tauri::async_runtime::block_on(async {
         crate::widget::setup(app, {